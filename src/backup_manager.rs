@@ -1,20 +1,223 @@
+// Backups of the boot configuration.
+//
+// Before grublist writes /etc/default/grub (or grublist.cfg) or the custom
+// entry script, the current configuration is copied into a snapshot in
+// /var/lib/grublist/backups: one directory per snapshot, named after its
+// time, holding the settings files, grub.cfg, grubenv and /etc/grub.d under
+// their system paths, and a manifest saying who changed what with each
+// file's checksum. A restore can roll back any of these components. History
+// is unlimited unless a retention policy is set. The single grub*.bak files
+// older versions left next to /etc/default/grub are still listed.
+
+use std::collections::{BTreeSet, HashMap};
 use std::fs;
 use std::io;
+use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
+use serde::{Deserialize, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use crate::default_grub::DefaultGrub;
+use crate::diff::{self, DiffLine};
+use crate::grub_config;
+use crate::grubenv::GrubEnv;
+use crate::paths::Paths;
+use crate::safe_write;
 
-#[derive(Debug, Clone)]
+const MANIFEST: &str = "manifest.json";
+
+/// Part of the boot configuration a backup holds and restores as a whole
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Component {
+    // /etc/default/grub and /etc/default/grub.d/*.cfg
+    #[default]
+    #[serde(rename = "settings")]
+    Settings,
+    #[serde(rename = "grub.cfg")]
+    GrubCfg,
+    #[serde(rename = "grubenv")]
+    Grubenv,
+    // Scripts in /etc/grub.d
+    #[serde(rename = "grub.d")]
+    GrubD,
+}
+
+impl Component {
+    pub const ALL: [Component; 4] = [Component::Settings, Component::GrubCfg, Component::Grubenv, Component::GrubD];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Component::Settings => "settings",
+            Component::GrubCfg => "grub.cfg",
+            Component::Grubenv => "grubenv",
+            Component::GrubD => "grub.d",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    pub fn describe(self, paths: &Paths) -> String {
+        match self {
+            Component::Settings => format!("{} and {}/*.cfg",
+                paths.display(&paths.default_grub()), paths.display(&paths.default_grub_d())),
+            Component::GrubCfg => paths.display(&paths.grub_cfg()),
+            Component::Grubenv => paths.display(&paths.grubenv()),
+            Component::GrubD => format!("{}/*", paths.display(&paths.grub_d())),
+        }
+    }
+
+    // Files of the component that exist now
+    fn files(self, paths: &Paths) -> Vec<PathBuf> {
+        let files = match self {
+            Component::Settings => {
+                let mut files = vec![paths.default_grub()];
+                files.extend(dir_files(&paths.default_grub_d()).into_iter()
+                    .filter(|f| f.extension().is_some_and(|ext| ext == "cfg")));
+                files
+            }
+            Component::GrubCfg => vec![paths.grub_cfg()],
+            Component::Grubenv => vec![paths.grubenv()],
+            Component::GrubD => dir_files(&paths.grub_d()),
+        };
+        files.into_iter().filter(|f| f.is_file()).collect()
+    }
+}
+
+// Regular files in `dir`, sorted
+fn dir_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| entries.flatten().map(|e| e.path()).filter(|p| p.is_file()).collect())
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct BackupInfo {
+    // Snapshot directory, or the file of a legacy backup
     pub path: PathBuf,
     pub size: u64,
+    #[serde(serialize_with = "serialize_time")]
     pub modified: SystemTime,
+    // None for a legacy grub*.bak file
+    pub manifest: Option<Manifest>,
 }
 
-pub fn list_backups() -> Vec<BackupInfo> {
+/// What a snapshot holds and why it was taken
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub id: String,
+    // RFC 3339
+    pub created: String,
+    pub user: String,
+    // The change the snapshot was taken before, e.g. "params set GRUB_TIMEOUT 0"
+    pub action: String,
+    pub changed_keys: Vec<String>,
+    // Components copied completely, restoring one also removes files added
+    // to it since. Empty in snapshots from before bundles.
+    #[serde(default)]
+    pub components: Vec<Component>,
+    pub files: Vec<BackupFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupFile {
+    // Path on the managed system, stored under the same path in the snapshot
+    pub path: String,
+    #[serde(default)]
+    pub component: Component,
+    #[serde(default = "default_mode")]
+    pub mode: u32,
+    pub size: u64,
+    pub sha256: String,
+}
+
+fn default_mode() -> u32 {
+    0o644
+}
+
+impl BackupInfo {
+    /// Components that can be restored from the backup
+    pub fn components(&self) -> Vec<Component> {
+        let Some(manifest) = &self.manifest else {
+            return vec![Component::Settings];
+        };
+        let components: BTreeSet<Component> = manifest.files.iter().map(|f| f.component).collect();
+        components.into_iter().collect()
+    }
+}
+
+/// How many snapshots to keep. A snapshot is pruned once it is neither one
+/// of the newest `keep` nor younger than `keep_days`; no limit by default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Retention {
+    #[serde(default)]
+    pub keep: Option<usize>,
+    #[serde(default)]
+    pub keep_days: Option<u64>,
+}
+
+impl Retention {
+    pub fn load(paths: &Paths) -> Self {
+        fs::read_to_string(paths.backup_dir().join("retention.json"))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, paths: &Paths) -> io::Result<()> {
+        let content = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::create_dir_all(paths.backup_dir())?;
+        let _lock = safe_write::lock(paths)?;
+        safe_write::write(&paths.backup_dir().join("retention.json"), content)
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.keep.is_none() && self.keep_days.is_none()
+    }
+
+    pub fn describe(&self) -> String {
+        match (self.keep, self.keep_days) {
+            (None, None) => "keep all backups".to_string(),
+            (Some(n), None) => format!("keep the newest {}", n),
+            (None, Some(days)) => format!("keep {} days", days),
+            (Some(n), Some(days)) => format!("keep the newest {} and anything from the last {} days", n, days),
+        }
+    }
+}
+
+// RFC 3339 in local time, e.g. "2026-01-15T10:20:30+08:00"
+fn serialize_time<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+    let datetime: chrono::DateTime<chrono::Local> = (*time).into();
+    serializer.serialize_str(&datetime.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+}
+
+/// Snapshots in the backup store and legacy backups, newest first
+pub fn list_backups(paths: &Paths) -> Vec<BackupInfo> {
     let mut backups = Vec::new();
-    let config_dir = Path::new("/etc/default");
-    
-    if let Ok(entries) = fs::read_dir(config_dir) {
+
+    if let Ok(entries) = fs::read_dir(paths.backup_dir()) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(manifest) = fs::read_to_string(path.join(MANIFEST)).ok()
+                .and_then(|content| serde_json::from_str::<Manifest>(&content).ok()) else {
+                continue;
+            };
+            let modified = chrono::DateTime::parse_from_rfc3339(&manifest.created)
+                .map(SystemTime::from)
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            backups.push(BackupInfo {
+                path,
+                size: manifest.files.iter().map(|f| f.size).sum(),
+                modified,
+                manifest: Some(manifest),
+            });
+        }
+    }
+
+    if let Ok(entries) = fs::read_dir(paths.default_dir()) {
         for entry in entries.flatten() {
             let path = entry.path();
             if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
@@ -24,6 +227,7 @@ pub fn list_backups() -> Vec<BackupInfo> {
                             path: path.clone(),
                             size: metadata.len(),
                             modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                            manifest: None,
                         });
                     }
                 }
@@ -31,31 +235,431 @@ pub fn list_backups() -> Vec<BackupInfo> {
         }
     }
     
-    // Sort by modification time (newest first)
-    backups.sort_by(|a, b| b.modified.cmp(&a.modified));
+    // Sort by modification time (newest first), snapshots of the same second by their -N suffix
+    backups.sort_by(|a, b| b.modified.cmp(&a.modified).then_with(|| b.path.cmp(&a.path)));
     backups
 }
 
-pub fn restore_backup(backup_path: &Path) -> io::Result<()> {
-    let target = Path::new("/etc/default/grub");
-    
-    // Create a new backup of current config before restoring
-    if target.exists() {
-        let timestamp = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        let new_backup = format!("/etc/default/grub.pre-restore-{}.bak", timestamp);
-        fs::copy(target, &new_backup)?;
+/// Find a backup by ID, file name or path
+pub fn find_backup(paths: &Paths, name: &str) -> Option<BackupInfo> {
+    list_backups(paths).into_iter().find(|b| {
+        b.manifest.as_ref().is_some_and(|m| m.id == name)
+            || b.path.file_name().and_then(|n| n.to_str()) == Some(name)
+            || paths.display(&b.path) == name
+            || b.path == Path::new(name)
+    })
+}
+
+/// The newest snapshot in the backup store
+pub fn latest(paths: &Paths) -> Option<BackupInfo> {
+    list_backups(paths).into_iter().find(|b| b.manifest.is_some())
+}
+
+/// Content of one file in a backup, checked against the manifest
+pub fn read_file(paths: &Paths, backup_path: &Path, path: &Path) -> io::Result<Vec<u8>> {
+    Version::load(paths, Some(backup_path))?
+        .files.into_iter()
+        .find(|f| f.path == path)
+        .map(|f| f.content)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound,
+            format!("backup has no {}", paths.display(path))))
+}
+
+/// Copy the current configuration into a new snapshot. Call with the lock
+/// held. Prunes older snapshots by the retention policy afterwards.
+pub fn snapshot(paths: &Paths, action: &str, changed_keys: &[String]) -> io::Result<Option<BackupInfo>> {
+    let files: Vec<(Component, PathBuf)> = Component::ALL.into_iter()
+        .flat_map(|component| component.files(paths).into_iter().map(move |file| (component, file)))
+        .collect();
+    if files.is_empty() {
+        return Ok(None);
+    }
+
+    let now = chrono::Local::now();
+    let stamp = now.format("%Y%m%d-%H%M%S").to_string();
+    let store = paths.backup_dir();
+    fs::create_dir_all(&store)?;
+    // Several writes in the same second get -2, -3, ...
+    let (id, dir) = (1..)
+        .map(|n| if n == 1 { stamp.clone() } else { format!("{}-{}", stamp, n) })
+        .map(|id| (id.clone(), store.join(id)))
+        .find(|(_, dir)| !dir.exists())
+        .unwrap_or_default();
+
+    let mut manifest = Manifest {
+        id,
+        created: now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        user: current_user(),
+        action: action.to_string(),
+        changed_keys: changed_keys.to_vec(),
+        components: Vec::new(),
+        files: Vec::new(),
+    };
+    for (component, file) in files {
+        let content = fs::read(&file)?;
+        let system_path = paths.display(&file);
+        let copy = dir.join(system_path.trim_start_matches('/'));
+        if let Some(parent) = copy.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mode = fs::metadata(&file)?.permissions().mode() & 0o7777;
+        safe_write::write_with_mode(&copy, &content, mode)?;
+        if !manifest.components.contains(&component) {
+            manifest.components.push(component);
+        }
+        manifest.files.push(BackupFile {
+            path: system_path,
+            component,
+            mode,
+            size: content.len() as u64,
+            sha256: sha256(&content),
+        });
+    }
+    let content = serde_json::to_string_pretty(&manifest).map_err(io::Error::other)?;
+    safe_write::write(&dir.join(MANIFEST), content)?;
+
+    prune(paths, &Retention::load(paths), false)?;
+    Ok(Some(BackupInfo {
+        path: dir,
+        size: manifest.files.iter().map(|f| f.size).sum(),
+        modified: now.into(),
+        manifest: Some(manifest),
+    }))
+}
+
+/// Remove snapshots the retention policy doesn't keep (only list them with
+/// `dry_run`). The newest snapshot and legacy backups are never pruned.
+pub fn prune(paths: &Paths, retention: &Retention, dry_run: bool) -> io::Result<Vec<BackupInfo>> {
+    if retention.is_unlimited() {
+        return Ok(Vec::new());
+    }
+    let now = SystemTime::now();
+    let snapshots: Vec<BackupInfo> = list_backups(paths).into_iter()
+        .filter(|b| b.manifest.is_some())
+        .collect();
+    let mut pruned = Vec::new();
+    for (idx, backup) in snapshots.into_iter().enumerate() {
+        let age_days = now.duration_since(backup.modified).map(|d| d.as_secs() / 86400).unwrap_or(0);
+        let kept = idx == 0
+            || retention.keep.is_some_and(|n| idx < n)
+            || retention.keep_days.is_some_and(|days| age_days < days);
+        if !kept {
+            if !dry_run {
+                fs::remove_dir_all(&backup.path)?;
+            }
+            pruned.push(backup);
+        }
+    }
+    Ok(pruned)
+}
+
+/// Change to one file between two versions of the configuration
+#[derive(Debug, Clone)]
+pub struct FileDiff {
+    pub path: PathBuf,
+    pub component: Component,
+    // The file only exists in the new / the old version
+    pub created: bool,
+    pub removed: bool,
+    pub diff: Vec<DiffLine>,
+}
+
+/// Changes from `old` to `new`, each a backup or the current configuration
+/// (None). Only the components both hold are compared.
+pub fn compare(paths: &Paths, old: Option<&Path>, new: Option<&Path>) -> io::Result<Vec<FileDiff>> {
+    let (old, new) = (Version::load(paths, old)?, Version::load(paths, new)?);
+    let components: Vec<Component> = Component::ALL.into_iter()
+        .filter(|c| old.holds(*c) && new.holds(*c))
+        .collect();
+    Ok(changes(&old, &new, &components).into_iter().map(Change::into_diff).collect())
+}
+
+/// The files restoring `components` of a backup changes, with their diffs
+pub fn restore_diff(paths: &Paths, backup_path: &Path, components: &[Component]) -> io::Result<Vec<FileDiff>> {
+    Ok(restore_plan(paths, backup_path, components)?.into_iter().map(Change::into_diff).collect())
+}
+
+/// Write `components` of a backup back, after taking a snapshot of the
+/// configuration it replaces
+pub fn restore_backup(paths: &Paths, backup_path: &Path, components: &[Component]) -> io::Result<()> {
+    let _lock = safe_write::lock(paths)?;
+    let changes = restore_plan(paths, backup_path, components)?;
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    let mut changed = BTreeSet::new();
+    for change in changes.iter().filter(|c| c.component == Component::Settings) {
+        changed.extend(changed_keys(&change.old_text(), &change.new_text()));
+    }
+    let changed: Vec<String> = changed.into_iter().collect();
+    let name = backup_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    snapshot(paths, &format!("restore {}", name), &changed)?;
+
+    for change in changes {
+        match change.new {
+            Some(content) => {
+                if let Some(parent) = change.path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                safe_write::write_with_mode(&change.path, content, change.mode)?;
+            }
+            None => fs::remove_file(&change.path)?,
+        }
     }
-    
-    fs::copy(backup_path, target)?;
     Ok(())
 }
 
+fn restore_plan(paths: &Paths, backup_path: &Path, components: &[Component]) -> io::Result<Vec<Change>> {
+    let backup = Version::load(paths, Some(backup_path))?;
+    if let Some(missing) = components.iter().find(|c| !backup.holds(**c)) {
+        return Err(io::Error::new(io::ErrorKind::NotFound,
+            format!("backup has no {}", missing.name())));
+    }
+    Ok(changes(&Version::load(paths, None)?, &backup, components))
+}
+
+// A backup, or the files on the system
+struct Version {
+    // Components with files in the version
+    held: Vec<Component>,
+    // Components copied completely, a file missing from them was removed
+    complete: Vec<Component>,
+    files: Vec<StoredFile>,
+}
+
+struct StoredFile {
+    path: PathBuf,
+    component: Component,
+    mode: u32,
+    content: Vec<u8>,
+}
+
+impl Version {
+    // Content checked against the manifest
+    fn load(paths: &Paths, backup_path: Option<&Path>) -> io::Result<Self> {
+        let Some(backup_path) = backup_path else {
+            let mut files = Vec::new();
+            for component in Component::ALL {
+                for path in component.files(paths) {
+                    let content = fs::read(&path)?;
+                    let mode = fs::metadata(&path)?.permissions().mode() & 0o7777;
+                    files.push(StoredFile { path, component, mode, content });
+                }
+            }
+            return Ok(Version { held: Component::ALL.to_vec(), complete: Component::ALL.to_vec(), files });
+        };
+
+        if !backup_path.is_dir() {
+            // Legacy backups are all of /etc/default/grub
+            let file = StoredFile {
+                path: paths.default_grub(),
+                component: Component::Settings,
+                mode: default_mode(),
+                content: fs::read(backup_path)?,
+            };
+            return Ok(Version { held: vec![Component::Settings], complete: Vec::new(), files: vec![file] });
+        }
+        let manifest: Manifest = serde_json::from_str(&fs::read_to_string(backup_path.join(MANIFEST))?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut files = Vec::new();
+        for file in &manifest.files {
+            let content = fs::read(backup_path.join(file.path.trim_start_matches('/')))?;
+            if sha256(&content) != file.sha256 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                    format!("checksum mismatch for {} in backup {}", file.path, manifest.id)));
+            }
+            files.push(StoredFile {
+                path: paths.resolve(&file.path),
+                component: file.component,
+                mode: file.mode,
+                content,
+            });
+        }
+        let held: BTreeSet<Component> = files.iter().map(|f| f.component).collect();
+        Ok(Version { held: held.into_iter().collect(), complete: manifest.components, files })
+    }
+
+    fn holds(&self, component: Component) -> bool {
+        self.held.contains(&component)
+    }
+
+    fn file(&self, path: &Path) -> Option<&StoredFile> {
+        self.files.iter().find(|f| f.path == path)
+    }
+}
+
+// A file that differs between two versions, None where it doesn't exist
+struct Change {
+    path: PathBuf,
+    component: Component,
+    // Mode of the new file
+    mode: u32,
+    old: Option<Vec<u8>>,
+    new: Option<Vec<u8>>,
+}
+
+impl Change {
+    fn old_text(&self) -> String {
+        String::from_utf8_lossy(self.old.as_deref().unwrap_or_default()).into_owned()
+    }
+
+    fn new_text(&self) -> String {
+        String::from_utf8_lossy(self.new.as_deref().unwrap_or_default()).into_owned()
+    }
+
+    fn into_diff(self) -> FileDiff {
+        FileDiff {
+            diff: diff::diff_lines(&self.old_text(), &self.new_text()),
+            created: self.old.is_none(),
+            removed: self.new.is_none(),
+            path: self.path,
+            component: self.component,
+        }
+    }
+}
+
+// Files of `components` that differ. A file only one version has counts if
+// the component is complete in the version that lacks it.
+fn changes(old: &Version, new: &Version, components: &[Component]) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for component in Component::ALL.iter().filter(|c| components.contains(c)) {
+        let files: BTreeSet<&PathBuf> = old.files.iter().chain(&new.files)
+            .filter(|f| f.component == *component)
+            .map(|f| &f.path)
+            .collect();
+        for path in files {
+            let (old_file, new_file) = (old.file(path), new.file(path));
+            if (old_file.is_none() && !old.complete.contains(component))
+                || (new_file.is_none() && !new.complete.contains(component)) {
+                continue;
+            }
+            let old_content = old_file.map(|f| f.content.clone());
+            let new_content = new_file.map(|f| f.content.clone());
+            if old_content != new_content {
+                changes.push(Change {
+                    path: path.clone(),
+                    component: *component,
+                    mode: new_file.map(|f| f.mode).unwrap_or_else(default_mode),
+                    old: old_content,
+                    new: new_content,
+                });
+            }
+        }
+    }
+    changes
+}
+
 pub fn delete_backup(backup_path: &Path) -> io::Result<()> {
-    fs::remove_file(backup_path)?;
-    Ok(())
+    if backup_path.is_dir() {
+        fs::remove_dir_all(backup_path)
+    } else {
+        fs::remove_file(backup_path)
+    }
+}
+
+/// Settings whose value differs between two versions of a settings file
+pub fn changed_keys(old: &str, new: &str) -> Vec<String> {
+    let (old, new) = (setting_values(old), setting_values(new));
+    changed_keys_of(&old, &new).into_iter().cloned().collect()
+}
+
+fn setting_values(content: &str) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    for assignment in DefaultGrub::parse(content).assignments() {
+        let value = assignment.expanded(&values);
+        values.insert(assignment.key.clone(), value);
+    }
+    values
+}
+
+/// What changed, by setting and variable rather than by line, e.g.
+/// "GRUB_TIMEOUT 5 → 0" or "GRUB_CMDLINE_LINUX_DEFAULT: added nomodeset"
+pub fn summarize(paths: &Paths, diffs: &[FileDiff]) -> Vec<String> {
+    let mut summary = Vec::new();
+    for file in diffs {
+        let path = paths.display(&file.path);
+        if file.created {
+            summary.push(format!("added {}", path));
+        } else if file.removed {
+            summary.push(format!("removed {}", path));
+        }
+        let (old, new) = diff::sides(&file.diff);
+        match file.component {
+            Component::Settings => {
+                let (old, new) = (setting_values(&old), setting_values(&new));
+                for key in changed_keys_of(&old, &new) {
+                    summary.push(describe_change(key, old.get(key).map(String::as_str), new.get(key).map(String::as_str)));
+                }
+            }
+            Component::Grubenv => {
+                let vars = |content: &str| -> HashMap<String, String> {
+                    GrubEnv::parse(content.as_bytes()).map(|env| env.vars.into_iter().collect()).unwrap_or_default()
+                };
+                let (old, new) = (vars(&old), vars(&new));
+                for key in changed_keys_of(&old, &new) {
+                    summary.push(format!("grubenv {}", describe_value(key, old.get(key).map(String::as_str), new.get(key).map(String::as_str))));
+                }
+            }
+            Component::GrubCfg | Component::GrubD if !file.created && !file.removed => {
+                let (added, removed) = diff::count_changes(&file.diff);
+                summary.push(format!("{}: +{} -{} lines", path, added, removed));
+            }
+            _ => {}
+        }
+    }
+    summary
+}
+
+fn changed_keys_of<'a>(old: &'a HashMap<String, String>, new: &'a HashMap<String, String>) -> BTreeSet<&'a String> {
+    old.keys().chain(new.keys())
+        .filter(|key| old.get(*key) != new.get(*key))
+        .collect()
+}
+
+// Kernel command lines by parameter, anything else by value
+fn describe_change(key: &str, old: Option<&str>, new: Option<&str>) -> String {
+    let (Some(old), Some(new)) = (old, new) else {
+        return describe_value(key, old, new);
+    };
+    if !key.starts_with("GRUB_CMDLINE_LINUX") {
+        return describe_value(key, Some(old), Some(new));
+    }
+    let (old, new) = (grub_config::parse_parameters(old), grub_config::parse_parameters(new));
+    let added: Vec<&String> = new.iter().filter(|p| !old.contains(p)).collect();
+    let removed: Vec<&String> = old.iter().filter(|p| !new.contains(p)).collect();
+    let mut parts = Vec::new();
+    if !added.is_empty() {
+        parts.push(format!("added {}", added.iter().map(|p| p.as_str()).collect::<Vec<_>>().join(" ")));
+    }
+    if !removed.is_empty() {
+        parts.push(format!("removed {}", removed.iter().map(|p| p.as_str()).collect::<Vec<_>>().join(" ")));
+    }
+    if parts.is_empty() {
+        // Same parameters in another order
+        parts.push("reordered".to_string());
+    }
+    format!("{}: {}", key, parts.join(", "))
+}
+
+fn describe_value(key: &str, old: Option<&str>, new: Option<&str>) -> String {
+    let show = |value: Option<&str>| match value {
+        None => "(unset)".to_string(),
+        Some("") => "\"\"".to_string(),
+        Some(value) => value.to_string(),
+    };
+    format!("{} {} → {}", key, show(old), show(new))
+}
+
+fn sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+// The user behind sudo if any
+fn current_user() -> String {
+    ["SUDO_USER", "USER", "LOGNAME"].iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_else(|| format!("uid {}", unsafe { libc::getuid() }))
 }
 
 pub fn format_size(bytes: u64) -> String {
@@ -73,7 +677,7 @@ pub fn format_size(bytes: u64) -> String {
 
 pub fn format_time(time: SystemTime) -> String {
     if let Ok(duration) = time.duration_since(SystemTime::UNIX_EPOCH) {
-        use chrono::{DateTime, Local, TimeZone};
+        use chrono::{Local, TimeZone};
         if let Some(datetime) = Local.timestamp_opt(duration.as_secs() as i64, 0).single() {
             return datetime.format("%Y-%m-%d %H:%M:%S").to_string();
         }
@@ -81,3 +685,95 @@ pub fn format_time(time: SystemTime) -> String {
     "Unknown".to_string()
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A configuration tree under the temp directory, removed on drop
+    struct Fixture {
+        paths: Paths,
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let root = std::env::temp_dir().join(format!("grublist-test-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            let paths = Paths::new(root);
+            fs::create_dir_all(paths.default_grub_d()).unwrap();
+            fs::create_dir_all(paths.grub_dir()).unwrap();
+            fs::write(paths.default_grub(), "GRUB_DEFAULT=0\nGRUB_TIMEOUT=5 # seconds\n").unwrap();
+            fs::write(paths.default_grub_d().join("50-cloud.cfg"), "GRUB_TERMINAL=console\n").unwrap();
+            fs::write(paths.grub_cfg(), "menuentry 'Ubuntu' {\n}\n").unwrap();
+            let mut env = GrubEnv::empty(paths.grubenv());
+            env.set("saved_entry", "0");
+            env.save().unwrap();
+            Fixture { paths }
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.paths.root);
+        }
+    }
+
+    #[test]
+    fn snapshot_and_restore() {
+        let fixture = Fixture::new("restore");
+        let paths = &fixture.paths;
+        let backup = snapshot(paths, "test", &["GRUB_TIMEOUT".to_string()]).unwrap().unwrap();
+        let manifest = backup.manifest.as_ref().unwrap();
+        assert_eq!(manifest.components, vec![Component::Settings, Component::GrubCfg, Component::Grubenv]);
+        assert_eq!(manifest.files.len(), 4);
+        assert_eq!(read_file(paths, &backup.path, &paths.default_grub()).unwrap(),
+            b"GRUB_DEFAULT=0\nGRUB_TIMEOUT=5 # seconds\n");
+
+        // Change a setting, remove and add a drop-in, change grub.cfg
+        let original_cfg = fs::read(paths.grub_cfg()).unwrap();
+        fs::write(paths.default_grub(), "GRUB_DEFAULT=0\nGRUB_TIMEOUT=0 # seconds\n").unwrap();
+        fs::remove_file(paths.default_grub_d().join("50-cloud.cfg")).unwrap();
+        fs::write(paths.default_grub_d().join("99-extra.cfg"), "GRUB_GFXMODE=auto\n").unwrap();
+        fs::write(paths.grub_cfg(), "menuentry 'Other' {\n}\n").unwrap();
+
+        let diffs = restore_diff(paths, &backup.path, &[Component::Settings]).unwrap();
+        assert_eq!(diffs.len(), 3);
+        assert!(diffs.iter().any(|d| d.created && d.path.ends_with("50-cloud.cfg")));
+        assert!(diffs.iter().any(|d| d.removed && d.path.ends_with("99-extra.cfg")));
+        assert_eq!(diff::count_changes(&diffs.iter().find(|d| d.path == paths.default_grub()).unwrap().diff), (1, 1));
+
+        restore_backup(paths, &backup.path, &[Component::Settings]).unwrap();
+        assert_eq!(fs::read_to_string(paths.default_grub()).unwrap(), "GRUB_DEFAULT=0\nGRUB_TIMEOUT=5 # seconds\n");
+        assert_eq!(fs::read_to_string(paths.default_grub_d().join("50-cloud.cfg")).unwrap(), "GRUB_TERMINAL=console\n");
+        assert!(!paths.default_grub_d().join("99-extra.cfg").exists());
+        // Components not asked for are left alone
+        assert_eq!(fs::read_to_string(paths.grub_cfg()).unwrap(), "menuentry 'Other' {\n}\n");
+        assert!(restore_diff(paths, &backup.path, &[Component::Settings]).unwrap().is_empty());
+
+        // The restore took a snapshot of what it replaced first
+        let backups = list_backups(paths);
+        assert_eq!(backups.len(), 2);
+        let restore = backups.iter().find(|b| b.path != backup.path).unwrap().manifest.clone().unwrap();
+        assert!(restore.action.starts_with("restore "));
+        assert_eq!(restore.changed_keys, vec!["GRUB_GFXMODE", "GRUB_TERMINAL", "GRUB_TIMEOUT"]);
+
+        restore_backup(paths, &backup.path, &[Component::GrubCfg]).unwrap();
+        assert_eq!(fs::read(paths.grub_cfg()).unwrap(), original_cfg);
+    }
+
+    #[test]
+    fn restore_missing_component() {
+        let fixture = Fixture::new("missing");
+        let paths = &fixture.paths;
+        let backup = snapshot(paths, "test", &[]).unwrap().unwrap();
+        assert!(restore_backup(paths, &backup.path, &[Component::GrubD]).is_err());
+    }
+
+    #[test]
+    fn changed_settings() {
+        let old = "GRUB_TIMEOUT=5\nGRUB_CMDLINE_LINUX=\"quiet\"\nGRUB_CMDLINE_LINUX=\"$GRUB_CMDLINE_LINUX splash\"\n";
+        let new = "GRUB_TIMEOUT=5\nGRUB_CMDLINE_LINUX=\"quiet\"\nGRUB_CMDLINE_LINUX=\"$GRUB_CMDLINE_LINUX\"\nGRUB_DEFAULT=saved\n";
+        assert_eq!(changed_keys(old, new), vec!["GRUB_CMDLINE_LINUX", "GRUB_DEFAULT"]);
+        assert!(changed_keys(old, old).is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+// Kernel parameters for the next boot only (e.g. systemd.unit=rescue.target).
+//
+// /etc/grub.d/43_grublist_once adds a menu entry to grub.cfg that boots the
+// kernel, initrd and command line stored in grubenv, and only exists while
+// they are set. Once that script is part of grub.cfg, a one-time boot only
+// writes those variables and next_entry, like grub-reboot. The entry removes
+// its variables with save_env when it is booted (GRUB clears next_entry
+// itself), so the following boots are normal again.
+//
+// The command line reaches the kernel through an unquoted variable: GRUB
+// splits it at whitespace and the linux loader escapes quotes and
+// backslashes, so parameters containing those are refused.
+
+use std::fs;
+use crate::custom_entries;
+use crate::grub::Entry;
+use crate::grub_config;
+use crate::grubenv::{self, GrubEnv};
+use crate::kernel_params;
+use crate::paths::Paths;
+use crate::safe_write;
+
+/// Menu entry ID selected through next_entry
+pub const ENTRY_ID: &str = "grublist-once";
+
+// grubenv variables read by the entry, the kernel comes first because it
+// decides whether the entry exists
+const VARS: &[&str] = &[
+    "grublist_once_linux",
+    "grublist_once_args",
+    "grublist_once_initrd",
+    "grublist_once_insmod",
+    "grublist_once_root",
+    "grublist_once_uuid",
+];
+
+// grubenv variables are not visible inside a menu entry unless exported
+const SCRIPT: &str = r#"#!/bin/sh
+exec tail -n +3 $0
+# One-time boot entry used by grublist. It only exists while grubenv holds
+# grublist_once_linux, and removes its variables from grubenv when booted.
+if [ -n "${grublist_once_linux}" ]; then
+  export grublist_once_linux
+  export grublist_once_args
+  export grublist_once_initrd
+  export grublist_once_insmod
+  export grublist_once_root
+  export grublist_once_uuid
+  menuentry 'One-time boot (grublist)' --class gnu-linux --id grublist-once {
+	for module in ${grublist_once_insmod}; do
+		insmod ${module}
+	done
+	if [ -n "${grublist_once_root}" ]; then
+		set root="${grublist_once_root}"
+	fi
+	if [ -n "${grublist_once_uuid}" ]; then
+		search --no-floppy --fs-uuid --set=root ${grublist_once_uuid}
+	fi
+	linux ${grublist_once_linux} ${grublist_once_args}
+	if [ -n "${grublist_once_initrd}" ]; then
+		initrd ${grublist_once_initrd}
+	fi
+	# save_env removes unset variables from grubenv
+	unset grublist_once_linux
+	unset grublist_once_args
+	unset grublist_once_initrd
+	unset grublist_once_insmod
+	unset grublist_once_root
+	unset grublist_once_uuid
+	save_env grublist_once_linux grublist_once_args grublist_once_initrd grublist_once_insmod grublist_once_root grublist_once_uuid
+  }
+fi
+"#;
+
+/// A pending one-time boot
+pub struct Pending {
+    pub linux: String,
+    pub params: Vec<String>,
+}
+
+/// The one-time boot set up in grubenv that hasn't happened yet, if any
+pub fn pending(env: &GrubEnv) -> Option<Pending> {
+    let linux = env.get("grublist_once_linux").filter(|v| !v.is_empty())?;
+    Some(Pending {
+        linux: linux.to_string(),
+        params: grub_config::parse_parameters(env.get("grublist_once_args").unwrap_or("")),
+    })
+}
+
+/// Whether grub.cfg already contains the current one-time entry
+pub fn is_generated(paths: &Paths) -> bool {
+    // What the script outputs, everything after `exec tail -n +3 $0`
+    let body = SCRIPT.splitn(3, '\n').nth(2).unwrap_or(SCRIPT);
+    fs::read_to_string(paths.grub_cfg()).is_ok_and(|cfg| cfg.contains(body))
+}
+
+// Parameters GRUB would pass on changed, see the top of this file
+fn check_params(params: &[String]) -> Result<(), String> {
+    match params.iter().find(|p| p.contains(|c: char| c == '"' || c == '\\' || c.is_whitespace())) {
+        Some(param) => Err(format!(
+            "{} can't be passed on for one boot: GRUB splits it at spaces and escapes quotes and backslashes",
+            param)),
+        None => Ok(()),
+    }
+}
+
+/// The entry's kernel command line with `extra` added: `name=value`
+/// replaces the entry's own value, like `grublist params add`
+pub fn command_line(entry: &Entry, extra: &[String]) -> Vec<String> {
+    // GRUB variables such as $vt_handoff would be passed to the kernel
+    // unexpanded from grubenv, leave them out
+    let mut params: Vec<String> = custom_entries::kernel_params(entry).into_iter()
+        .filter(|p| !p.starts_with('$'))
+        .collect();
+    for param in extra {
+        let (name, _) = grub_config::split_parameter(param);
+        let repeatable = kernel_params::lookup(&name).is_some_and(|p| p.repeatable);
+        let count = grub_config::kernel_parameter_count(&params);
+        match params[..count].iter().position(|p| !repeatable && grub_config::split_parameter(p).0 == name) {
+            Some(idx) => params[idx] = param.clone(),
+            None => params.insert(count, param.clone()),
+        }
+    }
+    params
+}
+
+/// Boot `entry` once at the next restart with `extra` kernel parameters.
+/// Installs the grub.d script if needed; returns true when grub.cfg has to
+/// be regenerated before the one-time entry exists.
+pub fn schedule(paths: &Paths, entry: &Entry, extra: &[String]) -> Result<bool, String> {
+    if entry.id.as_deref() == Some(ENTRY_ID) {
+        return Err("The one-time entry can't be booted once itself".to_string());
+    }
+    let Some(linux) = &entry.boot.linux else {
+        return Err(format!("'{}' does not boot a Linux kernel", entry.name));
+    };
+
+    let params = command_line(entry, extra);
+    check_params(&params)?;
+
+    // Also replaces the script of an older grublist
+    let script = paths.boot_once_script();
+    if fs::read_to_string(&script).ok().as_deref() != Some(SCRIPT) {
+        safe_write::lock(paths)
+            .and_then(|_lock| {
+                fs::create_dir_all(paths.grub_d())?;
+                safe_write::write_with_mode(&script, SCRIPT, 0o755)
+            })
+            .map_err(|e| format!("Failed to write {}: {}", paths.display(&script), e))?;
+    }
+
+    let boot = &entry.boot;
+    let values = [
+        linux.clone(),
+        grub_config::join_parameters(&params),
+        boot.initrd.join(" "),
+        boot.insmod.join(" "),
+        boot.root.clone().unwrap_or_default(),
+        boot.search_uuid.clone().unwrap_or_default(),
+    ];
+    grubenv::update(paths, |env| {
+        for (var, value) in VARS.iter().zip(values) {
+            // Unused ones are left out, the block only has 1024 bytes
+            if value.is_empty() {
+                env.unset(var);
+            } else {
+                env.set(var, &value);
+            }
+        }
+        env.set("next_entry", ENTRY_ID);
+    })?;
+    Ok(!is_generated(paths))
+}
+
+/// Forget a pending one-time boot
+pub fn cancel(paths: &Paths) -> Result<(), String> {
+    grubenv::update(paths, |env| {
+        for var in VARS {
+            env.unset(var);
+        }
+        if env.get("next_entry") == Some(ENTRY_ID) {
+            env.unset("next_entry");
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grub;
+
+    fn entry(linux_line: &str) -> Entry {
+        let cfg = format!("menuentry 'Linux' {{\n\tlinux\t{}\n}}\n", linux_line);
+        grub::parse_grub(&cfg, &[]).children.remove(0)
+    }
+
+    fn params(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn extra_parameters() {
+        let linux = entry("/vmlinuz root=UUID=1234 ro quiet loglevel=3 console=tty0 $vt_handoff");
+        // GRUB variables are left out
+        assert_eq!(command_line(&linux, &[]), params("root=UUID=1234 ro quiet loglevel=3 console=tty0"));
+        // A value replaces the entry's own, repeatable parameters are added
+        assert_eq!(
+            command_line(&linux, &params("loglevel=7 console=ttyS0 systemd.unit=rescue.target")),
+            params("root=UUID=1234 ro quiet loglevel=7 console=tty0 console=ttyS0 systemd.unit=rescue.target")
+        );
+    }
+
+    #[test]
+    fn extra_parameters_before_init_arguments() {
+        let linux = entry("/vmlinuz root=/dev/sda2 quiet -- single");
+        assert_eq!(
+            command_line(&linux, &params("debug quiet")),
+            params("root=/dev/sda2 quiet debug -- single")
+        );
+    }
+
+    #[test]
+    fn parameters_grub_would_change() {
+        assert!(check_params(&params("root=UUID=1234 systemd.unit=rescue.target")).is_ok());
+        assert_eq!(
+            check_params(&["dyndbg=\"file foo.c +p\"".to_string()]),
+            Err("dyndbg=\"file foo.c +p\" can't be passed on for one boot: GRUB splits it at spaces and escapes quotes and backslashes".to_string())
+        );
+        assert!(check_params(&["acpi_osi=Windows 2015".to_string()]).is_err());
+        assert!(check_params(&["path=C:\\boot".to_string()]).is_err());
+    }
+}
@@ -1,7 +1,8 @@
 use std::process::Command;
 use regex::Regex;
+use serde::Serialize;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct BootTimeEntry {
     pub kernel_version: String,
     pub boot_time: f64, // in seconds
@@ -112,7 +113,7 @@ fn get_boot_time_for_boot_index(boot_idx: i32) -> f64 {
     // Look for "Startup finished" message in systemd logs
     if let Ok(output) = Command::new("journalctl")
         .arg("-b")
-        .arg(&boot_idx.to_string())
+        .arg(boot_idx.to_string())
         .arg("--no-pager")
         .arg("--grep")
         .arg("Startup finished")
@@ -147,7 +148,7 @@ fn get_kernel_version_for_boot(boot_idx: i32) -> Option<String> {
     // Get kernel version from journal for specific boot
     if let Ok(output) = Command::new("journalctl")
         .arg("-b")
-        .arg(&boot_idx.to_string())
+        .arg(boot_idx.to_string())
         .arg("--no-pager")
         .arg("--grep")
         .arg("Linux version")
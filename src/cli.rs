@@ -0,0 +1,1219 @@
+// Non-interactive command line interface.
+//
+// `grublist [--root DIR] <command> [args]` runs a single action and exits,
+// so everything the TUI does can be scripted (e.g. over ssh). Without a
+// command the TUI starts as before.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use serde::Serialize;
+use crate::backup_manager::{self, BackupInfo};
+use crate::boot_once;
+use crate::boot_time;
+use crate::diff::{self, DiffLine};
+use crate::export;
+use crate::grub::{self, Entry};
+use crate::grub_config::{self, GrubConfig, PendingWrite, SaveTarget};
+use crate::grub_mkconfig;
+use crate::grub_settings;
+use crate::grub_validate;
+use crate::grubenv::{self, GrubEnv};
+use crate::kernel_cleanup;
+use crate::kernel_info;
+use crate::kernel_params;
+use crate::paths::Paths;
+use crate::profiles::Profiles;
+use crate::trial;
+
+pub const EXIT_OK: i32 = 0;
+// The action failed, or a check (validate, show-default) found a problem
+pub const EXIT_FAILURE: i32 = 1;
+// Bad command line
+pub const EXIT_USAGE: i32 = 2;
+
+// Version of the --json output documented in JSON.md. Bumped when a field is
+// renamed, removed or changes meaning; new fields may be added without a bump.
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+pub const USAGE: &str = "\
+Usage: grublist [--root DIR] [--json] [COMMAND]
+
+Without a command the interactive menu is started.
+
+Commands:
+  list                              List boot entries with their path and ID
+  show-default                      Show GRUB_DEFAULT, saved_entry, next_entry and which one wins
+  set-default ENTRY [--saved] [--fragment] [--apply] [--reboot] [--dry-run] [--trial[=N]]
+                                    Set the permanent default (GRUB_DEFAULT, or saved_entry with --saved)
+  boot-once ENTRY [PARAM...] [--apply] [--reboot]
+                                    Boot ENTRY once at the next restart (next_entry),
+                                    with extra kernel parameters if given
+  boot-once --cancel                Remove pending one-time kernel parameters
+  params get [KEY]                  Print one or all effective settings (/etc/default/grub and grub.d)
+  params source KEY                 Print the file that sets KEY
+  params help KEY                   Describe a setting and the values it accepts
+  params set KEY VALUE [--fragment] [--apply] [--dry-run] [--trial[=N]]
+                                    Set a setting
+  params add KEY PARAM... [--fragment] [--apply] [--dry-run] [--trial[=N]] [-- INIT_ARG...]
+                                    Add kernel parameters to KEY (e.g. GRUB_CMDLINE_LINUX_DEFAULT),
+                                    and arguments for init after --
+  params remove KEY NAME... [--fragment] [--apply] [--dry-run] [--trial[=N]]
+                                    Remove kernel parameters from KEY by name
+  profiles list                     List kernel parameter profiles and the active one
+  profiles apply NAME [--fragment] [--apply] [--dry-run] [--trial[=N]]
+                                    Switch to a profile (undoes the active one first)
+  profiles revert [--fragment] [--apply] [--dry-run] [--trial[=N]]
+                                    Undo the active profile
+  backups list                      List backups of the boot configuration, newest first
+  backups restore BACKUP [--only=COMPONENT,...] [--apply] [--dry-run]
+                                    Restore a backup (ID, file name or path); COMPONENT is
+                                    settings, grub.cfg, grubenv or grub.d, all by default
+  backups diff BACKUP [OTHER]       Show what changed since a backup, or from BACKUP to OTHER
+  backups prune [--keep=N] [--keep-days=D] [--dry-run]
+                                    Remove backups outside the retention policy
+                                    (or outside the limits given)
+  backups retention [--keep=N] [--keep-days=D]
+                                    Show or set the retention policy (0 = no limit)
+  kernels list                      List installed kernels
+  kernels clean [VERSION...] [--yes]
+                                    Remove unused kernels (only shows them without --yes)
+  validate                          Check the configuration with grub-mkconfig
+  boot-times                        Show boot time statistics
+  apply                             Regenerate grub.cfg
+  trial status                      Show the running trial and the boots it has left
+  trial start [--boots=N]           Make the latest backup the fallback of a trial
+  trial rollback                    Restore the fallback backup now and end the trial
+  confirm-boot                      Confirm a trial boot (run by grublist-confirm-boot.service)
+  export [FILE]                     Export settings, profiles, custom names and the default
+                                    entry as JSON (to stdout without FILE)
+  import FILE [--map=EXPORTED=ENTRY...] [--fragment] [--apply] [--yes]
+                                    Show what importing an export changes, import it with --yes;
+                                    --map picks the local entry for an exported ID or title
+
+ENTRY is a numeric path (\"1>2\"), an ID path or a title, like GRUB_DEFAULT.
+--fragment saves to /etc/default/grub.d/grublist.cfg instead of /etc/default/grub.
+--apply regenerates grub.cfg afterwards; --reboot restarts the system.
+--dry-run prints the changes to the file as a unified diff without writing it.
+--trial regenerates grub.cfg and restores the previous configuration unless a
+boot is confirmed within N boots (default 2, at most 9).
+--json prints list, show-default, params get, profiles list, backups list,
+kernels, validate and boot-times as JSON (see JSON.md).
+
+Exit status: 0 success, 1 failure, 2 usage error.";
+
+pub struct Args {
+    pub paths: Paths,
+    // Command and its arguments, empty for the TUI
+    pub command: Vec<String>,
+    pub json: bool,
+}
+
+/// Parse global options. Everything from the first non-option word on is
+/// the command and its arguments; `--json` may also follow the command.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let (json, args): (Vec<String>, Vec<String>) = args.into_iter().partition(|a| a == "--json");
+    let mut args = args.into_iter();
+    let mut paths = Paths::default();
+    let mut command = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "--root" {
+            let dir = args.next().ok_or("--root requires a directory")?;
+            paths = Paths::new(dir);
+        } else if let Some(dir) = arg.strip_prefix("--root=") {
+            paths = Paths::new(dir);
+        } else if arg == "-h" || arg == "--help" {
+            command.push("help".to_string());
+            break;
+        } else if arg.starts_with('-') {
+            return Err(format!("Unknown argument: {}", arg));
+        } else {
+            command.push(arg);
+            command.extend(args.by_ref());
+        }
+    }
+    if !paths.root.is_dir() {
+        return Err(format!("Root directory {} does not exist", paths.root.display()));
+    }
+    Ok(Args { paths, command, json: !json.is_empty() })
+}
+
+#[derive(Debug)]
+enum CliError {
+    Usage(String),
+    Failed(String),
+}
+
+impl From<String> for CliError {
+    fn from(e: String) -> Self {
+        CliError::Failed(e)
+    }
+}
+
+type CliResult = Result<i32, CliError>;
+
+/// Run a command and return the process exit code
+pub fn run(paths: &Paths, command: &[String], json: bool) -> i32 {
+    let (name, args) = match command.split_first() {
+        Some((name, args)) => (name.as_str(), args),
+        None => ("help", command),
+    };
+
+    let result = match name {
+        "help" => {
+            println!("{}", USAGE);
+            Ok(EXIT_OK)
+        }
+        "list" => cmd_list(paths, args, json),
+        "show-default" => cmd_show_default(paths, args, json),
+        "params" => cmd_params(paths, args, json),
+        "profiles" => cmd_profiles(paths, args, json),
+        "backups" => cmd_backups(paths, args, json),
+        "kernels" => cmd_kernels(paths, args, json),
+        "validate" => cmd_validate(paths, args, json),
+        "boot-times" => cmd_boot_times(args, json),
+        _ if json => Err(CliError::Usage(format!("--json is not supported by {}", name))),
+        "set-default" => cmd_set_default(paths, args),
+        "boot-once" => cmd_boot_once(paths, args),
+        "apply" => no_args(args).and_then(|_| apply(paths)),
+        "confirm-boot" => no_args(args).and_then(|_| cmd_confirm_boot(paths)),
+        "trial" => cmd_trial(paths, args),
+        "export" => cmd_export(paths, args),
+        "import" => cmd_import(paths, args),
+        _ => Err(CliError::Usage(format!("Unknown command: {}", name))),
+    };
+
+    match result {
+        Ok(code) => code,
+        Err(CliError::Usage(msg)) => {
+            eprintln!("{}", msg);
+            eprintln!();
+            eprintln!("{}", USAGE);
+            EXIT_USAGE
+        }
+        Err(CliError::Failed(msg)) => {
+            eprintln!("Error: {}", msg);
+            EXIT_FAILURE
+        }
+    }
+}
+
+// Split command arguments into flags (from `allowed`) and positional words.
+// A bare `--` and everything after it are positional, so kernel parameters
+// can be followed by init arguments (`params add KEY -- single`).
+fn split_flags<'a>(args: &'a [String], allowed: &[&str]) -> Result<(Vec<&'a str>, Vec<&'a str>), CliError> {
+    let mut flags = Vec::new();
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            positional.push(arg.as_str());
+            positional.extend(args.map(|a| a.as_str()));
+            break;
+        }
+        if arg.starts_with("--") {
+            // "--keep=" in `allowed` accepts --keep=VALUE
+            let name = match arg.find('=') {
+                Some(idx) => &arg[..=idx],
+                None => arg.as_str(),
+            };
+            if !allowed.contains(&name) {
+                return Err(CliError::Usage(format!("Unknown option: {}", arg)));
+            }
+            flags.push(arg.as_str());
+        } else {
+            positional.push(arg.as_str());
+        }
+    }
+    Ok((flags, positional))
+}
+
+// Value of a --name=VALUE flag, `name` given as "--name="
+fn flag_value<'a>(flags: &[&'a str], name: &str) -> Option<&'a str> {
+    flags.iter().rev().find_map(|flag| flag.strip_prefix(name))
+}
+
+fn save_target(flags: &[&str]) -> SaveTarget {
+    if flags.contains(&"--fragment") {
+        SaveTarget::Fragment
+    } else {
+        SaveTarget::Main
+    }
+}
+
+// --dry-run: show what would be written instead of writing it
+fn print_write(paths: &Paths, target: SaveTarget, write: Option<&PendingWrite>) {
+    match write {
+        Some(write) => {
+            let path = paths.display(&write.path);
+            let old = if write.old.is_some() { path.clone() } else { "/dev/null".to_string() };
+            print_diff(&old, &path, &write.diff());
+        }
+        None => println!("No changes to {}", paths.display(&target.path(paths))),
+    }
+}
+
+fn print_diff(old: &str, new: &str, diff: &[DiffLine]) {
+    println!("--- {}", old);
+    println!("+++ {}", new);
+    for line in diff::unified(diff, 3) {
+        println!("{}", line);
+    }
+}
+
+#[derive(Serialize)]
+struct JsonOutput<'a, T: Serialize> {
+    schema_version: u32,
+    command: &'a str,
+    data: T,
+}
+
+fn print_json<T: Serialize>(command: &str, data: T) -> Result<(), CliError> {
+    let output = JsonOutput { schema_version: JSON_SCHEMA_VERSION, command, data };
+    let text = serde_json::to_string_pretty(&output)
+        .map_err(|e| CliError::Failed(format!("Failed to serialize JSON: {}", e)))?;
+    println!("{}", text);
+    Ok(())
+}
+
+fn no_args(args: &[String]) -> Result<(), CliError> {
+    match args.first() {
+        Some(arg) => Err(CliError::Usage(format!("Unexpected argument: {}", arg))),
+        None => Ok(()),
+    }
+}
+
+fn load_menu(paths: &Paths) -> Result<Entry, CliError> {
+    grub::load_grub(paths).ok_or_else(|| {
+        CliError::Failed(format!("Could not read {}", paths.display(&paths.grub_cfg())))
+    })
+}
+
+fn path_string(path: &[usize]) -> String {
+    path.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(">")
+}
+
+// Resolve an ENTRY argument to a boot entry (not a submenu). Returns the
+// numeric path and the value to store: the ID path when every level has one.
+fn resolve_entry(menu: &Entry, spec: &str) -> Result<(Vec<usize>, String), CliError> {
+    let path = grub::resolve_entry_path(menu, spec)
+        .ok_or_else(|| CliError::Failed(format!("No boot entry matches '{}'", spec)))?;
+    let entry = grub::get_entry(menu, &path);
+    if entry.entry_type != grub::EntryType::MenuEntry {
+        return Err(CliError::Failed(format!("'{}' is a submenu, not a boot entry", entry.name)));
+    }
+    let value = grub::entry_id_path(menu, &path).unwrap_or_else(|| path_string(&path));
+    Ok((path, value))
+}
+
+fn cmd_list(paths: &Paths, args: &[String], json: bool) -> CliResult {
+    no_args(args)?;
+    let menu = load_menu(paths)?;
+    let config = GrubConfig::load(paths).ok();
+    let env = GrubEnv::load(paths).unwrap_or_else(|_| GrubEnv::empty(paths.grubenv()));
+    let default_path = config
+        .map(|c| grubenv::effective_default(&c.grub_default, &env).1)
+        .and_then(|spec| grub_validate::resolve_default(&menu, &spec));
+
+    if json {
+        #[derive(Serialize)]
+        struct ListData<'a> {
+            grub_cfg: String,
+            default_path: Option<String>,
+            entries: &'a [Entry],
+        }
+        print_json("list", ListData {
+            grub_cfg: paths.display(&paths.grub_cfg()),
+            default_path: default_path.as_deref().map(path_string),
+            entries: &menu.children,
+        })?;
+        return Ok(EXIT_OK);
+    }
+
+    fn print_entries(entry: &Entry, path: &mut Vec<usize>, default: Option<&[usize]>) {
+        for (i, child) in entry.children.iter().enumerate() {
+            path.push(i);
+            let marker = if default == Some(path.as_slice()) { "*" } else { " " };
+            let indent = "  ".repeat(path.len() - 1);
+            match &child.id {
+                Some(id) => println!("{} {:<8} {}{}  [{}]", marker, path_string(path), indent, child.name, id),
+                None => println!("{} {:<8} {}{}", marker, path_string(path), indent, child.name),
+            }
+            print_entries(child, path, default);
+            path.pop();
+        }
+    }
+
+    print_entries(&menu, &mut Vec::new(), default_path.as_deref());
+    Ok(EXIT_OK)
+}
+
+fn cmd_show_default(paths: &Paths, args: &[String], json: bool) -> CliResult {
+    no_args(args)?;
+    let menu = load_menu(paths)?;
+    let config = GrubConfig::load(paths)?;
+    let env = GrubEnv::load(paths)?;
+    let (source, effective) = grubenv::effective_default(&config.grub_default, &env);
+    let resolved = grub_validate::resolve_default(&menu, &effective);
+    let resolved_name = resolved.as_deref().and_then(|path| grub::try_get_entry(&menu, path)).map(|e| e.name.clone());
+    let code = if resolved.is_some() { EXIT_OK } else { EXIT_FAILURE };
+
+    if json {
+        #[derive(Serialize)]
+        struct DefaultData<'a> {
+            grub_default: &'a str,
+            saved_entry: Option<&'a str>,
+            next_entry: Option<&'a str>,
+            grub_savedefault: Option<&'a str>,
+            source: grubenv::DefaultSource,
+            effective: &'a str,
+            entry_path: Option<String>,
+            entry_name: Option<String>,
+            one_time_params: Option<Vec<String>>,
+        }
+        print_json("show-default", DefaultData {
+            grub_default: &config.grub_default,
+            saved_entry: env.get("saved_entry"),
+            next_entry: env.get("next_entry"),
+            grub_savedefault: config.get("GRUB_SAVEDEFAULT").map(|s| s.as_str()),
+            source,
+            effective: &effective,
+            entry_path: resolved.as_deref().map(path_string),
+            entry_name: resolved_name,
+            one_time_params: boot_once::pending(&env).map(|once| once.params),
+        })?;
+        return Ok(code);
+    }
+
+    println!("GRUB_DEFAULT: {}", config.grub_default);
+    println!("saved_entry:  {}", env.get("saved_entry").unwrap_or("(unset)"));
+    println!("next_entry:   {}", env.get("next_entry").unwrap_or("(unset)"));
+    if let Some(once) = boot_once::pending(&env) {
+        println!("one-time:     linux {} {}", once.linux, grub_config::join_parameters(&once.params));
+    }
+    if let Some(v) = config.get("GRUB_SAVEDEFAULT") {
+        println!("GRUB_SAVEDEFAULT: {}", v);
+    }
+    println!();
+    println!("Chosen by:    {}", source.label());
+    match resolved_name {
+        Some(name) => println!("Next boot:    {} ({})", name, effective),
+        None => println!("Next boot:    {} (no matching entry, GRUB falls back to the first one)", effective),
+    }
+    if grub_validate::is_old_grub_default_format(&config.grub_default) {
+        println!("Note: GRUB_DEFAULT is an old-style title, grub-mkconfig warns about it; set-default stores the ID path instead");
+    }
+    Ok(code)
+}
+
+fn cmd_set_default(paths: &Paths, args: &[String]) -> CliResult {
+    let (flags, positional) = split_flags(args, &["--saved", "--fragment", "--apply", "--reboot", "--dry-run", "--trial", "--trial="])?;
+    let [spec] = positional[..] else {
+        return Err(CliError::Usage("set-default takes exactly one ENTRY".to_string()));
+    };
+    let trial = trial_boots(paths, &flags)?;
+    let menu = load_menu(paths)?;
+    let (path, value) = resolve_entry(&menu, spec)?;
+    let name = &grub::get_entry(&menu, &path).name;
+    let mut config = GrubConfig::load(paths)?;
+    let target = save_target(&flags);
+    let saved = flags.contains(&"--saved");
+
+    if !saved {
+        config.set("GRUB_DEFAULT", value.clone());
+    } else if !grubenv::is_saved(&config.grub_default) {
+        // saved_entry is only read by grub.cfg generated with GRUB_DEFAULT=saved
+        config.set("GRUB_DEFAULT", "saved".to_string());
+    }
+    let write = config.prepare_save(target)?;
+    if flags.contains(&"--dry-run") {
+        if saved {
+            println!("Would set saved_entry={} ({}) in {}", value, name, paths.display(&paths.grubenv()));
+        }
+        print_write(paths, target, write.as_ref());
+        return Ok(EXIT_OK);
+    }
+    if trial.is_some() && write.is_none() {
+        return Err(CliError::Failed("--trial needs a change to the settings files, and none would change".to_string()));
+    }
+    let before = latest_backup(paths);
+
+    // Settings first, so their backup still has the old saved_entry
+    if let Some(write) = &write {
+        write.write(&format!("set-default {}", spec))?;
+    }
+    if saved {
+        grubenv::set_saved_entry(paths, &value)?;
+        println!("saved_entry={} ({})", value, name);
+    }
+    if !saved {
+        println!("GRUB_DEFAULT=\"{}\" ({})", value, name);
+    } else if write.is_some() {
+        println!("GRUB_DEFAULT=saved");
+    }
+
+    if let Some(v) = config.get("GRUB_SAVEDEFAULT").filter(|v| v.trim_matches('"') == "true") {
+        println!("Note: GRUB_SAVEDEFAULT={}, choosing another entry at boot replaces saved_entry", v);
+    }
+
+    // Whether grub.cfg has to be regenerated for the change to take effect.
+    // Rebooting into a stale grub.cfg would not use the new default.
+    let needs_apply = !saved || write.is_some();
+    let apply_now = flags.contains(&"--apply") || flags.contains(&"--reboot");
+    match trial {
+        Some(boots) => start_trial(paths, boots, before)?,
+        None => finish(paths, needs_apply && apply_now, needs_apply && !apply_now)?,
+    }
+    if flags.contains(&"--reboot") {
+        reboot(paths)?;
+    }
+    Ok(EXIT_OK)
+}
+
+fn cmd_boot_once(paths: &Paths, args: &[String]) -> CliResult {
+    let (flags, positional) = split_flags(args, &["--reboot", "--apply", "--cancel"])?;
+    if flags.contains(&"--cancel") {
+        if let Some(arg) = positional.first() {
+            return Err(CliError::Usage(format!("Unexpected argument: {}", arg)));
+        }
+        boot_once::cancel(paths)?;
+        println!("One-time kernel parameters removed");
+        return Ok(EXIT_OK);
+    }
+    let Some((&spec, extra)) = positional.split_first() else {
+        return Err(CliError::Usage("boot-once requires an ENTRY".to_string()));
+    };
+    let menu = load_menu(paths)?;
+    let (path, value) = resolve_entry(&menu, spec)?;
+    let entry = grub::get_entry(&menu, &path);
+
+    if extra.is_empty() {
+        grubenv::set_next_entry(paths, &value)?;
+        println!("next_entry={} ({})", value, entry.name);
+    } else {
+        // Without the one-time entry in grub.cfg GRUB would fall back to the first entry
+        if !boot_once::is_generated(paths) && !flags.contains(&"--apply") {
+            return Err(CliError::Failed(
+                "grub.cfg has no one-time boot entry yet, add --apply to regenerate it".to_string()));
+        }
+        let extra: Vec<String> = extra.iter()
+            .map(|p| {
+                let (name, value) = grub_config::split_parameter(p);
+                grub_config::format_parameter(&name, value.as_deref())
+            })
+            .collect();
+        let needs_apply = boot_once::schedule(paths, entry, &extra)?;
+        if needs_apply {
+            if let Err(e) = apply(paths) {
+                let _ = boot_once::cancel(paths);
+                return Err(e);
+            }
+        }
+        println!("next_entry={} ({} with one-time parameters)", boot_once::ENTRY_ID, entry.name);
+        println!("linux {} {}", entry.boot.linux.as_deref().unwrap_or(""),
+                 grub_config::join_parameters(&boot_once::command_line(entry, &extra)));
+    }
+
+    if flags.contains(&"--reboot") {
+        reboot(paths)?;
+    }
+    Ok(EXIT_OK)
+}
+
+fn cmd_params(paths: &Paths, args: &[String], json: bool) -> CliResult {
+    let (flags, positional) = split_flags(args, &["--fragment", "--apply", "--dry-run", "--trial", "--trial="])?;
+    let Some((&action, rest)) = positional.split_first() else {
+        return Err(CliError::Usage("params requires get, set, add or remove".to_string()));
+    };
+    let trial = trial_boots(paths, &flags)?;
+    let mut config = GrubConfig::load(paths)?;
+
+    match (action, rest) {
+        ("get", []) => {
+            let params: BTreeMap<_, _> = config.get_all_params().iter().collect();
+            if json {
+                print_json("params get", params)?;
+            } else {
+                for (key, value) in params {
+                    println!("{}={}", key, value);
+                }
+            }
+            return Ok(EXIT_OK);
+        }
+        ("get", [key]) => {
+            let value = config.get(key)
+                .ok_or_else(|| CliError::Failed(format!("{} is not set", key)))?;
+            if json {
+                print_json("params get", BTreeMap::from([(key, value)]))?;
+            } else {
+                println!("{}", value);
+            }
+            return Ok(EXIT_OK);
+        }
+        _ if json => {
+            return Err(CliError::Usage("--json is only supported by params get".to_string()));
+        }
+        ("help", [key]) => {
+            let setting = grub_settings::lookup(key)
+                .ok_or_else(|| CliError::Failed(format!("{} is not a known GRUB setting", key)))?;
+            println!("{} ({})", setting.key, setting.value_type.describe());
+            println!("{}", setting.help);
+            return Ok(EXIT_OK);
+        }
+        ("source", [key]) => {
+            let source = config.source(key)
+                .ok_or_else(|| CliError::Failed(format!("{} is not set in any file", key)))?;
+            println!("{}", paths.display(source));
+            return Ok(EXIT_OK);
+        }
+        ("set", [key, value]) => {
+            config.set(key, value.to_string());
+        }
+        ("add", [key, new_params @ ..]) if !new_params.is_empty() => {
+            let mut params = grub_config::parse_parameters(config.get(key).map(|s| s.as_str()).unwrap_or(""));
+            let (kernel_args, init_args) = match new_params.iter().position(|p| *p == "--") {
+                Some(idx) => (&new_params[..idx], &new_params[idx + 1..]),
+                None => (new_params, &[][..]),
+            };
+            for new_param in kernel_args {
+                // Quote values given with spaces (`'dyndbg=file foo.c +p'`)
+                let (name, value) = grub_config::split_parameter(new_param);
+                let new_param = grub_config::format_parameter(&name, value.as_deref());
+                let count = grub_config::kernel_parameter_count(&params);
+                // Adding `name=value` replaces an existing `name`, except for
+                // parameters that may be given several times (console=)
+                let repeatable = kernel_params::lookup(&name).is_some_and(|p| p.repeatable);
+                let existing = params[..count].iter().position(|p| {
+                    let (other, other_value) = grub_config::split_parameter(p);
+                    other == name && (!repeatable || other_value == value)
+                });
+                match existing {
+                    Some(idx) => params[idx] = new_param,
+                    None => params.insert(count, new_param),
+                }
+            }
+            // Arguments for init go after the kernel's, behind a single `--`
+            if new_params.contains(&"--") && grub_config::kernel_parameter_count(&params) == params.len() {
+                params.push("--".to_string());
+            }
+            for arg in init_args {
+                let (name, value) = grub_config::split_parameter(arg);
+                let arg = grub_config::format_parameter(&name, value.as_deref());
+                if !params[grub_config::kernel_parameter_count(&params)..].contains(&arg) {
+                    params.push(arg);
+                }
+            }
+            config.set(key, grub_config::join_parameters(&params));
+        }
+        ("remove", [key, names @ ..]) if !names.is_empty() => {
+            let mut params = grub_config::parse_parameters(config.get(key).map(|s| s.as_str()).unwrap_or(""));
+            for arg in names {
+                // `name` removes every occurrence, `name=value` only that one
+                let (name, value) = grub_config::split_parameter(arg);
+                let before = params.len();
+                let init_args = params.split_off(grub_config::kernel_parameter_count(&params));
+                params.retain(|p| {
+                    let (other, other_value) = grub_config::split_parameter(p);
+                    other != name || value.as_ref().is_some_and(|v| other_value.as_ref() != Some(v))
+                });
+                params.extend(init_args);
+                if params.len() == before {
+                    return Err(CliError::Failed(format!("{} has no parameter '{}'", key, arg)));
+                }
+            }
+            config.set(key, grub_config::join_parameters(&params));
+        }
+        _ => {
+            return Err(CliError::Usage(format!("Invalid params command: {}", positional.join(" "))));
+        }
+    }
+
+    let dry_run = flags.contains(&"--dry-run");
+    let before = latest_backup(paths);
+    if dry_run {
+        let target = save_target(&flags);
+        print_write(paths, target, config.prepare_save(target)?.as_ref());
+    } else {
+        config.save_to(save_target(&flags), &format!("params {}", positional.join(" ")))?;
+    }
+    if let Some(key) = rest.first() {
+        let value = config.get(key).map(|s| s.as_str()).unwrap_or("");
+        if !dry_run {
+            println!("{}={}", key, value);
+        }
+        if key.starts_with("GRUB_CMDLINE_LINUX") {
+            for warning in kernel_params::check(&grub_config::parse_parameters(value)) {
+                eprintln!("Warning: {}", warning);
+            }
+        }
+    }
+    if dry_run {
+        return Ok(EXIT_OK);
+    }
+    match trial {
+        Some(boots) => start_trial(paths, boots, before)?,
+        None => finish(paths, flags.contains(&"--apply"), !flags.contains(&"--apply"))?,
+    }
+    Ok(EXIT_OK)
+}
+
+fn cmd_profiles(paths: &Paths, args: &[String], json: bool) -> CliResult {
+    let (flags, positional) = split_flags(args, &["--fragment", "--apply", "--dry-run", "--trial", "--trial="])?;
+    let mut profiles = Profiles::load(paths)?;
+    let name = match positional[..] {
+        ["list"] if json => {
+            print_json("profiles list", &profiles)?;
+            return Ok(EXIT_OK);
+        }
+        ["list"] => {
+            let active = profiles.active.as_ref().map(|a| a.name.as_str());
+            for profile in &profiles.profiles {
+                let mut diff: Vec<String> = profile.remove.iter().map(|p| format!("-{}", p)).collect();
+                diff.extend(profile.add.iter().map(|p| format!("+{}", p)));
+                println!("{} {:<12} {}: {}",
+                         if active == Some(profile.name.as_str()) { "*" } else { " " },
+                         profile.name,
+                         profile.key,
+                         diff.join(" "));
+                if !profile.description.is_empty() {
+                    println!("               {}", profile.description);
+                }
+            }
+            return Ok(EXIT_OK);
+        }
+        _ if json => {
+            return Err(CliError::Usage("--json is only supported by profiles list".to_string()));
+        }
+        ["apply", name] => Some(name),
+        ["revert"] => {
+            if profiles.active.is_none() {
+                return Err(CliError::Failed("No profile is active".to_string()));
+            }
+            None
+        }
+        _ => {
+            return Err(CliError::Usage(format!("Invalid profiles command: {}", positional.join(" "))));
+        }
+    };
+
+    let trial = trial_boots(paths, &flags)?;
+    let mut config = GrubConfig::load(paths)?;
+    if flags.contains(&"--dry-run") {
+        let (changes, _) = profiles.stage(&mut config, name)?;
+        for change in changes {
+            println!("{}", change);
+        }
+        let target = save_target(&flags);
+        print_write(paths, target, config.prepare_save(target)?.as_ref());
+        return Ok(EXIT_OK);
+    }
+    let before = latest_backup(paths);
+    for change in profiles.switch(paths, &mut config, name, save_target(&flags))? {
+        println!("{}", change);
+    }
+    match trial {
+        Some(boots) => start_trial(paths, boots, before)?,
+        None => finish(paths, flags.contains(&"--apply"), !flags.contains(&"--apply"))?,
+    }
+    Ok(EXIT_OK)
+}
+
+fn cmd_backups(paths: &Paths, args: &[String], json: bool) -> CliResult {
+    let (flags, positional) = split_flags(args, &["--apply", "--dry-run", "--only=", "--keep=", "--keep-days="])?;
+    match positional[..] {
+        ["list"] if json => {
+            print_json("backups list", backup_manager::list_backups(paths))?;
+            Ok(EXIT_OK)
+        }
+        ["list"] => {
+            for backup in backup_manager::list_backups(paths) {
+                let name = backup.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                let details = match &backup.manifest {
+                    Some(manifest) if manifest.changed_keys.is_empty() => format!("{}: {}", manifest.user, manifest.action),
+                    Some(manifest) => format!("{}: {} ({})", manifest.user, manifest.action, manifest.changed_keys.join(", ")),
+                    None => format!("legacy backup of {}", paths.display(&paths.default_grub())),
+                };
+                println!("{:<20}  {}  {:>10}  {}",
+                    name,
+                    backup_manager::format_time(backup.modified),
+                    backup_manager::format_size(backup.size),
+                    details);
+            }
+            Ok(EXIT_OK)
+        }
+        [_, ..] if json => {
+            Err(CliError::Usage("--json is only supported by backups list".to_string()))
+        }
+        ["restore", name] => {
+            let backup = backup_manager::find_backup(paths, name)
+                .ok_or_else(|| CliError::Failed(format!("No backup named '{}'", name)))?;
+            let components = match flag_value(&flags, "--only=") {
+                Some(names) => names.split(',')
+                    .map(|name| backup_manager::Component::parse(name).ok_or_else(|| CliError::Usage(format!(
+                        "Unknown component '{}' (settings, grub.cfg, grubenv or grub.d)", name))))
+                    .collect::<Result<Vec<_>, _>>()?,
+                None => backup.components(),
+            };
+            let restores_grub_cfg = components.contains(&backup_manager::Component::GrubCfg);
+            if restores_grub_cfg && flags.contains(&"--apply") {
+                return Err(CliError::Usage("--apply would regenerate the restored grub.cfg".to_string()));
+            }
+            let diffs = backup_manager::restore_diff(paths, &backup.path, &components)
+                .map_err(|e| CliError::Failed(format!("Failed to read backup: {}", e)))?;
+            if diffs.is_empty() {
+                println!("{} is the same as the current configuration", paths.display(&backup.path));
+                return Ok(EXIT_OK);
+            }
+            if flags.contains(&"--dry-run") {
+                print_file_diffs(paths, None, Some(&backup), &diffs);
+                return Ok(EXIT_OK);
+            }
+            backup_manager::restore_backup(paths, &backup.path, &components)
+                .map_err(|e| CliError::Failed(format!("Failed to restore backup: {}", e)))?;
+            for file in &diffs {
+                println!("{} {}", if file.removed { "Removed" } else { "Restored" }, paths.display(&file.path));
+            }
+            let settings = diffs.iter().any(|f| f.component == backup_manager::Component::Settings);
+            finish(paths, flags.contains(&"--apply"), settings && !restores_grub_cfg && !flags.contains(&"--apply"))?;
+            Ok(EXIT_OK)
+        }
+        ["diff", name] | ["diff", name, _] => {
+            let find = |name: &str| backup_manager::find_backup(paths, name)
+                .ok_or_else(|| CliError::Failed(format!("No backup named '{}'", name)));
+            let old = find(name)?;
+            let new = positional.get(2).map(|name| find(name)).transpose()?;
+            let diffs = backup_manager::compare(paths, Some(&old.path), new.as_ref().map(|b| b.path.as_path()))
+                .map_err(|e| CliError::Failed(format!("Failed to read backup: {}", e)))?;
+            if diffs.is_empty() {
+                println!("No differences");
+                return Ok(EXIT_OK);
+            }
+            for line in backup_manager::summarize(paths, &diffs) {
+                println!("{}", line);
+            }
+            println!();
+            print_file_diffs(paths, Some(&old), new.as_ref(), &diffs);
+            Ok(EXIT_OK)
+        }
+        ["prune"] => {
+            let mut retention = backup_manager::Retention::load(paths);
+            if flags.iter().any(|f| f.starts_with("--keep")) {
+                retention = retention_flags(&flags, backup_manager::Retention::default())?;
+            }
+            if retention.is_unlimited() {
+                println!("No retention policy set, nothing to prune");
+                return Ok(EXIT_OK);
+            }
+            let dry_run = flags.contains(&"--dry-run");
+            let pruned = backup_manager::prune(paths, &retention, dry_run)
+                .map_err(|e| CliError::Failed(format!("Failed to prune backups: {}", e)))?;
+            for backup in &pruned {
+                println!("{} {}", if dry_run { "Would remove" } else { "Removed" }, paths.display(&backup.path));
+            }
+            if pruned.is_empty() {
+                println!("Nothing to prune ({})", retention.describe());
+            }
+            Ok(EXIT_OK)
+        }
+        ["retention"] => {
+            let mut retention = backup_manager::Retention::load(paths);
+            if flags.iter().any(|f| f.starts_with("--keep")) {
+                retention = retention_flags(&flags, retention)?;
+                retention.save(paths)
+                    .map_err(|e| CliError::Failed(format!("Failed to save retention policy: {}", e)))?;
+            }
+            println!("Retention: {}", retention.describe());
+            Ok(EXIT_OK)
+        }
+        _ => Err(CliError::Usage("backups requires list, restore BACKUP, diff BACKUP [OTHER], prune or retention".to_string())),
+    }
+}
+
+// Unified diffs between two versions of the configuration, each a backup or
+// the system (None)
+fn print_file_diffs(paths: &Paths, old: Option<&BackupInfo>, new: Option<&BackupInfo>, diffs: &[backup_manager::FileDiff]) {
+    let label = |version: Option<&BackupInfo>, file: &Path| match version {
+        None => paths.display(file),
+        Some(backup) if backup.manifest.is_some() => format!("{}{}", paths.display(&backup.path), paths.display(file)),
+        // A legacy backup is the file itself
+        Some(backup) => paths.display(&backup.path),
+    };
+    for file in diffs {
+        let old_label = if file.created { "/dev/null".to_string() } else { label(old, &file.path) };
+        let new_label = if file.removed { "/dev/null".to_string() } else { label(new, &file.path) };
+        print_diff(&old_label, &new_label, &file.diff);
+    }
+}
+
+// --keep=N and --keep-days=D on top of `retention`, 0 removes the limit
+fn retention_flags(flags: &[&str], mut retention: backup_manager::Retention) -> Result<backup_manager::Retention, CliError> {
+    let number = |name: &str| -> Result<Option<Option<u64>>, CliError> {
+        let Some(value) = flag_value(flags, name) else {
+            return Ok(None);
+        };
+        let n: u64 = value.parse()
+            .map_err(|_| CliError::Usage(format!("{}{} is not a number", name, value)))?;
+        Ok(Some((n > 0).then_some(n)))
+    };
+    if let Some(keep) = number("--keep=")? {
+        retention.keep = keep.map(|n| n as usize);
+    }
+    if let Some(days) = number("--keep-days=")? {
+        retention.keep_days = days;
+    }
+    Ok(retention)
+}
+
+fn cmd_export(paths: &Paths, args: &[String]) -> CliResult {
+    let (_, positional) = split_flags(args, &[])?;
+    let document = export::export(paths)?;
+    let text = serde_json::to_string_pretty(&document)
+        .map_err(|e| CliError::Failed(format!("Failed to serialize JSON: {}", e)))?;
+    match positional[..] {
+        [] | ["-"] => println!("{}", text),
+        [file] => {
+            fs::write(file, text + "\n")
+                .map_err(|e| CliError::Failed(format!("Failed to write {}: {}", file, e)))?;
+            println!("Exported to {}", file);
+        }
+        _ => return Err(CliError::Usage("export takes at most one FILE".to_string())),
+    }
+    Ok(EXIT_OK)
+}
+
+fn cmd_import(paths: &Paths, args: &[String]) -> CliResult {
+    let (flags, positional) = split_flags(args, &["--map=", "--fragment", "--apply", "--yes"])?;
+    let [file] = positional[..] else {
+        return Err(CliError::Usage("import takes exactly one FILE".to_string()));
+    };
+    let mut content = String::new();
+    let read = if file == "-" {
+        io::stdin().read_to_string(&mut content).map(|_| ())
+    } else {
+        fs::read_to_string(file).map(|text| content = text)
+    };
+    read.map_err(|e| CliError::Failed(format!("Failed to read {}: {}", file, e)))?;
+    let document = export::parse(&content)?;
+
+    // --map=EXPORTED=ENTRY, EXPORTED being an ID path or title from the export
+    let map = flags.iter()
+        .filter_map(|flag| flag.strip_prefix("--map="))
+        .map(|mapping| mapping.split_once('=')
+            .map(|(from, to)| (from.to_string(), to.to_string()))
+            .ok_or_else(|| CliError::Usage(format!("--map={} is not EXPORTED=ENTRY", mapping))))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let target = save_target(&flags);
+    let plan = export::plan(paths, &document, target, &map)?;
+    let from = if document.host.is_empty() { String::new() } else { format!(" from {}", document.host) };
+    println!("Configuration exported{} on {}", from, document.exported);
+    if let Some(write) = &plan.write {
+        print_write(paths, target, Some(write));
+    }
+    for change in &plan.changes {
+        println!("{}", change);
+    }
+    for conflict in &plan.conflicts {
+        println!("Conflict, not imported: {}", conflict);
+    }
+    for entry in &plan.missing {
+        eprintln!("Warning: '{}' doesn't exist here, its custom name is skipped (map it with --map='{}=ENTRY')",
+            entry.title, entry.key());
+    }
+    if plan.is_empty() {
+        println!("Nothing to import, the configuration is the same");
+        return Ok(EXIT_OK);
+    }
+    if !flags.contains(&"--yes") {
+        println!("Run again with --yes to import");
+        return Ok(EXIT_OK);
+    }
+    plan.apply(paths, &format!("import {}", file))?;
+    println!("Imported");
+    let apply_now = flags.contains(&"--apply");
+    finish(paths, apply_now && plan.write.is_some(), !apply_now && plan.write.is_some())?;
+    Ok(EXIT_OK)
+}
+
+fn cmd_kernels(paths: &Paths, args: &[String], json: bool) -> CliResult {
+    let (flags, positional) = split_flags(args, &["--yes", "--apply"])?;
+    let Some((&action, versions)) = positional.split_first() else {
+        return Err(CliError::Usage("kernels requires list or clean".to_string()));
+    };
+
+    match action {
+        "list" if versions.is_empty() && flags.is_empty() => {
+            let menu = grub::load_grub(paths);
+            let default_image = menu.as_ref().and_then(|m| kernel_cleanup::default_kernel_image(paths, m));
+            let current = if paths.is_host() { kernel_info::get_current_kernel() } else { None };
+            let kernels = kernel_info::list_kernel_files(paths);
+            let running = |k: &kernel_info::KernelInfo| current.as_deref() == Some(k.version.as_str());
+            let default = |k: &kernel_info::KernelInfo| {
+                default_image.as_deref() == Some(format!("vmlinuz-{}", k.version).as_str())
+            };
+
+            if json {
+                #[derive(Serialize)]
+                struct KernelData<'a> {
+                    #[serde(flatten)]
+                    kernel: &'a kernel_info::KernelInfo,
+                    running: bool,
+                    default: bool,
+                }
+                print_json("kernels list", kernels.iter().map(|k| KernelData {
+                    kernel: k,
+                    running: running(k),
+                    default: default(k),
+                }).collect::<Vec<_>>())?;
+                return Ok(EXIT_OK);
+            }
+
+            for kernel in &kernels {
+                let mut notes = Vec::new();
+                if running(kernel) {
+                    notes.push("running");
+                }
+                if default(kernel) {
+                    notes.push("default");
+                }
+                let line = format!("{:<32} {}  {}", kernel.version, paths.display(Path::new(&kernel.path)), notes.join(", "));
+                println!("{}", line.trim_end());
+            }
+            Ok(EXIT_OK)
+        }
+        "clean" => {
+            let menu = load_menu(paths)?;
+            let candidates = kernel_cleanup::scan_unused_kernels(paths, &menu);
+            for version in versions {
+                match candidates.iter().find(|k| k.version == *version) {
+                    Some(k) if k.is_default => {
+                        return Err(CliError::Failed(format!("{} is booted by the default entry", version)));
+                    }
+                    Some(_) => {}
+                    None => {
+                        return Err(CliError::Failed(format!("{} is not an installed, unused kernel", version)));
+                    }
+                }
+            }
+            let selected: Vec<_> = candidates.iter()
+                .filter(|k| !k.is_default)
+                .filter(|k| versions.is_empty() || versions.contains(&k.version.as_str()))
+                .collect();
+
+            let remove = flags.contains(&"--yes");
+
+            if json {
+                #[derive(Serialize)]
+                struct CleanData<'a> {
+                    removed: bool,
+                    kernels: Vec<&'a kernel_cleanup::KernelToClean>,
+                }
+                if remove {
+                    for kernel in &selected {
+                        kernel_cleanup::delete_kernel_files(paths, &kernel.version)
+                            .map_err(|e| CliError::Failed(format!("Failed to remove {}: {}", kernel.version, e)))?;
+                    }
+                }
+                print_json("kernels clean", CleanData { removed: remove, kernels: selected })?;
+                return Ok(EXIT_OK);
+            }
+            if selected.is_empty() {
+                println!("No unused kernels to remove");
+                return Ok(EXIT_OK);
+            }
+            if !remove {
+                for kernel in &selected {
+                    println!("Would remove {} ({})", kernel.version, kernel_cleanup::format_size(kernel.size));
+                }
+                println!("Run again with --yes to remove them");
+                return Ok(EXIT_OK);
+            }
+            for kernel in &selected {
+                kernel_cleanup::delete_kernel_files(paths, &kernel.version)
+                    .map_err(|e| CliError::Failed(format!("Failed to remove {}: {}", kernel.version, e)))?;
+                println!("Removed {} ({})", kernel.version, kernel_cleanup::format_size(kernel.size));
+            }
+            // grub.cfg still lists the removed kernels until it is regenerated
+            finish(paths, flags.contains(&"--apply"), !flags.contains(&"--apply"))?;
+            Ok(EXIT_OK)
+        }
+        _ => Err(CliError::Usage(format!("Invalid kernels command: {}", positional.join(" ")))),
+    }
+}
+
+fn cmd_validate(paths: &Paths, args: &[String], json: bool) -> CliResult {
+    no_args(args)?;
+    let result = grub_validate::validate_grub_config(paths)?;
+    if json {
+        print_json("validate", &result)?;
+        return Ok(if result.valid { EXIT_OK } else { EXIT_FAILURE });
+    }
+    for error in &result.errors {
+        println!("error: {}", error);
+    }
+    for warning in &result.warnings {
+        println!("warning: {}", warning);
+    }
+    if result.valid {
+        println!("Configuration is valid");
+        Ok(EXIT_OK)
+    } else if result.errors.is_empty() {
+        println!("grub-mkconfig failed");
+        Ok(EXIT_FAILURE)
+    } else {
+        println!("Configuration has errors");
+        Ok(EXIT_FAILURE)
+    }
+}
+
+fn cmd_boot_times(args: &[String], json: bool) -> CliResult {
+    no_args(args)?;
+    if json {
+        print_json("boot-times", boot_time::get_boot_times())?;
+        return Ok(EXIT_OK);
+    }
+    for entry in boot_time::get_boot_times() {
+        println!("{}  {:<32} {}", entry.timestamp, entry.kernel_version, boot_time::format_boot_time(entry.boot_time));
+    }
+    Ok(EXIT_OK)
+}
+
+fn apply(paths: &Paths) -> CliResult {
+    let result = grub_mkconfig::apply(paths)?;
+    eprint!("{}", result.output);
+    println!("{} regenerated with {} ({} entries)",
+        paths.display(&result.grub_cfg), result.command, result.entries);
+    Ok(EXIT_OK)
+}
+
+// After a change to /etc/default/grub: regenerate grub.cfg, or say how to
+fn finish(paths: &Paths, apply_now: bool, hint: bool) -> Result<(), CliError> {
+    if apply_now {
+        apply(paths)?;
+    } else if hint {
+        println!("Run 'grublist apply' to regenerate grub.cfg");
+    }
+    Ok(())
+}
+
+fn reboot(paths: &Paths) -> Result<(), CliError> {
+    if !paths.is_host() {
+        return Err(CliError::Failed("--reboot only works on the running system".to_string()));
+    }
+    let status = Command::new("systemctl")
+        .arg("reboot")
+        .status()
+        .or_else(|_| Command::new("reboot").status())
+        .map_err(|e| CliError::Failed(format!("Failed to reboot: {}", e)))?;
+    if !status.success() {
+        return Err(CliError::Failed(format!("reboot failed ({})", status)));
+    }
+    Ok(())
+}
+
+// Boots given by --trial[=N], None without the flag. Checked before the
+// change is made, so it isn't made if the trial can't start.
+fn trial_boots(paths: &Paths, flags: &[&str]) -> Result<Option<u32>, CliError> {
+    let boots = match flag_value(flags, "--trial=") {
+        Some(value) => parse_boots(value)?,
+        None if flags.contains(&"--trial") => trial::DEFAULT_BOOTS,
+        None => return Ok(None),
+    };
+    trial::check(paths, boots)?;
+    Ok(Some(boots))
+}
+
+fn parse_boots(value: &str) -> Result<u32, CliError> {
+    value.parse().map_err(|_| CliError::Usage(format!("Invalid number of boots: {}", value)))
+}
+
+fn latest_backup(paths: &Paths) -> Option<PathBuf> {
+    backup_manager::latest(paths).map(|backup| backup.path)
+}
+
+// After a change made with --trial: the backup the change took (newer than
+// `before`) becomes the fallback, and grub.cfg is regenerated with the counter
+fn start_trial(paths: &Paths, boots: u32, before: Option<PathBuf>) -> Result<(), CliError> {
+    let backup = backup_manager::latest(paths)
+        .filter(|backup| Some(&backup.path) != before.as_ref())
+        .ok_or_else(|| CliError::Failed("Nothing was changed, no trial started".to_string()))?;
+    let trial = trial::start(paths, &backup, boots)?;
+    print_trial(&trial);
+    Ok(())
+}
+
+fn print_trial(trial: &trial::Trial) {
+    println!("grub.cfg regenerated. Backup {} is restored unless 'grublist confirm-boot' runs within {} boot{}",
+        trial.backup, trial.boots, if trial.boots == 1 { "" } else { "s" });
+}
+
+fn cmd_confirm_boot(paths: &Paths) -> CliResult {
+    match trial::confirm_boot(paths)? {
+        trial::Outcome::NoTrial => println!("No trial running"),
+        trial::Outcome::Confirmed(trial) => println!("Boot confirmed, keeping '{}'", trial.action),
+        trial::Outcome::RolledBack(trial) => {
+            println!("The trial boot failed, restored the settings and grub.cfg of backup {} from before '{}'", trial.backup, trial.action);
+        }
+    }
+    Ok(EXIT_OK)
+}
+
+fn cmd_trial(paths: &Paths, args: &[String]) -> CliResult {
+    let (flags, positional) = split_flags(args, &["--boots="])?;
+    match positional[..] {
+        ["status"] => {
+            let Some(trial) = trial::load(paths) else {
+                println!("No trial running");
+                return Ok(EXIT_OK);
+            };
+            println!("Trial of '{}' since {}, fallback backup {}", trial.action, trial.started, trial.backup);
+            match trial::status(&GrubEnv::load(paths)?) {
+                trial::Status::BootsLeft(0) => println!("No boots left, the next one loads the fallback"),
+                trial::Status::BootsLeft(left) => println!("{} of {} boots left", left, trial.boots),
+                trial::Status::FellBack => println!("GRUB loaded the fallback, 'grublist confirm-boot' restores the backup"),
+                trial::Status::Unknown => println!("{} is not set in grubenv, the counter isn't running", trial::VAR),
+            }
+            Ok(EXIT_OK)
+        }
+        ["start"] => {
+            let boots = match flag_value(&flags, "--boots=") {
+                Some(value) => parse_boots(value)?,
+                None => trial::DEFAULT_BOOTS,
+            };
+            let backup = backup_manager::latest(paths)
+                .ok_or_else(|| CliError::Failed("There is no backup to fall back to".to_string()))?;
+            let trial = trial::start(paths, &backup, boots)?;
+            print_trial(&trial);
+            Ok(EXIT_OK)
+        }
+        ["rollback"] => {
+            let trial = trial::rollback(paths)?;
+            println!("Restored the settings and grub.cfg of backup {} from before '{}'", trial.backup, trial.action);
+            Ok(EXIT_OK)
+        }
+        _ => Err(CliError::Usage(format!("Invalid trial command: {}", positional.join(" ")))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn double_dash_is_positional() {
+        let allowed = ["--apply", "--trial="];
+        let words = args(&["add", "KEY", "quiet", "--apply", "--", "single", "--apply"]);
+        let (flags, positional) = split_flags(&words, &allowed).unwrap();
+        assert_eq!(flags, vec!["--apply"]);
+        assert_eq!(positional, vec!["add", "KEY", "quiet", "--", "single", "--apply"]);
+
+        let words = args(&["add", "KEY", "--trial=3", "--bogus"]);
+        assert!(matches!(split_flags(&words, &allowed), Err(CliError::Usage(_))));
+    }
+}
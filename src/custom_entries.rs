@@ -0,0 +1,219 @@
+// Boot entries with their own kernel parameters.
+//
+// GRUB_CMDLINE_LINUX* applies to every kernel. To boot one kernel with extra
+// parameters (e.g. a test kernel with kasan options), an existing entry is
+// copied into /etc/grub.d/42_grublist. Like 40_custom, that script prints
+// everything after its first two lines, so grub-mkconfig adds the entries to
+// grub.cfg after the generated ones. The script is the only record of the
+// entries: it is read back with the grub.cfg reader and rewritten as a whole.
+
+use std::fs;
+use std::io;
+use crate::backup_manager;
+use crate::grub::{self, Entry, EntryType};
+use crate::grub_config;
+use crate::paths::Paths;
+use crate::safe_write;
+
+const HEADER: &str = "\
+#!/bin/sh
+exec tail -n +3 $0
+# Boot entries managed by grublist, each a copy of a generated entry with its
+# own kernel parameters. Edit them with grublist, this file is rewritten.
+";
+
+/// Entries in the script, in menu order
+pub fn load(paths: &Paths) -> Vec<Entry> {
+    match fs::read_to_string(paths.custom_entries_script()) {
+        Ok(content) => grub::parse_grub(&content, &[]).children,
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Rewrite the script with `entries`, or remove it when there are none,
+/// after taking a backup. grub-mkconfig only runs executable scripts, a new
+/// one is made executable.
+pub fn save(paths: &Paths, entries: &[Entry]) -> io::Result<()> {
+    let script = paths.custom_entries_script();
+    let _lock = safe_write::lock(paths)?;
+    backup_manager::snapshot(paths, "edit custom entries", &[])?;
+    if entries.is_empty() {
+        return match fs::remove_file(&script) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+
+    let mut content = HEADER.to_string();
+    for entry in entries {
+        content.push('\n');
+        content.push_str(&render(entry));
+    }
+    if let Some(dir) = script.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    safe_write::write_with_mode(&script, content, 0o755)
+}
+
+/// Copy a Linux boot entry under a new title and ID. Only the commands
+/// grublist understands are kept: modules, root device, kernel and initrd.
+pub fn copy_entry(source: &Entry, existing: &[Entry]) -> Result<Entry, String> {
+    if source.entry_type != EntryType::MenuEntry || source.boot.linux.is_none() {
+        return Err(format!("'{}' does not boot a Linux kernel", source.name));
+    }
+
+    let id = (1..)
+        .map(|n| format!("grublist-{}", n))
+        .find(|id| !existing.iter().any(|e| e.id.as_deref() == Some(id.as_str())))
+        .unwrap_or_default();
+    let mut entry = source.clone();
+    entry.name = format!("{} (custom)", source.name);
+    entry.id = Some(id);
+    entry.hotkey = None;
+    entry.boot.chainloader = None;
+    Ok(entry)
+}
+
+/// Kernel parameters of an entry, in command line form (`dyndbg="a b"`)
+pub fn kernel_params(entry: &Entry) -> Vec<String> {
+    // GRUB has already taken the quotes off each argument
+    entry.boot.linux_args.iter()
+        .map(|arg| {
+            let (name, value) = grub_config::split_parameter(arg);
+            grub_config::format_parameter(&name, value.as_deref())
+        })
+        .collect()
+}
+
+pub fn set_kernel_params(entry: &mut Entry, params: &[String]) {
+    entry.boot.linux_args = params.iter()
+        .map(|param| match grub_config::split_parameter(param) {
+            (name, Some(value)) => format!("{}={}", name, value),
+            (name, None) => name,
+        })
+        .collect();
+}
+
+fn render(entry: &Entry) -> String {
+    let mut header = format!("menuentry {}", single_quote(&entry.name));
+    for class in &entry.classes {
+        header.push_str(&format!(" --class {}", quote_word(class)));
+    }
+    if let Some(users) = &entry.users {
+        header.push_str(&format!(" --users {}", quote_word(users)));
+    }
+    if entry.unrestricted {
+        header.push_str(" --unrestricted");
+    }
+    if let Some(id) = &entry.id {
+        header.push_str(&format!(" --id {}", single_quote(id)));
+    }
+
+    let boot = &entry.boot;
+    let mut lines = vec![format!("{} {{", header)];
+    lines.extend(boot.insmod.iter().map(|m| format!("\tinsmod {}", quote_word(m))));
+    if let Some(root) = &boot.root {
+        lines.push(format!("\tset root={}", single_quote(root)));
+    }
+    if let Some(uuid) = &boot.search_uuid {
+        lines.push(format!("\tsearch --no-floppy --fs-uuid --set=root {}", quote_word(uuid)));
+    }
+    if let Some(linux) = &boot.linux {
+        let mut words = vec![quote_word(linux)];
+        words.extend(boot.linux_args.iter().map(|a| quote_word(a)));
+        lines.push(format!("\tlinux\t{}", words.join(" ")));
+    }
+    if !boot.initrd.is_empty() {
+        let images: Vec<String> = boot.initrd.iter().map(|i| quote_word(i)).collect();
+        lines.push(format!("\tinitrd\t{}", images.join(" ")));
+    }
+    lines.push("}".to_string());
+    lines.join("\n") + "\n"
+}
+
+// A GRUB script word. `$var` references (e.g. Ubuntu's $vt_handoff) are
+// left where GRUB expands them; GRUB quotes arguments with spaces again
+// when it builds the kernel command line.
+fn quote_word(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word.chars().all(|c| c.is_ascii_alphanumeric() || "_-./:,+=@%${}".contains(c));
+    if plain {
+        word.to_string()
+    } else if word.contains('$') {
+        let escaped = word.replace('\\', "\\\\").replace('"', "\\\"");
+        format!("\"{}\"", escaped)
+    } else {
+        single_quote(word)
+    }
+}
+
+fn single_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    const GRUB_CFG: &str = r#"
+menuentry 'Ubuntu, with Linux 6.5.0-14-generic' --class ubuntu --class gnu-linux $menuentry_id_option 'gnulinux-6.5.0-14-generic-advanced-1234' {
+	recordfail
+	insmod gzio
+	insmod ext2
+	search --no-floppy --fs-uuid --set=root 1234
+	linux	/boot/vmlinuz-6.5.0-14-generic root=UUID=1234 ro quiet splash $vt_handoff
+	initrd	/boot/initrd.img-6.5.0-14-generic
+}
+"#;
+
+    #[test]
+    fn script_round_trip() {
+        let root = std::env::temp_dir().join(format!("grublist-test-custom-{}", std::process::id()));
+        let paths = Paths::new(&root);
+        let source = &grub::parse_grub(GRUB_CFG, &[]).children[0];
+
+        let mut entry = copy_entry(source, &[]).unwrap();
+        entry.name = "Bob's test kernel".to_string();
+        entry.users = Some("admin".to_string());
+        let mut params = kernel_params(&entry);
+        params.push("dyndbg=\"file foo.c +p\"".to_string());
+        set_kernel_params(&mut entry, &params);
+        let mut second = copy_entry(source, std::slice::from_ref(&entry)).unwrap();
+        set_kernel_params(&mut second, &["root=UUID=1234".to_string(), "single".to_string()]);
+
+        save(&paths, &[entry.clone(), second]).unwrap();
+        let script = paths.custom_entries_script();
+        let content = fs::read_to_string(&script).unwrap();
+        assert!(content.starts_with(HEADER));
+        assert!(content.contains("\tlinux\t/boot/vmlinuz-6.5.0-14-generic root=UUID=1234 ro quiet splash $vt_handoff 'dyndbg=file foo.c +p'\n"));
+        assert_eq!(fs::metadata(&script).unwrap().permissions().mode() & 0o777, 0o755);
+
+        let loaded = load(&paths);
+        assert_eq!(loaded.len(), 2);
+        let first = &loaded[0];
+        assert_eq!(first.name, "Bob's test kernel");
+        assert_eq!(first.id.as_deref(), Some("grublist-1"));
+        assert_eq!(first.classes, entry.classes);
+        assert_eq!(first.users.as_deref(), Some("admin"));
+        assert_eq!(kernel_params(first), params);
+        assert_eq!(first.boot.linux, entry.boot.linux);
+        assert_eq!(first.boot.initrd, entry.boot.initrd);
+        assert_eq!(first.boot.insmod, vec!["gzio", "ext2"]);
+        assert_eq!(first.boot.search_uuid.as_deref(), Some("1234"));
+        assert_eq!(loaded[1].id.as_deref(), Some("grublist-2"));
+        assert_eq!(kernel_params(&loaded[1]), vec!["root=UUID=1234", "single"]);
+
+        // Rendering what was read gives the same script
+        save(&paths, &loaded).unwrap();
+        assert_eq!(fs::read_to_string(&script).unwrap(), content);
+
+        // No entries left: no script
+        save(&paths, &[]).unwrap();
+        assert!(!script.exists());
+        assert!(load(&paths).is_empty());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
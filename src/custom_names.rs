@@ -1,10 +1,9 @@
 use std::fs;
 use std::io;
-use std::path::Path;
 use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
-
-const CUSTOM_NAMES_FILE: &str = "/etc/grublist-custom-names.json";
+use crate::paths::Paths;
+use crate::safe_write;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CustomNames {
@@ -12,8 +11,8 @@ pub struct CustomNames {
 }
 
 impl CustomNames {
-    pub fn load() -> Self {
-        if let Ok(content) = fs::read_to_string(CUSTOM_NAMES_FILE) {
+    pub fn load(paths: &Paths) -> Self {
+        if let Ok(content) = fs::read_to_string(paths.custom_names()) {
             if let Ok(names) = serde_json::from_str::<CustomNames>(&content) {
                 return names;
             }
@@ -23,11 +22,11 @@ impl CustomNames {
         }
     }
     
-    pub fn save(&self) -> io::Result<()> {
+    pub fn save(&self, paths: &Paths) -> io::Result<()> {
         let content = serde_json::to_string_pretty(self)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-        fs::write(CUSTOM_NAMES_FILE, content)?;
-        Ok(())
+            .map_err(io::Error::other)?;
+        let _lock = safe_write::lock(paths)?;
+        safe_write::write(&paths.custom_names(), content)
     }
     
     pub fn get_custom_name(&self, path: &[usize]) -> Option<&String> {
@@ -45,7 +44,7 @@ impl CustomNames {
     }
 }
 
-fn path_to_string(path: &[usize]) -> String {
+pub fn path_to_string(path: &[usize]) -> String {
     path.iter()
         .map(|x| x.to_string())
         .collect::<Vec<_>>()
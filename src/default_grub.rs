@@ -0,0 +1,548 @@
+// Lossless model of /etc/default/grub.
+//
+// The file is a shell fragment sourced by grub-mkconfig, so it may contain
+// quoting of any style, command substitutions, inline comments, `export` and
+// commented-out defaults. Each line is kept as written; setting a key only
+// rewrites the value of that key's assignment and leaves every other byte of
+// the file alone.
+
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Debug, Clone)]
+pub struct Assignment {
+    // Everything before the key: indentation, `#` of a commented-out
+    // default and `export `
+    prefix: String,
+    pub key: String,
+    // The value exactly as written, including quotes
+    raw_value: String,
+    // Whitespace and inline comment after the value
+    suffix: String,
+    pub commented: bool,
+}
+
+impl Assignment {
+    /// The value as the shell would see it, with quotes removed.
+    /// `$var`, `$(...)` and backticks are not expanded.
+    pub fn value(&self) -> String {
+        unquote(&self.raw_value, None)
+    }
+
+    /// The value with `$NAME` and `${NAME}` replaced from `vars`, e.g. the
+    /// settings read so far for `GRUB_CMDLINE_LINUX="$GRUB_CMDLINE_LINUX quiet"`.
+    /// Names not in `vars` are left as written.
+    pub fn expanded(&self, vars: &HashMap<String, String>) -> String {
+        unquote(&self.raw_value, Some(vars))
+    }
+
+    fn render(&self) -> String {
+        format!("{}{}={}{}", self.prefix, self.key, self.raw_value, self.suffix)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Line {
+    Assignment(Assignment),
+    // Blank lines, comments and any other shell code, kept verbatim
+    Other(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct DefaultGrub {
+    lines: Vec<Line>,
+    trailing_newline: bool,
+}
+
+impl DefaultGrub {
+    pub fn parse(content: &str) -> Self {
+        let mut lines = Vec::new();
+        let mut physical = content.split('\n').peekable();
+        if content.is_empty() {
+            physical.next();
+        }
+        let trailing_newline = content.ends_with('\n');
+
+        while let Some(first) = physical.next() {
+            if trailing_newline && physical.peek().is_none() {
+                // The empty piece after the final newline
+                break;
+            }
+            let mut logical = first.to_string();
+            // A quote left open or a trailing backslash continues on the next line
+            while is_continued(&logical) {
+                match physical.next() {
+                    Some(next) if !(trailing_newline && physical.peek().is_none()) => {
+                        logical.push('\n');
+                        logical.push_str(next);
+                    }
+                    _ => break,
+                }
+            }
+            lines.push(match parse_assignment(&logical) {
+                Some(assignment) => Line::Assignment(assignment),
+                None => Line::Other(logical),
+            });
+        }
+
+        DefaultGrub { lines, trailing_newline }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = self.lines.iter()
+            .map(|line| match line {
+                Line::Assignment(a) => a.render(),
+                Line::Other(text) => text.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        if self.trailing_newline {
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Active (not commented out) assignments in file order
+    pub fn assignments(&self) -> impl Iterator<Item = &Assignment> {
+        self.lines.iter().filter_map(|line| match line {
+            Line::Assignment(a) if !a.commented => Some(a),
+            _ => None,
+        })
+    }
+
+    /// Set a key, changing only its value. The last active assignment is
+    /// edited in place, keeping its quote style, `export` and inline comment.
+    /// Otherwise a commented-out default is uncommented, or a line appended.
+    pub fn set(&mut self, key: &str, value: &str) {
+        let active = self.position(key, false);
+        let commented = self.position(key, true);
+
+        match active.or(commented) {
+            Some(idx) => {
+                let Line::Assignment(a) = &mut self.lines[idx] else {
+                    unreachable!();
+                };
+                if a.commented {
+                    // "  # export GRUB_X=..." becomes "  export GRUB_X=..."
+                    let hash = a.prefix.find('#').unwrap_or(0);
+                    let export = a.prefix[hash..].contains("export");
+                    a.prefix = format!("{}{}", &a.prefix[..hash], if export { "export " } else { "" });
+                    a.commented = false;
+                }
+                if a.value() != value {
+                    a.raw_value = quote(value, Some(&a.raw_value));
+                }
+            }
+            None => {
+                self.lines.push(Line::Assignment(Assignment {
+                    prefix: String::new(),
+                    key: key.to_string(),
+                    raw_value: quote(value, None),
+                    suffix: String::new(),
+                    commented: false,
+                }));
+                self.trailing_newline = true;
+            }
+        }
+    }
+
+    fn position(&self, key: &str, commented: bool) -> Option<usize> {
+        self.lines.iter().rposition(|line| {
+            matches!(line, Line::Assignment(a) if a.key == key && a.commented == commented)
+        })
+    }
+}
+
+fn is_continued(text: &str) -> bool {
+    let mut rest = text;
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ';', '&', '|']);
+        // Nothing inside a comment continues the line
+        if rest.is_empty() || rest.starts_with('#') {
+            return false;
+        }
+        match scan_word(rest) {
+            Scan::Open => return true,
+            Scan::Complete(len) if len == rest.len() => {
+                // An unquoted backslash at the very end joins the next line
+                let trailing = rest.len() - rest.trim_end_matches('\\').len();
+                return trailing % 2 == 1;
+            }
+            Scan::Complete(len) => rest = &rest[len..],
+        }
+    }
+}
+
+// `[indent][#][export ]KEY=value[ # comment]`
+fn parse_assignment(line: &str) -> Option<Assignment> {
+    let body_start = line.len() - line.trim_start().len();
+    let mut rest = &line[body_start..];
+
+    let commented = rest.starts_with('#');
+    if commented {
+        rest = rest[1..].trim_start();
+    }
+    if let Some(after) = rest.strip_prefix("export") {
+        if after.starts_with([' ', '\t']) {
+            rest = after.trim_start();
+        }
+    }
+
+    let key_len = rest.find('=')?;
+    let key = &rest[..key_len];
+    let mut chars = key.chars();
+    let valid_key = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid_key {
+        return None;
+    }
+
+    let prefix_len = line.len() - rest.len();
+    let value_and_rest = &rest[key_len + 1..];
+    let value_len = match scan_word(value_and_rest) {
+        Scan::Complete(len) => len,
+        // An unterminated commented-out line is just a comment
+        Scan::Open => return None,
+    };
+    let suffix = &value_and_rest[value_len..];
+    // Anything but a comment after the value (e.g. `; export X`) is shell
+    // code this model doesn't understand, keep the line as it is. A
+    // commented-out line like `#GRUB_TERMINAL=console some note` is prose,
+    // uncommenting it would write broken shell.
+    let suffix_trimmed = suffix.trim_start();
+    if !suffix_trimmed.is_empty() && !suffix_trimmed.starts_with('#') {
+        return None;
+    }
+
+    Some(Assignment {
+        prefix: line[..prefix_len].to_string(),
+        key: key.to_string(),
+        raw_value: value_and_rest[..value_len].to_string(),
+        suffix: suffix.to_string(),
+        commented,
+    })
+}
+
+enum Scan {
+    // Length in bytes of the word
+    Complete(usize),
+    // A quote or substitution is still open at the end of the text
+    Open,
+}
+
+// Find the end of the shell word at the start of `text`
+fn scan_word(text: &str) -> Scan {
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            ' ' | '\t' | ';' | '&' | '|' => return Scan::Complete(i),
+            '\\' => {
+                chars.next();
+            }
+            '\'' => {
+                let closed = chars.by_ref().any(|(_, n)| n == '\'');
+                if !closed {
+                    return Scan::Open;
+                }
+            }
+            '"' | '`' => {
+                let mut closed = false;
+                while let Some((_, n)) = chars.next() {
+                    if n == '\\' {
+                        chars.next();
+                    } else if n == c {
+                        closed = true;
+                        break;
+                    }
+                }
+                if !closed {
+                    return Scan::Open;
+                }
+            }
+            '$' if chars.peek().map(|&(_, n)| n) == Some('(') => {
+                chars.next();
+                let mut depth = 1;
+                for (_, n) in chars.by_ref() {
+                    match n {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                if depth > 0 {
+                    return Scan::Open;
+                }
+            }
+            _ => {}
+        }
+    }
+    Scan::Complete(text.len())
+}
+
+fn unquote(raw: &str, vars: Option<&HashMap<String, String>>) -> String {
+    let mut out = String::new();
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                for n in chars.by_ref() {
+                    if n == '\'' {
+                        break;
+                    }
+                    out.push(n);
+                }
+            }
+            '"' => {
+                while let Some(n) = chars.next() {
+                    match n {
+                        '"' => break,
+                        '\\' => match chars.peek().copied() {
+                            Some('\n') => {
+                                chars.next();
+                            }
+                            Some(esc @ ('"' | '\\')) => {
+                                chars.next();
+                                out.push(esc);
+                            }
+                            // \$ and \` stay escaped so the value can be written back as is
+                            _ => out.push('\\'),
+                        },
+                        '$' => push_variable(&mut chars, vars, &mut out),
+                        _ => out.push(n),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') | None => {}
+                Some(n) => out.push(n),
+            },
+            // Command substitutions are kept as written, quotes included
+            '`' => {
+                out.push(c);
+                for n in chars.by_ref() {
+                    out.push(n);
+                    if n == '`' {
+                        break;
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'(') => {
+                out.push(c);
+                let mut depth = 0;
+                for n in chars.by_ref() {
+                    out.push(n);
+                    match n {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            '$' => push_variable(&mut chars, vars, &mut out),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// After a `$`: push the value of the variable reference that follows, or
+// the reference itself if it isn't known
+fn push_variable(chars: &mut Peekable<Chars>, vars: Option<&HashMap<String, String>>, out: &mut String) {
+    let braced = chars.peek() == Some(&'{');
+    let mut name = String::new();
+    let mut closed = false;
+    if braced {
+        chars.next();
+        for n in chars.by_ref() {
+            if n == '}' {
+                closed = true;
+                break;
+            }
+            name.push(n);
+        }
+    } else {
+        while let Some(&n) = chars.peek() {
+            let valid = n == '_' || n.is_ascii_alphabetic() || (!name.is_empty() && n.is_ascii_digit());
+            if !valid {
+                break;
+            }
+            name.push(n);
+            chars.next();
+        }
+    }
+
+    match vars.and_then(|vars| vars.get(&name)) {
+        Some(value) if !braced || closed => out.push_str(value),
+        _ if braced => {
+            out.push_str("${");
+            out.push_str(&name);
+            if closed {
+                out.push('}');
+            }
+        }
+        _ => {
+            out.push('$');
+            out.push_str(&name);
+        }
+    }
+}
+
+/// Quote a value for the shell, in the style of the value it replaces when
+/// possible
+pub fn quote(value: &str, like: Option<&str>) -> String {
+    let plain = !value.is_empty()
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || "_-./:,+=@%".contains(c));
+    // `cmd` or $(cmd) on its own must stay unquoted to keep its meaning
+    let substitution = (value.starts_with('`') && value.ends_with('`') && value.len() > 1)
+        || (value.starts_with("$(") && value.ends_with(')'));
+
+    match like.and_then(|raw| raw.chars().next()) {
+        Some('\'') if !value.contains('\'') => format!("'{}'", value),
+        Some('"') => double_quote(value),
+        _ if plain || substitution => value.to_string(),
+        _ => double_quote(value),
+    }
+}
+
+fn double_quote(value: &str) -> String {
+    let mut out = String::from("\"");
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => out.push_str("\\\""),
+            // Keep \$ and \` (see unquote), escape any other backslash
+            '\\' if matches!(chars.peek(), Some('$') | Some('`')) => out.push('\\'),
+            '\\' => out.push_str("\\\\"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULT: &str = r#"# If you change this file, run 'update-grub' afterwards.
+GRUB_DEFAULT=0
+GRUB_TIMEOUT_STYLE=hidden
+export GRUB_TIMEOUT=5 # seconds
+GRUB_DISTRIBUTOR=`lsb_release -i -s 2> /dev/null || echo Debian`
+GRUB_CMDLINE_LINUX_DEFAULT='quiet splash'
+GRUB_CMDLINE_LINUX="console=ttyS0 \
+dyndbg=\"file foo.c +p\""
+#GRUB_DISABLE_LINUX_UUID=true
+# export GRUB_GFXMODE=640x480
+GRUB_CMDLINE_LINUX="$GRUB_CMDLINE_LINUX ${EXTRA}"
+"#;
+
+    fn get<'a>(grub: &'a DefaultGrub, key: &str) -> Option<&'a Assignment> {
+        grub.assignments().filter(|a| a.key == key).last()
+    }
+
+    #[test]
+    fn render_is_lossless() {
+        assert_eq!(DefaultGrub::parse(DEFAULT).render(), DEFAULT);
+        let no_newline = "GRUB_TIMEOUT=5\n\n# end";
+        assert_eq!(DefaultGrub::parse(no_newline).render(), no_newline);
+        assert_eq!(DefaultGrub::parse("").render(), "");
+    }
+
+    #[test]
+    fn values() {
+        let grub = DefaultGrub::parse(DEFAULT);
+        assert_eq!(get(&grub, "GRUB_TIMEOUT").unwrap().value(), "5");
+        assert_eq!(get(&grub, "GRUB_CMDLINE_LINUX_DEFAULT").unwrap().value(), "quiet splash");
+        assert_eq!(
+            get(&grub, "GRUB_DISTRIBUTOR").unwrap().value(),
+            "`lsb_release -i -s 2> /dev/null || echo Debian`"
+        );
+        // Commented-out defaults are not active
+        assert!(get(&grub, "GRUB_DISABLE_LINUX_UUID").is_none());
+
+        let first = grub.assignments().find(|a| a.key == "GRUB_CMDLINE_LINUX").unwrap();
+        assert_eq!(first.value(), "console=ttyS0 dyndbg=\"file foo.c +p\"");
+
+        let vars = HashMap::from([
+            ("GRUB_CMDLINE_LINUX".to_string(), "console=ttyS0".to_string()),
+            ("EXTRA".to_string(), "nomodeset".to_string()),
+        ]);
+        let last = get(&grub, "GRUB_CMDLINE_LINUX").unwrap();
+        assert_eq!(last.expanded(&vars), "console=ttyS0 nomodeset");
+        assert_eq!(last.value(), "$GRUB_CMDLINE_LINUX ${EXTRA}");
+    }
+
+    #[test]
+    fn set_keeps_export_and_comment() {
+        let mut grub = DefaultGrub::parse(DEFAULT);
+        grub.set("GRUB_TIMEOUT", "10");
+        grub.set("GRUB_CMDLINE_LINUX_DEFAULT", "quiet");
+        let out = grub.render();
+        assert!(out.contains("\nexport GRUB_TIMEOUT=10 # seconds\n"));
+        assert!(out.contains("\nGRUB_CMDLINE_LINUX_DEFAULT='quiet'\n"));
+        // Nothing else changed
+        assert_eq!(out.lines().count(), DEFAULT.lines().count());
+    }
+
+    #[test]
+    fn set_edits_last_assignment() {
+        let mut grub = DefaultGrub::parse(DEFAULT);
+        grub.set("GRUB_CMDLINE_LINUX", "console=ttyS0 quiet");
+        let out = grub.render();
+        assert!(out.contains("\nGRUB_CMDLINE_LINUX=\"console=ttyS0 quiet\"\n"));
+        assert!(out.contains("dyndbg=\\\"file foo.c +p\\\"\"\n"));
+    }
+
+    #[test]
+    fn set_uncomments_or_appends() {
+        let mut grub = DefaultGrub::parse(DEFAULT);
+        grub.set("GRUB_DISABLE_LINUX_UUID", "true");
+        grub.set("GRUB_GFXMODE", "1024x768");
+        grub.set("GRUB_DISABLE_OS_PROBER", "false");
+        let out = grub.render();
+        assert!(out.contains("\nGRUB_DISABLE_LINUX_UUID=true\n"));
+        assert!(out.contains("\nexport GRUB_GFXMODE=1024x768\n"));
+        assert!(out.ends_with("\nGRUB_DISABLE_OS_PROBER=false\n"));
+    }
+
+    #[test]
+    fn commented_prose_is_not_uncommented() {
+        let mut grub = DefaultGrub::parse("#GRUB_TERMINAL=console some note\n#GRUB_GFXMODE=640x480 # try this\n");
+        grub.set("GRUB_TERMINAL", "serial");
+        grub.set("GRUB_GFXMODE", "auto");
+        assert_eq!(grub.render(), "#GRUB_TERMINAL=console some note\nGRUB_GFXMODE=auto # try this\nGRUB_TERMINAL=serial\n");
+    }
+
+    #[test]
+    fn unchanged_value_keeps_quoting() {
+        let mut grub = DefaultGrub::parse("GRUB_DEFAULT=\"0\"\n");
+        grub.set("GRUB_DEFAULT", "0");
+        assert_eq!(grub.render(), "GRUB_DEFAULT=\"0\"\n");
+    }
+
+    #[test]
+    fn other_shell_code_is_not_an_assignment() {
+        let grub = DefaultGrub::parse("GRUB_A=1; export GRUB_B=2\nif true; then\n  GRUB_C=3\nfi\n");
+        let keys: Vec<&str> = grub.assignments().map(|a| a.key.as_str()).collect();
+        assert_eq!(keys, vec!["GRUB_C"]);
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("quiet", None), "quiet");
+        assert_eq!(quote("quiet splash", None), "\"quiet splash\"");
+        assert_eq!(quote("quiet splash", Some("'x'")), "'quiet splash'");
+        assert_eq!(quote("it's", Some("'x'")), "\"it's\"");
+        assert_eq!(quote("a \"b\" \\c", None), "\"a \\\"b\\\" \\\\c\"");
+        assert_eq!(quote("$(cat /etc/x)", None), "$(cat /etc/x)");
+        assert_eq!(quote("", None), "\"\"");
+    }
+}
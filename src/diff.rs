@@ -0,0 +1,196 @@
+// Line diffs between the current and the proposed content of a file, shown
+// before anything is written (the TUI preview and `--dry-run`).
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// Diff `old` against `new` line by line (longest common subsequence)
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Config files differ in a few lines, only the middle needs the table
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    // lcs[i][j]: length of the common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff: Vec<DiffLine> = old[..prefix].iter().map(|l| DiffLine::Same(l.to_string())).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            diff.push(DiffLine::Same(a[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            diff.push(DiffLine::Removed(a[i].to_string()));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Added(b[j].to_string()));
+            j += 1;
+        }
+    }
+    diff.extend(old[old.len() - suffix..].iter().map(|l| DiffLine::Same(l.to_string())));
+    diff
+}
+
+/// The old and the new text a diff was made from
+pub fn sides(diff: &[DiffLine]) -> (String, String) {
+    let (mut old, mut new) = (String::new(), String::new());
+    for line in diff {
+        match line {
+            DiffLine::Same(text) => {
+                old.push_str(text);
+                old.push('\n');
+                new.push_str(text);
+                new.push('\n');
+            }
+            DiffLine::Removed(text) => {
+                old.push_str(text);
+                old.push('\n');
+            }
+            DiffLine::Added(text) => {
+                new.push_str(text);
+                new.push('\n');
+            }
+        }
+    }
+    (old, new)
+}
+
+/// Lines added and removed
+pub fn count_changes(diff: &[DiffLine]) -> (usize, usize) {
+    let added = diff.iter().filter(|l| matches!(l, DiffLine::Added(_))).count();
+    let removed = diff.iter().filter(|l| matches!(l, DiffLine::Removed(_))).count();
+    (added, removed)
+}
+
+/// Unified diff hunks (`@@ -3,4 +3,5 @@` followed by ` `, `-` and `+`
+/// lines) with `context` unchanged lines around each change
+pub fn unified(diff: &[DiffLine], context: usize) -> Vec<String> {
+    // Ranges of diff lines to show, overlapping ones merged
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (idx, line) in diff.iter().enumerate() {
+        if matches!(line, DiffLine::Same(_)) {
+            continue;
+        }
+        let start = idx.saturating_sub(context);
+        let end = (idx + context + 1).min(diff.len());
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    let mut output = Vec::new();
+    for (start, end) in ranges {
+        // Line numbers in the old and new file before the hunk
+        let old_before = diff[..start].iter().filter(|l| !matches!(l, DiffLine::Added(_))).count();
+        let new_before = diff[..start].iter().filter(|l| !matches!(l, DiffLine::Removed(_))).count();
+        let hunk = &diff[start..end];
+        let old_count = hunk.iter().filter(|l| !matches!(l, DiffLine::Added(_))).count();
+        let new_count = hunk.iter().filter(|l| !matches!(l, DiffLine::Removed(_))).count();
+        // An empty side starts at the line before it, like diff -u
+        let first = |before: usize, count: usize| if count == 0 { before } else { before + 1 };
+        output.push(format!("@@ -{},{} +{},{} @@",
+            first(old_before, old_count), old_count,
+            first(new_before, new_count), new_count));
+        output.extend(hunk.iter().map(|line| match line {
+            DiffLine::Same(text) => format!(" {}", text),
+            DiffLine::Removed(text) => format!("-{}", text),
+            DiffLine::Added(text) => format!("+{}", text),
+        }));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = "GRUB_DEFAULT=0\nGRUB_TIMEOUT_STYLE=hidden\nGRUB_TIMEOUT=5\nGRUB_DISTRIBUTOR=Ubuntu\nGRUB_CMDLINE_LINUX_DEFAULT=\"quiet splash\"\nGRUB_CMDLINE_LINUX=\"\"\n";
+
+    #[test]
+    fn identical() {
+        let diff = diff_lines(OLD, OLD);
+        assert!(diff.iter().all(|l| matches!(l, DiffLine::Same(_))));
+        assert_eq!(count_changes(&diff), (0, 0));
+        assert!(unified(&diff, 3).is_empty());
+    }
+
+    #[test]
+    fn changed_line() {
+        let new = OLD.replace("GRUB_TIMEOUT=5", "GRUB_TIMEOUT=10");
+        let diff = diff_lines(OLD, &new);
+        assert_eq!(count_changes(&diff), (1, 1));
+        assert_eq!(diff[2], DiffLine::Removed("GRUB_TIMEOUT=5".to_string()));
+        assert_eq!(diff[3], DiffLine::Added("GRUB_TIMEOUT=10".to_string()));
+        assert_eq!(sides(&diff), (OLD.to_string(), new));
+
+        assert_eq!(unified(&diff, 1), vec![
+            "@@ -2,3 +2,3 @@",
+            " GRUB_TIMEOUT_STYLE=hidden",
+            "-GRUB_TIMEOUT=5",
+            "+GRUB_TIMEOUT=10",
+            " GRUB_DISTRIBUTOR=Ubuntu",
+        ]);
+    }
+
+    #[test]
+    fn separate_hunks() {
+        let new = OLD.replace("GRUB_DEFAULT=0", "GRUB_DEFAULT=saved")
+            .replace("GRUB_CMDLINE_LINUX=\"\"", "GRUB_CMDLINE_LINUX=\"\"\nGRUB_DISABLE_OS_PROBER=false");
+        let diff = diff_lines(OLD, &new);
+        assert_eq!(count_changes(&diff), (2, 1));
+        assert_eq!(unified(&diff, 1), vec![
+            "@@ -1,2 +1,2 @@",
+            "-GRUB_DEFAULT=0",
+            "+GRUB_DEFAULT=saved",
+            " GRUB_TIMEOUT_STYLE=hidden",
+            "@@ -6,1 +6,2 @@",
+            " GRUB_CMDLINE_LINUX=\"\"",
+            "+GRUB_DISABLE_OS_PROBER=false",
+        ]);
+        assert_eq!(unified(&diff, 0)[3], "@@ -6,0 +7,1 @@");
+        // With more context the hunks merge
+        assert_eq!(unified(&diff, 3).iter().filter(|l| l.starts_with("@@")).count(), 1);
+    }
+
+    #[test]
+    fn new_and_removed_file() {
+        let diff = diff_lines("", "a\nb\n");
+        assert_eq!(diff, vec![DiffLine::Added("a".to_string()), DiffLine::Added("b".to_string())]);
+        assert_eq!(unified(&diff, 3)[0], "@@ -0,0 +1,2 @@");
+
+        let diff = diff_lines("a\nb\n", "");
+        assert_eq!(count_changes(&diff), (0, 2));
+        assert_eq!(unified(&diff, 3)[0], "@@ -1,2 +0,0 @@");
+    }
+
+    #[test]
+    fn common_subsequence() {
+        let diff = diff_lines("a\nb\nc\nd\n", "a\nc\nb\nd\n");
+        assert_eq!(count_changes(&diff), (1, 1));
+        let (old, new) = sides(&diff);
+        assert_eq!(old, "a\nb\nc\nd\n");
+        assert_eq!(new, "a\nc\nb\nd\n");
+    }
+}
@@ -0,0 +1,430 @@
+// Export and import of the grublist configuration, to set up another
+// machine the same way.
+//
+// The document holds the effective settings, the parameter profiles, the
+// custom entry names and the default entry. Menus differ between machines,
+// so entries are stored by ID path and title instead of by position. On
+// import each one is looked up in the local menu: by ID path, by title, then
+// by ID with the trailing filesystem UUID left out (gnulinux-simple-<uuid>).
+// Entries that still don't match can be mapped to a local entry by hand.
+//
+// Settings and kernel parameters naming the exporting machine's disks
+// (root=UUID=..., resume=, cryptdevice=, GRUB_ENABLE_CRYPTODISK, ...) are
+// never merged, the local ones are kept and differences reported as
+// conflicts.
+
+use std::collections::BTreeMap;
+use std::fs;
+use serde::{Deserialize, Serialize};
+use crate::custom_names::{self, CustomNames};
+use crate::grub::{self, Entry, EntryType};
+use crate::grub_config::{self, GrubConfig, PendingWrite, SaveTarget};
+use crate::grubenv::{self, GrubEnv};
+use crate::paths::Paths;
+use crate::profiles::{ActiveProfile, Profiles};
+
+pub const FORMAT: &str = "grublist-export";
+// Bumped when a field is renamed, removed or changes meaning
+pub const VERSION: u32 = 1;
+
+// Settings about this machine's disks and installed systems
+const MACHINE_SETTINGS: &[&str] = &[
+    "GRUB_ENABLE_CRYPTODISK",
+    "GRUB_DISABLE_OS_PROBER",
+    "GRUB_OS_PROBER_SKIP_LIST",
+    "GRUB_DISABLE_LINUX_UUID",
+    "GRUB_DISABLE_LINUX_PARTUUID",
+];
+
+// Kernel parameters naming a disk, partition or volume
+const MACHINE_PARAMS: &[&str] = &[
+    "root", "rootflags", "resume", "resume_offset", "cryptdevice", "cryptkey",
+    "rd.luks.uuid", "rd.luks.name", "luks.uuid", "luks.name",
+    "rd.lvm.lv", "rd.lvm.vg", "rd.md.uuid",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Export {
+    pub format: String,
+    pub version: u32,
+    // RFC 3339
+    pub exported: String,
+    #[serde(default)]
+    pub host: String,
+    // Effective settings, GRUB_DEFAULT is in `default`
+    pub settings: BTreeMap<String, String>,
+    pub default: DefaultEntry,
+    pub profiles: Profiles,
+    #[serde(default)]
+    pub custom_names: Vec<CustomName>,
+}
+
+/// A menu entry as another machine can find it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntryRef {
+    // ID path, None if an entry along the path has no ID
+    pub id: Option<String>,
+    // Title path, e.g. "Advanced options for Ubuntu>Ubuntu, with Linux 6.5.0-14-generic"
+    pub title: String,
+}
+
+impl EntryRef {
+    /// ID path if there is one, for messages and --map
+    pub fn key(&self) -> &str {
+        self.id.as_deref().unwrap_or(&self.title)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DefaultEntry {
+    // GRUB_DEFAULT=saved, the entry is grubenv's saved_entry
+    pub saved: bool,
+    // None when the default matches no entry
+    pub entry: Option<EntryRef>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomName {
+    pub entry: EntryRef,
+    pub name: String,
+}
+
+/// Export the configuration of the managed system
+pub fn export(paths: &Paths) -> Result<Export, String> {
+    let menu = grub::load_grub(paths)
+        .ok_or_else(|| format!("Could not read {}", paths.display(&paths.grub_cfg())))?;
+    let config = GrubConfig::load(paths)?;
+    let env = GrubEnv::load(paths)?;
+
+    let mut settings: BTreeMap<String, String> = config.get_all_params().clone().into_iter().collect();
+    settings.remove("GRUB_DEFAULT");
+
+    let saved = grubenv::is_saved(&config.grub_default);
+    let spec = if saved {
+        env.get("saved_entry").filter(|v| !v.is_empty()).unwrap_or("0").to_string()
+    } else {
+        let value = config.grub_default.trim_matches('"').trim_matches('\'');
+        if value.is_empty() { "0".to_string() } else { value.to_string() }
+    };
+    let entry = grub::resolve_entry_path(&menu, &spec).map(|path| entry_ref(&menu, &path));
+
+    let names = CustomNames::load(paths);
+    let mut custom_names: Vec<(Vec<usize>, CustomName)> = names.names.iter()
+        .filter_map(|(path, name)| {
+            let path = custom_names::string_to_path(path);
+            // Names of entries that are gone are left behind
+            grub::try_get_entry(&menu, &path)?;
+            Some((path.clone(), CustomName { entry: entry_ref(&menu, &path), name: name.clone() }))
+        })
+        .collect();
+    custom_names.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(Export {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        exported: chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        host: fs::read_to_string(paths.resolve("/etc/hostname")).map(|h| h.trim().to_string()).unwrap_or_default(),
+        settings,
+        default: DefaultEntry { saved, entry },
+        profiles: Profiles::load(paths)?,
+        custom_names: custom_names.into_iter().map(|(_, name)| name).collect(),
+    })
+}
+
+/// Read an exported document, refusing ones from a newer grublist
+pub fn parse(content: &str) -> Result<Export, String> {
+    let value: serde_json::Value = serde_json::from_str(content)
+        .map_err(|e| format!("Not a JSON document: {}", e))?;
+    if value.get("format").and_then(|f| f.as_str()) != Some(FORMAT) {
+        return Err("Not a grublist export".to_string());
+    }
+    let version = value.get("version").and_then(|v| v.as_u64()).unwrap_or(0);
+    if version != VERSION as u64 {
+        return Err(format!("Unsupported export version {} (this grublist reads version {})", version, VERSION));
+    }
+    serde_json::from_value(value).map_err(|e| format!("Invalid export: {}", e))
+}
+
+pub fn entry_ref(menu: &Entry, path: &[usize]) -> EntryRef {
+    let mut e = menu;
+    let mut titles = Vec::new();
+    for &idx in path {
+        e = &e.children[idx];
+        titles.push(e.name.clone());
+    }
+    EntryRef { id: grub::entry_id_path(menu, path), title: titles.join(">") }
+}
+
+/// Find an exported entry in the local menu. `map` holds entries mapped by
+/// hand: an exported ID path or title and the local entry (any GRUB_DEFAULT
+/// style value).
+pub fn resolve(menu: &Entry, entry: &EntryRef, map: &[(String, String)]) -> Option<Vec<usize>> {
+    if let Some((_, local)) = map.iter().find(|(from, _)| Some(from) == entry.id.as_ref() || *from == entry.title) {
+        return grub::resolve_entry_path(menu, local);
+    }
+    // resolve_entry_path() takes an index, ID or title at each level, only
+    // a match of the whole ID path or title path counts
+    let by_id = entry.id.as_deref()
+        .and_then(|id| grub::resolve_entry_path(menu, id))
+        .filter(|path| entry_ref(menu, path).id == entry.id);
+    let by_title = || grub::resolve_entry_path(menu, &entry.title)
+        .filter(|path| entry_ref(menu, path).title == entry.title);
+    by_id.or_else(by_title)
+        .or_else(|| resolve_stem(menu, entry.id.as_deref()?))
+}
+
+// Match every level of an ID path by its stem, when exactly one entry has it
+fn resolve_stem(menu: &Entry, id_path: &str) -> Option<Vec<usize>> {
+    let mut e = menu;
+    let mut path = Vec::new();
+    for id in id_path.split('>') {
+        let stem = id_stem(id);
+        let mut matching = e.children.iter().enumerate()
+            .filter(|(_, c)| c.id.as_deref().map(id_stem) == Some(stem));
+        let (idx, child) = matching.next()?;
+        if matching.next().is_some() {
+            return None;
+        }
+        path.push(idx);
+        e = child;
+    }
+    Some(path)
+}
+
+// "gnulinux-simple-1c0a5c9e-3f1a-4b7d-9d5e-2c7e1b0c9a11" -> "gnulinux-simple"
+fn id_stem(id: &str) -> &str {
+    let mut stem = id;
+    while let Some((rest, last)) = stem.rsplit_once('-') {
+        if last.is_empty() || !last.chars().all(|c| c.is_ascii_hexdigit()) {
+            break;
+        }
+        stem = rest;
+    }
+    stem
+}
+
+/// What an import changes, to preview it before anything is written
+pub struct Plan {
+    // Settings file write, including GRUB_DEFAULT
+    pub write: Option<PendingWrite>,
+    // New saved_entry
+    pub saved_entry: Option<String>,
+    pub profiles: Option<Profiles>,
+    pub custom_names: Option<CustomNames>,
+    // One line per change besides the settings file
+    pub changes: Vec<String>,
+    // Exported entries with no local match, their names are skipped
+    pub missing: Vec<EntryRef>,
+    // Machine-specific settings and parameters the export has differently,
+    // the local ones are kept
+    pub conflicts: Vec<String>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.write.is_none() && self.saved_entry.is_none() && self.profiles.is_none() && self.custom_names.is_none()
+    }
+
+    /// Write everything, the settings first so their backup is taken
+    /// before anything else changes
+    pub fn apply(&self, paths: &Paths, action: &str) -> Result<(), String> {
+        if let Some(write) = &self.write {
+            write.write(action)?;
+        }
+        if let Some(entry) = &self.saved_entry {
+            grubenv::set_saved_entry(paths, entry)?;
+        }
+        if let Some(profiles) = &self.profiles {
+            profiles.save(paths)
+                .map_err(|e| format!("Failed to write {}: {}", paths.display(&paths.profiles()), e))?;
+        }
+        if let Some(names) = &self.custom_names {
+            names.save(paths)
+                .map_err(|e| format!("Failed to write {}: {}", paths.display(&paths.custom_names()), e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Work out what importing `export` changes. Settings, profiles and names
+/// are merged: ones the export doesn't have are kept, and so are
+/// machine-specific settings and parameters. Fails if the default entry has
+/// no local match.
+pub fn plan(paths: &Paths, export: &Export, target: SaveTarget, map: &[(String, String)]) -> Result<Plan, String> {
+    let menu = grub::load_grub(paths)
+        .ok_or_else(|| format!("Could not read {}", paths.display(&paths.grub_cfg())))?;
+    let mut config = GrubConfig::load(paths)?;
+    let env = GrubEnv::load(paths)?;
+    let mut changes = Vec::new();
+    let mut conflicts = Vec::new();
+
+    for (key, value) in &export.settings {
+        let local = config.get(key).cloned();
+        let value = if MACHINE_SETTINGS.contains(&key.as_str()) {
+            if local.as_ref() != Some(value) {
+                conflicts.push(format!("{}={} in the export, kept {} here",
+                    key, value, local.as_deref().unwrap_or("unset")));
+            }
+            continue;
+        } else if key.starts_with("GRUB_CMDLINE_") {
+            let merged = merge_cmdline(key, value, local.as_deref().unwrap_or(""), &mut conflicts);
+            if local.is_none() && merged.is_empty() {
+                continue;
+            }
+            merged
+        } else {
+            value.clone()
+        };
+        if local != Some(value.clone()) {
+            config.set(key, value);
+        }
+    }
+
+    let mut saved_entry = None;
+    if let Some(entry) = &export.default.entry {
+        let path = resolve(&menu, entry, map)
+            .filter(|path| grub::get_entry(&menu, path).entry_type == EntryType::MenuEntry)
+            .ok_or_else(|| format!("The default entry '{}' ({}) doesn't exist here, map it with --map='{}=ENTRY'",
+                entry.title, entry.key(), entry.key()))?;
+        let value = grub::entry_id_path(&menu, &path).unwrap_or_else(|| custom_names::path_to_string(&path));
+        let local = entry_ref(&menu, &path);
+        if local.title != entry.title || local.id != entry.id {
+            changes.push(format!("Default entry '{}' is '{}' ({}) here", entry.title, local.title, value));
+        }
+        if export.default.saved {
+            if !grubenv::is_saved(&config.grub_default) {
+                config.set("GRUB_DEFAULT", "saved".to_string());
+            }
+            if env.get("saved_entry") != Some(value.as_str()) {
+                changes.push(format!("saved_entry={} ({})", value, local.title));
+                saved_entry = Some(value);
+            }
+        } else if config.grub_default.trim_matches('"').trim_matches('\'') != value {
+            config.set("GRUB_DEFAULT", value);
+        }
+    }
+    let write = config.prepare_save(target)?;
+
+    let local = Profiles::load(paths)?;
+    let mut profiles = local.clone();
+    for profile in &export.profiles.profiles {
+        match profiles.profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) if existing == profile => {}
+            Some(existing) => {
+                *existing = profile.clone();
+                changes.push(format!("Profile '{}' replaced", profile.name));
+            }
+            None => {
+                profiles.profiles.push(profile.clone());
+                changes.push(format!("Profile '{}' added", profile.name));
+            }
+        }
+    }
+    // The imported command line has the exported profile applied
+    if profiles.active != export.profiles.active {
+        let name = |active: &Option<ActiveProfile>| {
+            active.as_ref().map(|a| a.name.clone()).unwrap_or_else(|| "none".to_string())
+        };
+        changes.push(format!("Active profile: {} → {}", name(&profiles.active), name(&export.profiles.active)));
+        profiles.active = export.profiles.active.clone();
+    }
+    let profiles_changed = profiles.profiles != local.profiles || profiles.active != local.active;
+
+    let mut names = CustomNames::load(paths);
+    let mut names_changed = false;
+    let mut missing = Vec::new();
+    for custom in &export.custom_names {
+        let Some(path) = resolve(&menu, &custom.entry, map) else {
+            missing.push(custom.entry.clone());
+            continue;
+        };
+        if names.get_custom_name(&path) != Some(&custom.name) {
+            changes.push(format!("Custom name of '{}' ({}): {}",
+                grub::get_entry(&menu, &path).name, custom_names::path_to_string(&path), custom.name));
+            names.set_custom_name(&path, custom.name.clone());
+            names_changed = true;
+        }
+    }
+
+    Ok(Plan {
+        write,
+        saved_entry,
+        profiles: profiles_changed.then_some(profiles),
+        custom_names: names_changed.then_some(names),
+        changes,
+        missing,
+        conflicts,
+    })
+}
+
+/// Kernel parameter naming a disk, partition or volume of one machine
+pub fn is_machine_param(param: &str) -> bool {
+    let (name, value) = grub_config::split_parameter(param);
+    let value = value.unwrap_or_default();
+    MACHINE_PARAMS.contains(&name.as_str())
+        || ["UUID=", "LABEL=", "/dev/"].iter().any(|p| value.contains(p))
+}
+
+// The exported command line with the local machine-specific parameters in
+// place of the exported ones
+fn merge_cmdline(key: &str, exported: &str, local: &str, conflicts: &mut Vec<String>) -> String {
+    let (theirs, params): (Vec<String>, Vec<String>) = grub_config::parse_parameters(exported)
+        .into_iter()
+        .partition(|p| is_machine_param(p));
+    let (ours, local_params): (Vec<String>, Vec<String>) = grub_config::parse_parameters(local)
+        .into_iter()
+        .partition(|p| is_machine_param(p));
+    if theirs != ours {
+        let show = |params: &[String]| if params.is_empty() { "none".to_string() } else { grub_config::join_parameters(params) };
+        conflicts.push(format!("{}: {} in the export, kept {} here", key, show(&theirs), show(&ours)));
+    }
+    if params == local_params {
+        return local.to_string();
+    }
+    // In front, where they usually are
+    grub_config::join_parameters(&[ours, params].concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn machine_params() {
+        for param in ["root=UUID=1234", "root=/dev/mapper/vg-root", "resume=UUID=5678", "rootflags=subvol=@",
+            "cryptdevice=UUID=abcd:cryptroot", "rd.luks.uuid=luks-abcd", "rd.lvm.lv=vg/root",
+            "systemd.swap=PARTUUID=ef01", "\"rd.luks.name=abcd=root\""] {
+            assert!(is_machine_param(param), "{}", param);
+        }
+        for param in ["quiet", "splash", "console=ttyS0,115200", "iommu=pt", "dyndbg=\"file foo.c +p\"", "--"] {
+            assert!(!is_machine_param(param), "{}", param);
+        }
+    }
+
+    #[test]
+    fn cmdline_keeps_local_disks() {
+        let mut conflicts = Vec::new();
+        let merged = merge_cmdline("GRUB_CMDLINE_LINUX", "root=UUID=aaaa quiet iommu=pt", "quiet root=UUID=bbbb", &mut conflicts);
+        assert_eq!(merged, "root=UUID=bbbb quiet iommu=pt");
+        assert_eq!(conflicts, vec!["GRUB_CMDLINE_LINUX: root=UUID=aaaa in the export, kept root=UUID=bbbb here"]);
+    }
+
+    #[test]
+    fn cmdline_unchanged_apart_from_disks() {
+        let mut conflicts = Vec::new();
+        let local = "quiet  resume=UUID=bbbb splash";
+        assert_eq!(merge_cmdline("GRUB_CMDLINE_LINUX_DEFAULT", "quiet splash", local, &mut conflicts), local);
+        assert_eq!(conflicts, vec!["GRUB_CMDLINE_LINUX_DEFAULT: none in the export, kept resume=UUID=bbbb here"]);
+
+        conflicts.clear();
+        assert_eq!(merge_cmdline("GRUB_CMDLINE_LINUX", "nomodeset", "", &mut conflicts), "nomodeset");
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn id_stems() {
+        assert_eq!(id_stem("gnulinux-simple-1c0a5c9e-3f1a-4b7d-9d5e-2c7e1b0c9a11"), "gnulinux-simple");
+        assert_eq!(id_stem("gnulinux-6.5.0-14-generic-advanced-1234"), "gnulinux-6.5.0-14-generic-advanced");
+        assert_eq!(id_stem("uefi-firmware"), "uefi-firmware");
+    }
+}
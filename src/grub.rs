@@ -1,19 +1,106 @@
-use regex::Regex;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use crate::grub_script::{self, Node};
+use serde::Serialize;
+use crate::paths::Paths;
+use std::fs;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum EntryType {
     Root,
     MenuEntry,
     Submenu,
 }
 
-#[derive(Debug, Clone)]
+// Boot commands found in a menuentry body, i.e. what GRUB will actually boot
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BootCommands {
+    pub linux: Option<String>,
+    pub linux_args: Vec<String>,
+    pub initrd: Vec<String>,
+    pub search_uuid: Option<String>,
+    pub root: Option<String>,
+    pub insmod: Vec<String>,
+    pub chainloader: Option<String>,
+}
+
+impl BootCommands {
+    fn from_body(nodes: &[Node]) -> Self {
+        let mut boot = BootCommands::default();
+        for node in nodes {
+            if let Node::Command(cmd) = node {
+                boot.apply(&cmd.words);
+            }
+        }
+        boot
+    }
+
+    fn apply(&mut self, words: &[String]) {
+        let Some((name, args)) = skip_keywords(words).split_first() else {
+            return;
+        };
+
+        match name.as_str() {
+            "linux" | "linux16" | "linuxefi" => {
+                self.linux = args.first().cloned();
+                self.linux_args = args.iter().skip(1).cloned().collect();
+            }
+            "initrd" | "initrd16" | "initrdefi" => {
+                self.initrd = args.to_vec();
+            }
+            "insmod" => {
+                self.insmod.extend(args.iter().cloned());
+            }
+            "chainloader" => {
+                self.chainloader = args.iter().find(|a| !a.starts_with('-')).cloned();
+            }
+            "set" => {
+                if let Some(root) = args.iter().find_map(|a| a.strip_prefix("root=")) {
+                    self.root = Some(root.to_string());
+                }
+            }
+            "search" | "search.fs_uuid" => {
+                let mut sets_root = true;
+                let mut by_uuid = name == "search.fs_uuid";
+                let mut positional = Vec::new();
+                for arg in args {
+                    match arg.as_str() {
+                        "--fs-uuid" | "-u" => by_uuid = true,
+                        // A bare --set sets "root"
+                        "--set" | "-s" => sets_root = true,
+                        a if a.starts_with("--set=") => sets_root = a == "--set=root",
+                        a if a.starts_with('-') => {}
+                        a => positional.push(a.to_string()),
+                    }
+                }
+                if by_uuid && sets_root {
+                    if let Some(uuid) = positional.into_iter().next() {
+                        self.search_uuid = Some(uuid);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Kernel command line as passed by the `linux` command
+    pub fn cmdline(&self) -> String {
+        self.linux_args.join(" ")
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Entry {
     pub name: String,
+    #[serde(rename = "type")]
     pub entry_type: EntryType,
     pub children: Vec<Entry>,
+    // Options from the menuentry/submenu line
+    pub id: Option<String>,
+    pub classes: Vec<String>,
+    pub users: Option<String>,
+    pub hotkey: Option<String>,
+    pub unrestricted: bool,
+    pub boot: BootCommands,
 }
 
 impl Entry {
@@ -22,69 +109,208 @@ impl Entry {
             name,
             entry_type,
             children: Vec::new(),
+            id: None,
+            classes: Vec::new(),
+            users: None,
+            hotkey: None,
+            unrestricted: false,
+            boot: BootCommands::default(),
+        }
+    }
+
+    // Parse the arguments following `menuentry`/`submenu`. Options may come
+    // before or after the title; the first positional argument is the title.
+    pub fn from_header(args: &[String], entry_type: EntryType) -> Self {
+        let mut entry = Entry::new(String::new(), entry_type);
+        let mut title = None;
+        let mut options_done = false;
+        let mut iter = args.iter();
+
+        while let Some(arg) = iter.next() {
+            if options_done || !arg.starts_with("--") && !is_id_option(arg) {
+                if title.is_none() {
+                    title = Some(arg.clone());
+                }
+                continue;
+            }
+            if arg == "--" {
+                options_done = true;
+                continue;
+            }
+
+            // Both "--opt value" and "--opt=value" forms are accepted
+            let (opt, inline_value) = match arg.split_once('=') {
+                Some((opt, value)) => (opt, Some(value.to_string())),
+                None => (arg.as_str(), None),
+            };
+            let opt = if is_id_option(opt) { "--id" } else { opt };
+            let takes_value = matches!(opt, "--id" | "--class" | "--users" | "--hotkey" | "--source");
+            let value = if takes_value {
+                inline_value.or_else(|| iter.next().cloned())
+            } else {
+                None
+            };
+
+            match opt {
+                "--id" => entry.id = value,
+                "--class" => entry.classes.extend(value),
+                "--users" => entry.users = value,
+                "--hotkey" => entry.hotkey = value,
+                "--unrestricted" => entry.unrestricted = true,
+                _ => {}
+            }
         }
+
+        entry.name = title.unwrap_or_default();
+        entry
     }
 }
 
-pub fn load_grub() -> Option<Entry> {
-    let file = File::open("/boot/grub/grub.cfg").ok()?;
-    
+// grub-mkconfig writes `$menuentry_id_option 'id'`, which expands to `--id`
+fn is_id_option(arg: &str) -> bool {
+    arg == "$menuentry_id_option" || arg == "${menuentry_id_option}"
+}
+
+pub fn load_grub(paths: &Paths) -> Option<Entry> {
+    let content = fs::read_to_string(paths.grub_cfg()).ok()?;
+    let bls = load_bls_entries(paths);
+    Some(parse_grub(&content, &bls))
+}
+
+pub fn parse_grub(content: &str, bls: &[Entry]) -> Entry {
     let mut entry = Entry::new("root".to_string(), EntryType::Root);
-    let mut level: usize = 0;
-    
-    let menuentry_re = Regex::new(r"^\s*(menuentry|submenu)\s*'([^']*)'").unwrap();
-    let open_brace_re = Regex::new(r"\{\s*$").unwrap();
-    let close_brace_re = Regex::new(r"^\s*\}").unwrap();
-    
-    for line in BufReader::new(file).lines() {
-        let line = line.ok()?;
-        
-        // Check for menuentry or submenu
-        if let Some(caps) = menuentry_re.captures(&line) {
-            let entry_type_str = caps.get(1)?.as_str();
-            let name = caps.get(2)?.as_str();
-            
-            // Navigate to the correct level (similar to Python: e = entry; for i in range(0, level): e = e['child'][childnum-1])
-            // We need to navigate through the tree structure using a stack or mutable references
-            // Since we can't easily do mutable references through multiple levels, we'll use indices
-            let mut path: Vec<usize> = Vec::new();
-            for _ in 0..level {
-                path.push(0); // We'll update these indices as we build
-            }
-            
-            // Navigate to the parent entry at the correct level
-            let mut current = &mut entry;
-            for _ in 0..level {
-                if current.children.is_empty() {
-                    break;
+    collect_entries(&grub_script::parse(content), &mut entry, bls);
+    entry
+}
+
+// Walk the script tree the way GRUB builds its menu: every menuentry and
+// submenu block counts towards the index at its level, submenus nest
+fn collect_entries(nodes: &[Node], parent: &mut Entry, bls: &[Entry]) {
+    for node in nodes {
+        match node {
+            Node::Block { header, body } => {
+                let words = skip_keywords(&header.words);
+                let entry_type = match words.first().map(|w| w.as_str()) {
+                    Some("menuentry") => EntryType::MenuEntry,
+                    Some("submenu") => EntryType::Submenu,
+                    // Functions are only definitions, their bodies are not part of the menu
+                    Some("function") => continue,
+                    _ => {
+                        collect_entries(body, parent, bls);
+                        continue;
+                    }
+                };
+
+                let mut new_entry = Entry::from_header(&words[1..], entry_type);
+                if new_entry.entry_type == EntryType::Submenu {
+                    collect_entries(body, &mut new_entry, bls);
+                } else {
+                    new_entry.boot = BootCommands::from_body(body);
                 }
-                let last_idx = current.children.len() - 1;
-                current = current.children.get_mut(last_idx)?;
+                parent.children.push(new_entry);
             }
-            
-            // Create new entry
-            let entry_type = match entry_type_str {
-                "menuentry" => EntryType::MenuEntry,
-                "submenu" => EntryType::Submenu,
-                _ => EntryType::MenuEntry,
+            // Fedora/RHEL: entries come from Boot Loader Specification snippets
+            Node::Command(cmd) if skip_keywords(&cmd.words).first().is_some_and(|w| w == "blscfg") => {
+                parent.children.extend(bls.iter().cloned());
+            }
+            Node::Command(_) => {}
+        }
+    }
+}
+
+// `then insmod xzio`, `else menuentry ...` and friends: skip the shell
+// keywords in front of the command
+fn skip_keywords(words: &[String]) -> &[String] {
+    let start = words.iter()
+        .position(|w| !matches!(w.as_str(), "then" | "else" | "do"))
+        .unwrap_or(words.len());
+    &words[start..]
+}
+
+/// Read /boot/loader/entries/*.conf the way GRUB's blscfg command does:
+/// newest version first, the file name (without .conf) is the entry ID
+pub fn load_bls_entries(paths: &Paths) -> Vec<Entry> {
+    let mut entries: Vec<(String, Entry)> = Vec::new();
+    let Ok(dir) = fs::read_dir(paths.bls_entries_dir()) else {
+        return Vec::new();
+    };
+
+    for file in dir.flatten() {
+        let path = file.path();
+        let Some(id) = path.file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_suffix(".conf")) else {
+            continue;
+        };
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+
+        let mut entry = Entry::new(id.to_string(), EntryType::MenuEntry);
+        entry.id = Some(id.to_string());
+        let mut version = String::new();
+        for line in content.lines() {
+            let line = line.trim();
+            let Some((key, value)) = line.split_once(char::is_whitespace) else {
+                continue;
             };
-            
-            let new_entry = Entry::new(name.to_string(), entry_type);
-            current.children.push(new_entry);
+            let value = value.trim();
+            match key {
+                "title" => entry.name = value.to_string(),
+                "version" => version = value.to_string(),
+                "linux" => entry.boot.linux = Some(value.to_string()),
+                "initrd" => entry.boot.initrd.extend(value.split_whitespace().map(|s| s.to_string())),
+                "options" => entry.boot.linux_args.extend(value.split_whitespace().map(|s| s.to_string())),
+                "grub_class" => entry.classes.push(value.to_string()),
+                "grub_users" => entry.users = Some(value.to_string()),
+                "grub_hotkey" => entry.hotkey = Some(value.to_string()),
+                _ => {}
+            }
         }
-        
-        // Check for opening brace
-        if open_brace_re.is_match(&line) {
-            level += 1;
+        if version.is_empty() {
+            version = id.to_string();
         }
-        
-        // Check for closing brace
-        if close_brace_re.is_match(&line) {
-            level = level.saturating_sub(1);
+        entries.push((version, entry));
+    }
+
+    entries.sort_by(|a, b| version_cmp(&b.0, &a.0).then_with(|| b.1.name.cmp(&a.1.name)));
+    entries.into_iter().map(|(_, e)| e).collect()
+}
+
+// Compare version strings with digit runs as numbers ("5.14.0-362" > "5.14.0-70")
+fn version_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return std::cmp::Ordering::Equal,
+            (None, Some(_)) => return std::cmp::Ordering::Less,
+            (Some(_), None) => return std::cmp::Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let mut na = String::new();
+                while let Some(c) = a.next_if(|c| c.is_ascii_digit()) {
+                    na.push(c);
+                }
+                let mut nb = String::new();
+                while let Some(c) = b.next_if(|c| c.is_ascii_digit()) {
+                    nb.push(c);
+                }
+                let na = na.trim_start_matches('0');
+                let nb = nb.trim_start_matches('0');
+                let ord = na.len().cmp(&nb.len()).then_with(|| na.cmp(nb));
+                if ord != std::cmp::Ordering::Equal {
+                    return ord;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
         }
     }
-    
-    Some(entry)
 }
 
 pub fn get_entry<'a>(root: &'a Entry, path: &[usize]) -> &'a Entry {
@@ -106,3 +332,218 @@ pub fn try_get_entry<'a>(root: &'a Entry, path: &[usize]) -> Option<&'a Entry> {
     Some(e)
 }
 
+
+/// Build the stable ID path (e.g. "gnulinux-advanced-<uuid>>gnulinux-<ver>-advanced-<uuid>")
+/// for a numeric path. Returns None if any entry along the path has no ID.
+pub fn entry_id_path(root: &Entry, path: &[usize]) -> Option<String> {
+    let mut e = root;
+    let mut ids = Vec::new();
+    for &idx in path {
+        e = e.children.get(idx)?;
+        ids.push(e.id.clone()?);
+    }
+    Some(ids.join(">"))
+}
+
+/// Resolve a GRUB_DEFAULT style value to a numeric path. Each ">" separated
+/// component is matched the way GRUB does: as an index, an entry ID or a title.
+pub fn resolve_entry_path(root: &Entry, spec: &str) -> Option<Vec<usize>> {
+    let spec = spec.trim_matches('"').trim_matches('\'');
+    if spec.is_empty() {
+        return None;
+    }
+
+    let mut e = root;
+    let mut path = Vec::new();
+    for part in spec.split('>') {
+        let idx = match part.parse::<usize>() {
+            Ok(idx) if idx < e.children.len() => idx,
+            Ok(_) => return None,
+            Err(_) => e.children.iter().position(|c| {
+                c.id.as_deref() == Some(part) || c.name == part
+            })?,
+        };
+        path.push(idx);
+        e = &e.children[idx];
+    }
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRUB_CFG: &str = r#"
+function load_video {
+  insmod all_video
+}
+menuentry 'Ubuntu' --class ubuntu --class gnu-linux --class os $menuentry_id_option 'gnulinux-simple-1234' {
+	recordfail
+	load_video
+	insmod gzio
+	if [ x$grub_platform = xxen ]; then insmod xzio; insmod lzopio; fi
+	insmod part_gpt
+	insmod ext2
+	search --no-floppy --fs-uuid --set=root 1234
+	linux	/boot/vmlinuz-6.5.0-14-generic root=UUID=1234 ro quiet splash $vt_handoff
+	initrd	/boot/initrd.img-6.5.0-14-generic
+}
+submenu 'Advanced options for Ubuntu' $menuentry_id_option 'gnulinux-advanced-1234' {
+	menuentry 'Ubuntu, with Linux 6.5.0-14-generic' --class ubuntu $menuentry_id_option 'gnulinux-6.5.0-14-generic-advanced-1234' {
+		linux	/boot/vmlinuz-6.5.0-14-generic root=UUID=1234 ro quiet splash
+		initrd	/boot/initrd.img-6.5.0-14-generic
+	}
+	menuentry 'Ubuntu, with Linux 6.5.0-14-generic (recovery mode)' {
+		linux	/boot/vmlinuz-6.5.0-14-generic root=UUID=1234 ro recovery nomodeset
+	}
+}
+if [ "${grub_platform}" == "efi" ]; then
+  menuentry "UEFI Firmware Settings" --id uefi-firmware {
+	fwsetup
+  }
+fi
+"#;
+
+    fn args(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn header_options_around_title() {
+        let entry = Entry::from_header(
+            &args(&["Ubuntu", "--class", "ubuntu", "--class=os", "$menuentry_id_option", "gnulinux-simple-1234"]),
+            EntryType::MenuEntry,
+        );
+        assert_eq!(entry.name, "Ubuntu");
+        assert_eq!(entry.classes, vec!["ubuntu", "os"]);
+        assert_eq!(entry.id.as_deref(), Some("gnulinux-simple-1234"));
+        assert!(entry.users.is_none());
+        assert!(!entry.unrestricted);
+
+        let entry = Entry::from_header(
+            &args(&["--users", "admin", "--hotkey=r", "--unrestricted", "Rescue", "--id", "rescue"]),
+            EntryType::MenuEntry,
+        );
+        assert_eq!(entry.name, "Rescue");
+        assert_eq!(entry.users.as_deref(), Some("admin"));
+        assert_eq!(entry.hotkey.as_deref(), Some("r"));
+        assert!(entry.unrestricted);
+        assert_eq!(entry.id.as_deref(), Some("rescue"));
+    }
+
+    #[test]
+    fn header_double_dash_ends_options() {
+        let entry = Entry::from_header(&args(&["--", "--weird title"]), EntryType::MenuEntry);
+        assert_eq!(entry.name, "--weird title");
+        assert!(entry.id.is_none());
+    }
+
+    #[test]
+    fn menu_tree() {
+        let root = parse_grub(GRUB_CFG, &[]);
+        let names: Vec<&str> = root.children.iter().map(|e| e.name.as_str()).collect();
+        // The function body is not a menu entry, the entry inside `if` is
+        assert_eq!(names, vec!["Ubuntu", "Advanced options for Ubuntu", "UEFI Firmware Settings"]);
+        assert_eq!(root.children[1].entry_type, EntryType::Submenu);
+        assert_eq!(root.children[1].children.len(), 2);
+        assert_eq!(root.children[2].id.as_deref(), Some("uefi-firmware"));
+    }
+
+    #[test]
+    fn conditional_entries() {
+        let root = parse_grub(
+            "if [ -e /boot/memtest86+.bin ]; then menuentry 'Memory test' --id memtest {\n\
+             \tlinux16 /boot/memtest86+.bin\n\
+             }\n\
+             else submenu 'Other' {\n\
+             \tmenuentry 'Inner' { true; }\n\
+             }\n\
+             fi\n",
+            &[],
+        );
+        let names: Vec<&str> = root.children.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["Memory test", "Other"]);
+        assert_eq!(root.children[0].id.as_deref(), Some("memtest"));
+        assert_eq!(root.children[1].entry_type, EntryType::Submenu);
+        assert_eq!(root.children[1].children[0].name, "Inner");
+    }
+
+    #[test]
+    fn id_paths() {
+        let root = parse_grub(GRUB_CFG, &[]);
+        assert_eq!(
+            entry_id_path(&root, &[1, 0]).as_deref(),
+            Some("gnulinux-advanced-1234>gnulinux-6.5.0-14-generic-advanced-1234")
+        );
+        // The recovery entry has no ID
+        assert_eq!(entry_id_path(&root, &[1, 1]), None);
+        assert_eq!(entry_id_path(&root, &[5]), None);
+    }
+
+    #[test]
+    fn resolve_default_specs() {
+        let root = parse_grub(GRUB_CFG, &[]);
+        assert_eq!(resolve_entry_path(&root, "0"), Some(vec![0]));
+        assert_eq!(resolve_entry_path(&root, "1>1"), Some(vec![1, 1]));
+        assert_eq!(
+            resolve_entry_path(&root, "gnulinux-advanced-1234>gnulinux-6.5.0-14-generic-advanced-1234"),
+            Some(vec![1, 0])
+        );
+        assert_eq!(
+            resolve_entry_path(&root, "\"Advanced options for Ubuntu>Ubuntu, with Linux 6.5.0-14-generic (recovery mode)\""),
+            Some(vec![1, 1])
+        );
+        assert_eq!(resolve_entry_path(&root, "3"), None);
+        assert_eq!(resolve_entry_path(&root, "missing"), None);
+        assert_eq!(resolve_entry_path(&root, ""), None);
+    }
+
+    #[test]
+    fn version_order() {
+        use std::cmp::Ordering;
+        assert_eq!(version_cmp("5.14.0-362", "5.14.0-70"), Ordering::Greater);
+        assert_eq!(version_cmp("6.5.0", "6.5.0"), Ordering::Equal);
+        assert_eq!(version_cmp("6.5", "6.5.0"), Ordering::Less);
+        assert_eq!(version_cmp("007", "7"), Ordering::Equal);
+    }
+
+    #[test]
+    fn boot_commands() {
+        let root = parse_grub(GRUB_CFG, &[]);
+        let boot = &root.children[0].boot;
+        assert_eq!(boot.linux.as_deref(), Some("/boot/vmlinuz-6.5.0-14-generic"));
+        assert_eq!(boot.cmdline(), "root=UUID=1234 ro quiet splash $vt_handoff");
+        assert_eq!(boot.initrd, vec!["/boot/initrd.img-6.5.0-14-generic"]);
+        assert_eq!(boot.search_uuid.as_deref(), Some("1234"));
+        // `then insmod xzio` inside the if line still counts
+        assert_eq!(boot.insmod, vec!["gzio", "xzio", "lzopio", "part_gpt", "ext2"]);
+        assert!(boot.chainloader.is_none());
+
+        let recovery = &root.children[1].children[1].boot;
+        assert_eq!(recovery.cmdline(), "root=UUID=1234 ro recovery nomodeset");
+        assert!(recovery.initrd.is_empty());
+    }
+
+    #[test]
+    fn boot_commands_variants() {
+        let body = grub_script::parse(
+            "search.fs_uuid ABCD-1234 root hd0,gpt1\n\
+             search --label --set=root boot\n\
+             set root='hd0,gpt2'\n\
+             chainloader --force /EFI/Microsoft/Boot/bootmgfw.efi\n\
+             linuxefi /vmlinuz\n\
+             initrdefi /intel-ucode.img /initramfs.img\n",
+        );
+        let boot = BootCommands::from_body(&body);
+        assert_eq!(boot.search_uuid.as_deref(), Some("ABCD-1234"));
+        assert_eq!(boot.root.as_deref(), Some("hd0,gpt2"));
+        assert_eq!(boot.chainloader.as_deref(), Some("/EFI/Microsoft/Boot/bootmgfw.efi"));
+        assert_eq!(boot.linux.as_deref(), Some("/vmlinuz"));
+        assert_eq!(boot.cmdline(), "");
+        assert_eq!(boot.initrd, vec!["/intel-ucode.img", "/initramfs.img"]);
+
+        // --set=other stores the UUID in another variable, not root
+        let boot = BootCommands::from_body(&grub_script::parse("search --fs-uuid --set=other 5678\n"));
+        assert!(boot.search_uuid.is_none());
+    }
+}
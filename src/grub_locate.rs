@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::grub_script::{self, Node};
+use crate::paths::Paths;
+
+// Maximum number of stub redirections to follow
+const MAX_REDIRECTS: usize = 4;
+
+#[derive(Debug, Clone)]
+pub struct GrubCfgLocation {
+    // The grub.cfg that actually holds the menu
+    pub path: PathBuf,
+    // Stub files that were followed to get there, in order
+    pub via: Vec<PathBuf>,
+}
+
+impl GrubCfgLocation {
+    pub fn describe(&self, paths: &Paths) -> String {
+        let mut s = paths.display(&self.path);
+        for stub in self.via.iter().rev() {
+            s.push_str(&format!(" (via {})", paths.display(stub)));
+        }
+        s
+    }
+}
+
+/// Known grub.cfg locations, in the order they are probed:
+/// Debian/Ubuntu, Fedora/RHEL/openSUSE, then EFI system partition copies
+pub fn candidates(paths: &Paths) -> Vec<PathBuf> {
+    let mut list = vec![
+        paths.resolve("/boot/grub/grub.cfg"),
+        paths.resolve("/boot/grub2/grub.cfg"),
+    ];
+
+    for esp in ["/boot/efi/EFI", "/efi/EFI", "/boot/EFI"] {
+        if let Ok(entries) = fs::read_dir(paths.resolve(esp)) {
+            let mut dirs: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+            dirs.sort();
+            list.extend(dirs.into_iter().map(|d| d.join("grub.cfg")));
+        }
+    }
+    list
+}
+
+/// Find the grub.cfg GRUB will show, following EFI stubs that only
+/// `configfile` to the real file
+pub fn locate_grub_cfg(paths: &Paths) -> Option<GrubCfgLocation> {
+    for candidate in candidates(paths) {
+        if !candidate.is_file() {
+            continue;
+        }
+        if let Some(location) = follow(paths, candidate) {
+            return Some(location);
+        }
+    }
+    None
+}
+
+fn follow(paths: &Paths, start: PathBuf) -> Option<GrubCfgLocation> {
+    let mut current = start;
+    let mut via = Vec::new();
+
+    loop {
+        let content = fs::read_to_string(&current).ok()?;
+        let nodes = grub_script::parse(&content);
+        let target = match stub_target(&nodes) {
+            // A file with its own menu is the real one, even if it also chains
+            Some(target) if !has_menu(&nodes) => target,
+            _ => return Some(GrubCfgLocation { path: current, via }),
+        };
+
+        let next = resolve_target(paths, &current, &target)?;
+        if via.len() >= MAX_REDIRECTS || via.contains(&next) {
+            return None;
+        }
+        via.push(current);
+        current = next;
+    }
+}
+
+fn has_menu(nodes: &[Node]) -> bool {
+    nodes.iter().any(|node| match node {
+        Node::Block { header, body } => {
+            matches!(header.name(), Some("menuentry") | Some("submenu")) || has_menu(body)
+        }
+        Node::Command(cmd) => cmd.name() == Some("blscfg"),
+    })
+}
+
+// The configfile argument of a stub, with variables set in the stub expanded
+fn stub_target(nodes: &[Node]) -> Option<String> {
+    let mut vars: HashMap<String, String> = HashMap::new();
+    let mut target = None;
+
+    for node in nodes {
+        let Node::Command(cmd) = node else {
+            continue;
+        };
+        match cmd.name() {
+            Some("set") => {
+                for arg in cmd.args() {
+                    if let Some((name, value)) = arg.split_once('=') {
+                        let value = expand(value, &vars);
+                        vars.insert(name.to_string(), value);
+                    }
+                }
+            }
+            Some("configfile") => {
+                if let Some(arg) = cmd.args().first() {
+                    target = Some(expand(arg, &vars));
+                }
+            }
+            _ => {}
+        }
+    }
+    target
+}
+
+fn expand(value: &str, vars: &HashMap<String, String>) -> String {
+    let mut out = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            out.push(c);
+            continue;
+        }
+        let mut name = String::new();
+        if chars.peek() == Some(&'{') {
+            chars.next();
+            for n in chars.by_ref() {
+                if n == '}' {
+                    break;
+                }
+                name.push(n);
+            }
+        } else {
+            while let Some(&n) = chars.peek() {
+                if !(n.is_ascii_alphanumeric() || n == '_') {
+                    break;
+                }
+                name.push(n);
+                chars.next();
+            }
+        }
+        // Unknown variables (e.g. $root) expand to nothing, like in GRUB
+        if let Some(v) = vars.get(&name) {
+            out.push_str(v);
+        }
+    }
+    out
+}
+
+// Map a GRUB path like "($root)/grub2/grub.cfg" to a local file. The device
+// part can't be resolved offline, so try the path relative to "/" and to
+// "/boot" (a separate /boot partition), or relative to the stub itself.
+fn resolve_target(paths: &Paths, stub: &Path, target: &str) -> Option<PathBuf> {
+    let path = match target.strip_prefix('(') {
+        Some(rest) => rest.split_once(')').map(|(_, p)| p).unwrap_or(rest),
+        None => target,
+    };
+
+    let mut options = Vec::new();
+    if path.starts_with('/') {
+        options.push(paths.resolve(path));
+        options.push(paths.resolve(&format!("/boot{}", path)));
+    } else if let Some(dir) = stub.parent() {
+        options.push(dir.join(path));
+    }
+    options.into_iter().find(|p| p.is_file() && p != stub)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn candidate_order() {
+        let root = std::env::temp_dir().join(format!("grublist-test-locate-order-{}", std::process::id()));
+        fs::create_dir_all(root.join("boot/efi/EFI/ubuntu")).unwrap();
+        fs::create_dir_all(root.join("boot/efi/EFI/fedora")).unwrap();
+        fs::create_dir_all(root.join("efi/EFI/debian")).unwrap();
+        let paths = Paths::new(&root);
+
+        let list: Vec<String> = candidates(&paths).iter().map(|p| paths.display(p)).collect();
+        assert_eq!(list, vec![
+            "/boot/grub/grub.cfg",
+            "/boot/grub2/grub.cfg",
+            "/boot/efi/EFI/fedora/grub.cfg",
+            "/boot/efi/EFI/ubuntu/grub.cfg",
+            "/efi/EFI/debian/grub.cfg",
+        ]);
+
+        // The first existing file wins, /boot/grub before /boot/grub2
+        write(&root.join("boot/efi/EFI/fedora/grub.cfg"), "menuentry 'ESP' { true; }\n");
+        assert_eq!(locate_grub_cfg(&paths).unwrap().describe(&paths), "/boot/efi/EFI/fedora/grub.cfg");
+        write(&root.join("boot/grub2/grub.cfg"), "menuentry 'Fedora' { true; }\n");
+        write(&root.join("boot/grub/grub.cfg"), "menuentry 'Debian' { true; }\n");
+        assert_eq!(locate_grub_cfg(&paths).unwrap().describe(&paths), "/boot/grub/grub.cfg");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn stub_resolved_under_root() {
+        let root = std::env::temp_dir().join(format!("grublist-test-locate-stub-{}", std::process::id()));
+        let paths = Paths::new(&root);
+        let stub = root.join("boot/efi/EFI/fedora/grub.cfg");
+        write(
+            &stub,
+            "search --no-floppy --fs-uuid --set=dev 1234\n\
+             set prefix=($dev)/grub2\n\
+             configfile $prefix/grub.cfg\n",
+        );
+
+        // No real grub.cfg anywhere: the stub leads nowhere
+        assert!(locate_grub_cfg(&paths).is_none());
+
+        // A separate /boot partition: "/grub2/grub.cfg" is /boot/grub2 inside the root
+        write(&root.join("boot/grub2/grub.cfg"), "blscfg\n");
+        let location = follow(&paths, stub.clone()).unwrap();
+        assert_eq!(location.path, root.join("boot/grub2/grub.cfg"));
+        assert_eq!(location.via, vec![stub]);
+        assert_eq!(location.describe(&paths), "/boot/grub2/grub.cfg (via /boot/efi/EFI/fedora/grub.cfg)");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use crate::grub;
+use crate::grub_script::{self, Node};
+use crate::paths::Paths;
+use crate::safe_write;
+
+// Directories searched for the generator, inside the managed root
+const BIN_DIRS: [&str; 5] = ["/usr/sbin", "/usr/bin", "/sbin", "/bin", "/usr/local/sbin"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Generator {
+    // grub-mkconfig (Debian, Arch) or grub2-mkconfig (Fedora, RHEL, openSUSE)
+    Mkconfig(String),
+    // Debian/Ubuntu wrapper, always writes /boot/grub/grub.cfg in place
+    UpdateGrub,
+}
+
+impl Generator {
+    /// The command as the user would type it, for messages
+    pub fn command_line(&self, paths: &Paths) -> String {
+        match self {
+            Generator::Mkconfig(program) => {
+                format!("{} -o {}", program, paths.display(&paths.grub_cfg()))
+            }
+            Generator::UpdateGrub => "update-grub".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ApplyResult {
+    pub command: String,
+    pub grub_cfg: PathBuf,
+    pub entries: usize,
+    pub output: String,
+}
+
+/// Find the grub.cfg generator installed on the managed system. grub-mkconfig
+/// is preferred since it can write to a temp file; update-grub is the fallback.
+pub fn find_generator(paths: &Paths) -> Option<Generator> {
+    let installed = |name: &str| {
+        BIN_DIRS.iter().any(|dir| paths.resolve(dir).join(name).is_file())
+    };
+    ["grub2-mkconfig", "grub-mkconfig"]
+        .into_iter()
+        .find(|name| installed(name))
+        .map(|name| Generator::Mkconfig(name.to_string()))
+        .or_else(|| installed("update-grub").then_some(Generator::UpdateGrub))
+}
+
+/// Regenerate grub.cfg. With grub-mkconfig the new file is generated next to
+/// the current one, checked with the script reader and renamed over it, so
+/// a failure leaves grub.cfg untouched. update-grub writes in place, so the
+/// previous contents are written back if it fails.
+pub fn apply(paths: &Paths) -> Result<ApplyResult, String> {
+    let generator = find_generator(paths)
+        .ok_or_else(|| "Neither grub-mkconfig, grub2-mkconfig nor update-grub is installed".to_string())?;
+    // Two runs would write the same temp file
+    let _lock = safe_write::lock(paths)
+        .map_err(|e| format!("Failed to lock: {}", e))?;
+    let grub_cfg = paths.grub_cfg();
+
+    match &generator {
+        Generator::Mkconfig(program) => {
+            let temp = grub_cfg.with_file_name("grub.cfg.grublist-new");
+            let output = run(paths, program, &["-o", &paths.display(&temp)]);
+            let result = output.and_then(|output| {
+                let content = fs::read_to_string(&temp)
+                    .map_err(|e| format!("Failed to read generated {}: {}", temp.display(), e))?;
+                let entries = check_generated(paths, &content)?;
+                Ok((output, entries))
+            });
+            let (output, entries) = match result {
+                Ok(r) => r,
+                Err(e) => {
+                    let _ = fs::remove_file(&temp);
+                    return Err(e);
+                }
+            };
+
+            // Keeps the mode (grub.cfg is often 0600), owner and SELinux label
+            safe_write::replace(&temp, &grub_cfg)
+                .map_err(|e| format!("Failed to replace {}: {}", grub_cfg.display(), e))?;
+
+            Ok(ApplyResult {
+                command: generator.command_line(paths),
+                grub_cfg,
+                entries,
+                output,
+            })
+        }
+        Generator::UpdateGrub => {
+            let previous = fs::read(&grub_cfg).ok();
+            let result = run(paths, "update-grub", &[]).and_then(|output| {
+                let content = fs::read_to_string(&grub_cfg)
+                    .map_err(|e| format!("Failed to read {}: {}", grub_cfg.display(), e))?;
+                let entries = check_generated(paths, &content)?;
+                Ok((output, entries))
+            });
+            match result {
+                Ok((output, entries)) => Ok(ApplyResult {
+                    command: generator.command_line(paths),
+                    grub_cfg,
+                    entries,
+                    output,
+                }),
+                Err(e) => {
+                    if let Some(previous) = previous {
+                        safe_write::write(&grub_cfg, previous).map_err(|re| {
+                            format!("{}\nFailed to restore previous {}: {}", e, grub_cfg.display(), re)
+                        })?;
+                    }
+                    Err(format!("{}\nPrevious grub.cfg restored.", e))
+                }
+            }
+        }
+    }
+}
+
+// Run the generator, inside the managed root if it isn't the host.
+// Returns stdout and stderr together, grub-mkconfig reports progress on stderr.
+fn run(paths: &Paths, program: &str, args: &[&str]) -> Result<String, String> {
+    let mut command = if paths.is_host() {
+        Command::new(program)
+    } else {
+        let mut chroot = Command::new("chroot");
+        chroot.arg(&paths.root).arg(program);
+        chroot
+    };
+    let output = command
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+
+    let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    if !output.status.success() {
+        return Err(format!("{} failed ({}):\n{}", program, output.status, text.trim_end()));
+    }
+    Ok(text)
+}
+
+/// Check a generated grub.cfg before it is installed. Returns the number of
+/// top level menu entries.
+pub fn check_generated(paths: &Paths, content: &str) -> Result<usize, String> {
+    grub_script::check(content).map_err(|e| format!("Generated grub.cfg: {}", e))?;
+    check_titles(&grub_script::parse(content))
+        .map_err(|e| format!("Generated grub.cfg: {}", e))?;
+
+    let menu = grub::parse_grub(content, &grub::load_bls_entries(paths));
+    if menu.children.is_empty() {
+        return Err("Generated grub.cfg has no boot entries".to_string());
+    }
+    Ok(menu.children.len())
+}
+
+// GRUB refuses a menuentry or submenu without a title
+fn check_titles(nodes: &[Node]) -> Result<(), String> {
+    for node in nodes {
+        if let Node::Block { header, body } = node {
+            if matches!(header.name(), Some("menuentry") | Some("submenu"))
+                && grub::Entry::from_header(header.args(), grub::EntryType::MenuEntry).name.is_empty()
+            {
+                return Err(format!("line {}: {} without a title", header.line, header.name().unwrap_or_default()));
+            }
+            check_titles(body)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,361 @@
+// Minimal GRUB script reader.
+//
+// This understands enough of the grub.cfg syntax to rebuild the menu the
+// same way GRUB does: single and double quoted words, backslash escapes,
+// line continuations, comments, `$var`/`${var}` references (kept verbatim)
+// and `{ ... }` blocks. Commands are not evaluated.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Word(String),
+    Separator,
+    OpenBrace,
+    CloseBrace,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub words: Vec<String>,
+    pub line: usize,
+}
+
+impl Command {
+    pub fn name(&self) -> Option<&str> {
+        self.words.first().map(|s| s.as_str())
+    }
+
+    pub fn args(&self) -> &[String] {
+        if self.words.is_empty() {
+            &[]
+        } else {
+            &self.words[1..]
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Node {
+    Command(Command),
+    // `header { body }`, e.g. menuentry, submenu or function definitions
+    Block { header: Command, body: Vec<Node> },
+}
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    tokens: Vec<Token>,
+    word: Option<String>,
+    word_line: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(input: &'a str) -> Self {
+        Lexer {
+            chars: input.chars().peekable(),
+            line: 1,
+            tokens: Vec::new(),
+            word: None,
+            word_line: 1,
+        }
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    fn word_mut(&mut self) -> &mut String {
+        if self.word.is_none() {
+            self.word_line = self.line;
+        }
+        self.word.get_or_insert_with(String::new)
+    }
+
+    fn finish_word(&mut self) {
+        if let Some(word) = self.word.take() {
+            self.tokens.push(Token {
+                kind: TokenKind::Word(word),
+                line: self.word_line,
+            });
+        }
+    }
+
+    fn push(&mut self, kind: TokenKind) {
+        self.finish_word();
+        self.tokens.push(Token { kind, line: self.line });
+    }
+
+    fn run(mut self) -> Vec<Token> {
+        while let Some(c) = self.next_char() {
+            match c {
+                ' ' | '\t' | '\r' => self.finish_word(),
+                '\n' => {
+                    // The separator belongs to the line it terminates
+                    self.finish_word();
+                    self.tokens.push(Token { kind: TokenKind::Separator, line: self.line - 1 });
+                }
+                ';' => self.push(TokenKind::Separator),
+                '{' => self.push(TokenKind::OpenBrace),
+                '}' => self.push(TokenKind::CloseBrace),
+                '#' if self.word.is_none() => {
+                    // Comment runs to the end of the line; keep the newline
+                    while let Some(&next) = self.chars.peek() {
+                        if next == '\n' {
+                            break;
+                        }
+                        self.next_char();
+                    }
+                }
+                '\'' => {
+                    self.word_mut();
+                    while let Some(next) = self.next_char() {
+                        if next == '\'' {
+                            break;
+                        }
+                        self.word_mut().push(next);
+                    }
+                }
+                '"' => {
+                    self.word_mut();
+                    while let Some(next) = self.next_char() {
+                        match next {
+                            '"' => break,
+                            '\\' => match self.chars.peek().copied() {
+                                Some('\n') => {
+                                    self.next_char();
+                                }
+                                Some(esc @ ('$' | '"' | '\\')) => {
+                                    self.next_char();
+                                    self.word_mut().push(esc);
+                                }
+                                _ => self.word_mut().push('\\'),
+                            },
+                            _ => self.word_mut().push(next),
+                        }
+                    }
+                }
+                '\\' => match self.next_char() {
+                    // Line continuation
+                    Some('\n') => {}
+                    Some(esc) => self.word_mut().push(esc),
+                    None => self.word_mut().push('\\'),
+                },
+                '$' if self.chars.peek() == Some(&'{') => {
+                    // ${var} is a single word part, its braces are not blocks
+                    self.word_mut().push('$');
+                    while let Some(next) = self.next_char() {
+                        self.word_mut().push(next);
+                        if next == '}' {
+                            break;
+                        }
+                    }
+                }
+                _ => self.word_mut().push(c),
+            }
+        }
+        self.finish_word();
+        self.tokens
+    }
+}
+
+pub fn tokenize(input: &str) -> Vec<Token> {
+    Lexer::new(input).run()
+}
+
+pub fn parse(input: &str) -> Vec<Node> {
+    let tokens = tokenize(input);
+    let mut pos = 0;
+    let mut nodes = Vec::new();
+
+    // Stray closing braces at the top level are ignored, like an unbalanced
+    // file would be by the reader before GRUB reports a syntax error
+    while pos < tokens.len() {
+        nodes.extend(parse_block(&tokens, &mut pos));
+        pos += 1;
+    }
+    nodes
+}
+
+// Parse commands until the matching close brace (consumed by the caller) or EOF
+fn parse_block(tokens: &[Token], pos: &mut usize) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut line = 0;
+
+    while *pos < tokens.len() {
+        let token = &tokens[*pos];
+        match &token.kind {
+            TokenKind::Word(w) => {
+                if words.is_empty() {
+                    line = token.line;
+                }
+                words.push(w.clone());
+            }
+            TokenKind::Separator => {
+                if !words.is_empty() {
+                    nodes.push(Node::Command(Command { words: std::mem::take(&mut words), line }));
+                }
+            }
+            TokenKind::OpenBrace => {
+                let header = Command { words: std::mem::take(&mut words), line: if line == 0 { token.line } else { line } };
+                *pos += 1;
+                let body = parse_block(tokens, pos);
+                nodes.push(Node::Block { header, body });
+                line = 0;
+            }
+            TokenKind::CloseBrace => {
+                break;
+            }
+        }
+        *pos += 1;
+    }
+
+    if !words.is_empty() {
+        nodes.push(Node::Command(Command { words, line }));
+    }
+    nodes
+}
+
+/// Check that every `{` has a matching `}`, the way grub-script-check would
+/// reject an unbalanced file
+pub fn check(input: &str) -> Result<(), String> {
+    let mut open: Vec<usize> = Vec::new();
+    for token in tokenize(input) {
+        match token.kind {
+            TokenKind::OpenBrace => open.push(token.line),
+            TokenKind::CloseBrace => {
+                open.pop().ok_or_else(|| format!("line {}: unexpected '}}'", token.line))?;
+            }
+            _ => {}
+        }
+    }
+    match open.pop() {
+        Some(line) => Err(format!("line {}: '{{' is never closed", line)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(input: &str) -> Vec<Vec<String>> {
+        parse(input).into_iter()
+            .map(|node| match node {
+                Node::Command(command) => command.words,
+                Node::Block { header, .. } => header.words,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn quoting_and_escapes() {
+        assert_eq!(words(r#"menuentry 'It'\''s "quoted"' "a \"b\" \$c \\d \e" x\ y"#), vec![vec![
+            "menuentry".to_string(),
+            r#"It's "quoted""#.to_string(),
+            r#"a "b" $c \d \e"#.to_string(),
+            "x y".to_string(),
+        ]]);
+    }
+
+    #[test]
+    fn empty_quotes_are_a_word() {
+        assert_eq!(words("set x=''"), vec![vec!["set", "x="]]);
+        assert_eq!(words("echo '' \"\""), vec![vec!["echo", "", ""]]);
+    }
+
+    #[test]
+    fn comments_and_separators() {
+        let input = "# comment { not a block\nset a=1; set b=2 # trailing }\necho a#b\n";
+        assert_eq!(words(input), vec![
+            vec!["set", "a=1"],
+            vec!["set", "b=2"],
+            vec!["echo", "a#b"],
+        ]);
+    }
+
+    #[test]
+    fn line_continuation() {
+        assert_eq!(words("linux /vmlinuz \\\n  root=/dev/sda1 \\\n  ro\ninitrd /initrd"), vec![
+            vec!["linux", "/vmlinuz", "root=/dev/sda1", "ro"],
+            vec!["initrd", "/initrd"],
+        ]);
+        // Inside double quotes the newline is dropped too
+        assert_eq!(words("echo \"a\\\nb\""), vec![vec!["echo", "ab"]]);
+    }
+
+    #[test]
+    fn variable_braces_are_not_blocks() {
+        let input = "if [ \"${timeout}\" = 0 ]; then set x${a}y=${b}; fi";
+        assert_eq!(words(input), vec![
+            vec!["if", "[", "${timeout}", "=", "0", "]"],
+            vec!["then", "set", "x${a}y=${b}"],
+            vec!["fi"],
+        ]);
+        assert!(check(input).is_ok());
+    }
+
+    #[test]
+    fn nested_blocks_and_lines() {
+        let input = "\
+submenu 'Advanced' $menuentry_id_option 'adv' {
+\tmenuentry 'Kernel' {
+\t\tlinux /vmlinuz
+\t}
+}
+menuentry Unquoted\\ Title{
+}
+";
+        let nodes = parse(input);
+        assert_eq!(nodes.len(), 2);
+        let Node::Block { header, body } = &nodes[0] else { panic!("submenu is not a block") };
+        assert_eq!(header.words, vec!["submenu", "Advanced", "$menuentry_id_option", "adv"]);
+        assert_eq!(header.line, 1);
+        let Node::Block { header, body } = &body[0] else { panic!("menuentry is not a block") };
+        assert_eq!(header.name(), Some("menuentry"));
+        assert_eq!(header.args(), ["Kernel"]);
+        let Node::Command(linux) = &body[0] else { panic!("linux is not a command") };
+        assert_eq!(linux.words, vec!["linux", "/vmlinuz"]);
+        assert_eq!(linux.line, 3);
+        let Node::Block { header, body } = &nodes[1] else { panic!("second menuentry is not a block") };
+        assert_eq!(header.words, vec!["menuentry", "Unquoted Title"]);
+        assert_eq!(header.line, 6);
+        assert!(body.is_empty());
+    }
+
+    #[test]
+    fn generated_custom_script() {
+        // What grub-mkconfig copies into grub.cfg from an `exec tail` script
+        let input = "\
+### BEGIN /etc/grub.d/40_custom ###
+# This file provides an easy way to add custom menu entries.
+menuentry \"Windows\" --class windows {
+\tinsmod chain
+\tchainloader /EFI/Microsoft/Boot/bootmgfw.efi
+}
+### END /etc/grub.d/40_custom ###
+";
+        assert!(check(input).is_ok());
+        let nodes = parse(input);
+        assert_eq!(nodes.len(), 1);
+        let Node::Block { header, body } = &nodes[0] else { panic!("menuentry is not a block") };
+        assert_eq!(header.words, vec!["menuentry", "Windows", "--class", "windows"]);
+        assert_eq!(body.len(), 2);
+    }
+
+    #[test]
+    fn unbalanced_braces() {
+        assert_eq!(check("menuentry a {\n linux /x\n"), Err("line 1: '{' is never closed".to_string()));
+        assert_eq!(check("linux /x\n}\n"), Err("line 2: unexpected '}'".to_string()));
+        assert!(check("echo '{' \"}\" \\{").is_ok());
+    }
+}
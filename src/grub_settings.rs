@@ -0,0 +1,354 @@
+// Catalog of the /etc/default/grub settings read by grub-mkconfig.
+//
+// Each known key has a value type used to pick an editor and to check values
+// before they are saved, and a short description shown as help. Keys not in
+// the catalog are accepted as free-form text.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueType {
+    Integer { min: i64 },
+    // `true` or `false`
+    Bool,
+    // Exactly one of the listed values
+    Choice(&'static [&'static str]),
+    // Space separated list of the listed values
+    Words(&'static [&'static str]),
+    // Comma or semicolon separated list of WIDTHxHEIGHT[xDEPTH] and the
+    // listed keywords (e.g. `auto`)
+    Resolutions(&'static [&'static str]),
+    Text,
+}
+
+impl ValueType {
+    /// Short description of the accepted values, for editors and errors
+    pub fn describe(&self) -> String {
+        match self {
+            ValueType::Integer { min } => format!("an integer >= {}", min),
+            ValueType::Bool => "true or false".to_string(),
+            ValueType::Choice(values) => format!("one of {}", values.join(", ")),
+            ValueType::Words(values) => format!("a space separated list of {}", values.join(", ")),
+            ValueType::Resolutions(keywords) => {
+                let mut forms = vec!["WIDTHxHEIGHT", "WIDTHxHEIGHTxDEPTH"];
+                forms.extend_from_slice(keywords);
+                format!("a comma separated list of {}", forms.join(", "))
+            }
+            ValueType::Text => "text".to_string(),
+        }
+    }
+
+    /// Values an option editor cycles through, None for typed input
+    pub fn options(&self) -> Option<&'static [&'static str]> {
+        match self {
+            ValueType::Bool => Some(&["true", "false"]),
+            ValueType::Choice(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Setting {
+    pub key: &'static str,
+    pub value_type: ValueType,
+    pub help: &'static str,
+}
+
+const TERMINAL_INPUTS: &[&str] = &["console", "serial", "ofconsole", "at_keyboard", "usb_keyboard"];
+const TERMINAL_OUTPUTS: &[&str] = &["console", "serial", "ofconsole", "gfxterm", "vga_text", "mda_text", "morse", "spkmodem"];
+const TERMINALS: &[&str] = &["console", "serial", "ofconsole", "at_keyboard", "usb_keyboard", "gfxterm", "vga_text", "mda_text", "morse", "spkmodem"];
+
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        key: "GRUB_DEFAULT",
+        value_type: ValueType::Text,
+        help: "Default menu entry: a number, a '>' separated path of numbers, IDs or titles, or 'saved' to use saved_entry from grubenv.",
+    },
+    Setting {
+        key: "GRUB_SAVEDEFAULT",
+        value_type: ValueType::Bool,
+        help: "Remember the entry chosen at boot as the next default. Only works with GRUB_DEFAULT=saved.",
+    },
+    Setting {
+        key: "GRUB_TIMEOUT",
+        value_type: ValueType::Integer { min: -1 },
+        help: "Seconds to wait before booting the default entry. 0 boots immediately, -1 waits until a key is pressed.",
+    },
+    Setting {
+        key: "GRUB_TIMEOUT_STYLE",
+        value_type: ValueType::Choice(&["menu", "countdown", "hidden"]),
+        help: "menu shows the menu during the timeout; countdown and hidden only show it if Esc is pressed, with or without a countdown.",
+    },
+    Setting {
+        key: "GRUB_HIDDEN_TIMEOUT",
+        value_type: ValueType::Integer { min: 0 },
+        help: "Deprecated, use GRUB_TIMEOUT_STYLE=countdown or hidden instead.",
+    },
+    Setting {
+        key: "GRUB_HIDDEN_TIMEOUT_QUIET",
+        value_type: ValueType::Bool,
+        help: "Deprecated, with GRUB_HIDDEN_TIMEOUT hides the countdown.",
+    },
+    Setting {
+        key: "GRUB_RECORDFAIL_TIMEOUT",
+        value_type: ValueType::Integer { min: -1 },
+        help: "Ubuntu: timeout used after a failed or interrupted boot, -1 waits until a key is pressed.",
+    },
+    Setting {
+        key: "GRUB_DEFAULT_BUTTON",
+        value_type: ValueType::Text,
+        help: "Like GRUB_DEFAULT, used when the machine was started with the vendor's alternate power button.",
+    },
+    Setting {
+        key: "GRUB_TIMEOUT_BUTTON",
+        value_type: ValueType::Integer { min: -1 },
+        help: "Like GRUB_TIMEOUT, used when the machine was started with the alternate power button.",
+    },
+    Setting {
+        key: "GRUB_TIMEOUT_STYLE_BUTTON",
+        value_type: ValueType::Choice(&["menu", "countdown", "hidden"]),
+        help: "Like GRUB_TIMEOUT_STYLE, used when the machine was started with the alternate power button.",
+    },
+    Setting {
+        key: "GRUB_DISTRIBUTOR",
+        value_type: ValueType::Text,
+        help: "Name used in menu entry titles, often `lsb_release -i -s`.",
+    },
+    Setting {
+        key: "GRUB_CMDLINE_LINUX",
+        value_type: ValueType::Text,
+        help: "Kernel parameters added to every Linux entry, including recovery mode.",
+    },
+    Setting {
+        key: "GRUB_CMDLINE_LINUX_DEFAULT",
+        value_type: ValueType::Text,
+        help: "Kernel parameters added to normal Linux entries only, after GRUB_CMDLINE_LINUX.",
+    },
+    Setting {
+        key: "GRUB_CMDLINE_XEN",
+        value_type: ValueType::Text,
+        help: "Xen hypervisor parameters added to every Xen entry.",
+    },
+    Setting {
+        key: "GRUB_CMDLINE_XEN_DEFAULT",
+        value_type: ValueType::Text,
+        help: "Xen hypervisor parameters added to normal Xen entries only.",
+    },
+    Setting {
+        key: "GRUB_CMDLINE_NETBSD",
+        value_type: ValueType::Text,
+        help: "Kernel parameters for NetBSD entries.",
+    },
+    Setting {
+        key: "GRUB_EARLY_INITRD_LINUX_CUSTOM",
+        value_type: ValueType::Text,
+        help: "Space separated initrd images in /boot loaded before the kernel's own initrd (e.g. CPU microcode).",
+    },
+    Setting {
+        key: "GRUB_EARLY_INITRD_LINUX_STOCK",
+        value_type: ValueType::Text,
+        help: "Distribution provided early initrd images, normally left unset. 'no' disables the search.",
+    },
+    Setting {
+        key: "GRUB_DISABLE_LINUX_UUID",
+        value_type: ValueType::Bool,
+        help: "Pass root=/dev/... instead of root=UUID=... to the kernel.",
+    },
+    Setting {
+        key: "GRUB_DISABLE_LINUX_PARTUUID",
+        value_type: ValueType::Bool,
+        help: "Don't pass root=PARTUUID=... to the kernel when there is no initrd. Defaults to true.",
+    },
+    Setting {
+        key: "GRUB_DISABLE_UUID",
+        value_type: ValueType::Bool,
+        help: "Find filesystems by device name instead of UUID.",
+    },
+    Setting {
+        key: "GRUB_DISABLE_RECOVERY",
+        value_type: ValueType::Bool,
+        help: "Don't generate recovery mode entries.",
+    },
+    Setting {
+        key: "GRUB_DISABLE_SUBMENU",
+        value_type: ValueType::Bool,
+        help: "List all kernels at the top level instead of in an 'Advanced options' submenu.",
+    },
+    Setting {
+        key: "GRUB_DISABLE_OS_PROBER",
+        value_type: ValueType::Bool,
+        help: "Don't search other disks for operating systems. Set to false to add Windows and other systems to the menu.",
+    },
+    Setting {
+        key: "GRUB_OS_PROBER_SKIP_LIST",
+        value_type: ValueType::Text,
+        help: "Space separated filesystem UUIDs (UUID@/dev/...) that os-prober should skip.",
+    },
+    Setting {
+        key: "GRUB_ENABLE_CRYPTODISK",
+        value_type: ValueType::Choice(&["y", "n"]),
+        help: "Set to y to unlock encrypted disks from GRUB when /boot is encrypted.",
+    },
+    Setting {
+        key: "GRUB_ENABLE_BLSCFG",
+        value_type: ValueType::Bool,
+        help: "Fedora/RHEL: read kernels from /boot/loader/entries at boot instead of listing them in grub.cfg.",
+    },
+    Setting {
+        key: "GRUB_TERMINAL_INPUT",
+        value_type: ValueType::Words(TERMINAL_INPUTS),
+        help: "Input terminals, e.g. 'console serial'.",
+    },
+    Setting {
+        key: "GRUB_TERMINAL_OUTPUT",
+        value_type: ValueType::Words(TERMINAL_OUTPUTS),
+        help: "Output terminals. gfxterm is needed for themes and background images.",
+    },
+    Setting {
+        key: "GRUB_TERMINAL",
+        value_type: ValueType::Words(TERMINALS),
+        help: "Sets both GRUB_TERMINAL_INPUT and GRUB_TERMINAL_OUTPUT.",
+    },
+    Setting {
+        key: "GRUB_SERIAL_COMMAND",
+        value_type: ValueType::Text,
+        help: "serial command used with the serial terminal, e.g. 'serial --speed=115200 --unit=0'.",
+    },
+    Setting {
+        key: "GRUB_GFXMODE",
+        value_type: ValueType::Resolutions(&["auto"]),
+        help: "Resolutions tried in order for the graphical terminal, e.g. '1024x768,auto'.",
+    },
+    Setting {
+        key: "GRUB_GFXPAYLOAD_LINUX",
+        value_type: ValueType::Resolutions(&["text", "keep", "auto"]),
+        help: "Video mode handed to Linux: text, keep (the GRUB mode) or resolutions.",
+    },
+    Setting {
+        key: "GRUB_VIDEO_BACKEND",
+        value_type: ValueType::Text,
+        help: "Video driver to use, normally detected automatically.",
+    },
+    Setting {
+        key: "GRUB_BACKGROUND",
+        value_type: ValueType::Text,
+        help: "Background image (.png, .jpg or .tga) for the graphical terminal.",
+    },
+    Setting {
+        key: "GRUB_THEME",
+        value_type: ValueType::Text,
+        help: "theme.txt of the theme used by the graphical terminal.",
+    },
+    Setting {
+        key: "GRUB_INIT_TUNE",
+        value_type: ValueType::Text,
+        help: "Tune played at start, 'TEMPO [PITCH DURATION]...' as for the play command.",
+    },
+    Setting {
+        key: "GRUB_BADRAM",
+        value_type: ValueType::Text,
+        help: "Memory ranges to exclude, as ADDR,MASK[,ADDR,MASK...].",
+    },
+    Setting {
+        key: "GRUB_PRELOAD_MODULES",
+        value_type: ValueType::Text,
+        help: "Space separated GRUB modules to load early.",
+    },
+    Setting {
+        key: "GRUB_TOP_LEVEL",
+        value_type: ValueType::Text,
+        help: "Kernel image to put first in the menu.",
+    },
+];
+
+pub fn lookup(key: &str) -> Option<&'static Setting> {
+    SETTINGS.iter().find(|s| s.key == key)
+}
+
+/// Check a value before it is saved. Unknown keys, and values the shell
+/// would expand ($VAR, $(...), backticks), are not checked.
+pub fn validate(key: &str, value: &str) -> Result<(), String> {
+    let Some(setting) = lookup(key) else {
+        return Ok(());
+    };
+    if value.contains('$') || value.contains('`') {
+        return Ok(());
+    }
+
+    let valid = match setting.value_type {
+        ValueType::Integer { min } => value.parse::<i64>().is_ok_and(|n| n >= min),
+        ValueType::Bool => value == "true" || value == "false",
+        ValueType::Choice(values) => values.contains(&value),
+        ValueType::Words(values) => value.split_whitespace().all(|w| values.contains(&w)),
+        ValueType::Resolutions(keywords) => {
+            value.is_empty() || value.split([',', ';']).all(|mode| {
+                let mode = mode.trim();
+                keywords.contains(&mode) || is_resolution(mode)
+            })
+        }
+        ValueType::Text => true,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("{} must be {}, not '{}'", key, setting.value_type.describe(), value))
+    }
+}
+
+// WIDTHxHEIGHT or WIDTHxHEIGHTxDEPTH
+fn is_resolution(mode: &str) -> bool {
+    let parts: Vec<&str> = mode.split('x').collect();
+    (2..=3).contains(&parts.len())
+        && parts.iter().all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integers() {
+        assert!(validate("GRUB_TIMEOUT", "5").is_ok());
+        // -1 waits forever
+        assert!(validate("GRUB_TIMEOUT", "-1").is_ok());
+        assert_eq!(
+            validate("GRUB_TIMEOUT", "-2"),
+            Err("GRUB_TIMEOUT must be an integer >= -1, not '-2'".to_string())
+        );
+        assert!(validate("GRUB_TIMEOUT", "5s").is_err());
+        assert!(validate("GRUB_HIDDEN_TIMEOUT", "-1").is_err());
+    }
+
+    #[test]
+    fn choices_and_bools() {
+        assert!(validate("GRUB_TIMEOUT_STYLE", "hidden").is_ok());
+        assert_eq!(
+            validate("GRUB_TIMEOUT_STYLE", "Hidden"),
+            Err("GRUB_TIMEOUT_STYLE must be one of menu, countdown, hidden, not 'Hidden'".to_string())
+        );
+        assert!(validate("GRUB_DISABLE_OS_PROBER", "false").is_ok());
+        assert!(validate("GRUB_DISABLE_OS_PROBER", "yes").is_err());
+        assert!(validate("GRUB_ENABLE_CRYPTODISK", "y").is_ok());
+        assert!(validate("GRUB_ENABLE_CRYPTODISK", "true").is_err());
+    }
+
+    #[test]
+    fn lists() {
+        assert!(validate("GRUB_TERMINAL_OUTPUT", "console serial").is_ok());
+        assert!(validate("GRUB_TERMINAL_OUTPUT", "console at_keyboard").is_err());
+        assert!(validate("GRUB_GFXMODE", "1024x768x32,auto").is_ok());
+        assert!(validate("GRUB_GFXMODE", "").is_ok());
+        assert!(validate("GRUB_GFXMODE", "1024x").is_err());
+    }
+
+    #[test]
+    fn unchecked_values() {
+        // Shell expansions are only known when grub-mkconfig runs
+        assert!(validate("GRUB_TIMEOUT", "$TIMEOUT").is_ok());
+        assert!(validate("GRUB_DISABLE_OS_PROBER", "`cat /etc/probe`").is_ok());
+        assert!(validate("GRUB_TIMEOUT_STYLE", "${STYLE:-menu}").is_ok());
+        // Unknown keys and free text
+        assert!(validate("GRUB_SOMETHING_NEW", "anything").is_ok());
+        assert!(validate("GRUB_DISTRIBUTOR", "My Linux").is_ok());
+    }
+}
@@ -1,10 +1,18 @@
 use std::process::Command;
-use std::io;
 use regex::Regex;
+use serde::Serialize;
+use crate::paths::Paths;
 
-pub fn validate_grub_config() -> Result<ValidationResult, String> {
-    // Try to run grub-mkconfig --dry-run
-    let output = Command::new("grub-mkconfig")
+pub fn validate_grub_config(paths: &Paths) -> Result<ValidationResult, String> {
+    // Try to run grub-mkconfig --dry-run, inside the managed root if it isn't the host
+    let mut command = if paths.is_host() {
+        Command::new("grub-mkconfig")
+    } else {
+        let mut chroot = Command::new("chroot");
+        chroot.arg(&paths.root).arg("grub-mkconfig");
+        chroot
+    };
+    let output = command
         .arg("--dry-run")
         .output()
         .map_err(|e| format!("Failed to run grub-mkconfig: {}", e))?;
@@ -33,7 +41,7 @@ pub fn validate_grub_config() -> Result<ValidationResult, String> {
     })
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ValidationResult {
     pub valid: bool,
     pub errors: Vec<String>,
@@ -41,37 +49,16 @@ pub struct ValidationResult {
     pub output: String,
 }
 
-/// Get GRUB version (major.minor format, e.g., "2.06" or "2.00")
-pub fn get_grub_version() -> Option<(u32, u32)> {
-    // Try grub-mkconfig --version first
-    if let Ok(output) = Command::new("grub-mkconfig").arg("--version").output() {
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let version_re = Regex::new(r"grub-mkconfig\s+\(GRUB\)\s+(\d+)\.(\d+)").ok()?;
-        if let Some(caps) = version_re.captures(&stdout) {
-            if let (Ok(major), Ok(minor)) = (
-                caps.get(1)?.as_str().parse::<u32>(),
-                caps.get(2)?.as_str().parse::<u32>(),
-            ) {
-                return Some((major, minor));
-            }
-        }
-    }
-    
-    // Try grub-install --version as fallback
-    if let Ok(output) = Command::new("grub-install").arg("--version").output() {
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let version_re = Regex::new(r"grub-install\s+\(GRUB\)\s+(\d+)\.(\d+)").ok()?;
-        if let Some(caps) = version_re.captures(&stdout) {
-            if let (Ok(major), Ok(minor)) = (
-                caps.get(1)?.as_str().parse::<u32>(),
-                caps.get(2)?.as_str().parse::<u32>(),
-            ) {
-                return Some((major, minor));
-            }
+/// Resolve a GRUB_DEFAULT value to a numeric path the way the generated
+/// grub.cfg does: an old-style bare kernel title is mapped by grub-mkconfig
+/// to the entry in the submenu (with a warning)
+pub fn resolve_default(menu: &crate::grub::Entry, spec: &str) -> Option<Vec<usize>> {
+    crate::grub::resolve_entry_path(menu, spec).or_else(|| {
+        if !is_old_grub_default_format(spec) {
+            return None;
         }
-    }
-    
-    None
+        fix_old_grub_default_format(spec, menu).map(|path| crate::custom_names::string_to_path(&path))
+    })
 }
 
 /// Check if GRUB_DEFAULT uses old title format
@@ -0,0 +1,288 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use serde::Serialize;
+use crate::paths::Paths;
+use crate::safe_write;
+
+// GRUB environment block (what grub-editenv, grub-reboot and grub-set-default
+// write). It is a fixed size file GRUB itself rewrites in place with save_env,
+// so it is never truncated or resized here, only overwritten byte for byte.
+const HEADER: &str = "# GRUB Environment Block\n";
+const DEFAULT_SIZE: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub struct GrubEnv {
+    pub path: PathBuf,
+    // Variables in file order
+    pub vars: Vec<(String, String)>,
+    size: usize,
+}
+
+impl GrubEnv {
+    /// Load the grubenv next to grub.cfg. A missing file gives an empty block
+    /// that will be created with the standard 1024 byte size on save.
+    pub fn load(paths: &Paths) -> Result<Self, String> {
+        let path = paths.grubenv();
+        if !path.exists() {
+            return Ok(Self::empty(path));
+        }
+        let content = fs::read(&path)
+            .map_err(|e| format!("Failed to read {}: {}", paths.display(&path), e))?;
+        let mut env = Self::parse(&content)
+            .map_err(|e| format!("{}: {}", paths.display(&path), e))?;
+        env.path = path;
+        Ok(env)
+    }
+
+    pub fn empty(path: PathBuf) -> Self {
+        GrubEnv { path, vars: Vec::new(), size: DEFAULT_SIZE }
+    }
+
+    pub fn parse(content: &[u8]) -> Result<Self, String> {
+        let text = std::str::from_utf8(content)
+            .map_err(|_| "environment block is not valid UTF-8".to_string())?;
+        let body = text.strip_prefix(HEADER)
+            .ok_or_else(|| "invalid environment block header".to_string())?;
+
+        let mut vars = Vec::new();
+        for line in split_lines(body) {
+            // Padding and comments
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                vars.push((key.to_string(), unescape(value)));
+            }
+        }
+
+        Ok(GrubEnv {
+            path: PathBuf::new(),
+            vars,
+            size: content.len(),
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.vars.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.vars.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.vars.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn unset(&mut self, key: &str) {
+        self.vars.retain(|(k, _)| k != key);
+    }
+
+    /// Serialize to a block of the original size, padded with '#'
+    pub fn to_block(&self) -> Result<Vec<u8>, String> {
+        let mut block = String::from(HEADER);
+        for (key, value) in &self.vars {
+            block.push_str(&format!("{}={}\n", key, escape(value)));
+        }
+        if block.len() > self.size {
+            return Err(format!(
+                "Environment block too small: {} bytes needed, {} available",
+                block.len(), self.size
+            ));
+        }
+        let mut bytes = block.into_bytes();
+        bytes.resize(self.size, b'#');
+        Ok(bytes)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let block = self.to_block()?;
+        // Overwrite in place: same size, no truncation, so the file keeps its blocks
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)
+            .map_err(|e| format!("Failed to open {}: {}", self.path.display(), e))?;
+        file.write_all(&block)
+            .and_then(|_| file.sync_all())
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))?;
+        Ok(())
+    }
+}
+
+// Lines end at a newline not escaped by a backslash
+fn split_lines(body: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                current.push(c);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            '\n' => lines.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+fn escape(value: &str) -> String {
+    let mut out = String::new();
+    for c in value.chars() {
+        if c == '\\' || c == '\n' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(next) = chars.next() {
+                out.push(next);
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Load, change and save grubenv without another grublist writing it in between
+pub fn update(paths: &Paths, change: impl FnOnce(&mut GrubEnv)) -> Result<(), String> {
+    let _lock = safe_write::lock(paths)
+        .map_err(|e| format!("Failed to lock: {}", e))?;
+    let mut env = GrubEnv::load(paths)?;
+    change(&mut env);
+    env.save()
+}
+
+/// Boot the given entry once at the next restart (grub-reboot)
+pub fn set_next_entry(paths: &Paths, entry: &str) -> Result<(), String> {
+    update(paths, |env| env.set("next_entry", entry))
+}
+
+/// Make the given entry the permanent default through saved_entry (grub-set-default).
+/// Only takes effect when grub.cfg was generated with GRUB_DEFAULT=saved.
+pub fn set_saved_entry(paths: &Paths, entry: &str) -> Result<(), String> {
+    update(paths, |env| env.set("saved_entry", entry))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DefaultSource {
+    NextEntry,
+    SavedEntry,
+    GrubDefault,
+}
+
+impl DefaultSource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DefaultSource::NextEntry => "next_entry (grubenv, boot once)",
+            DefaultSource::SavedEntry => "saved_entry (grubenv)",
+            DefaultSource::GrubDefault => "GRUB_DEFAULT (/etc/default/grub)",
+        }
+    }
+}
+
+/// Which mechanism picks the entry at the next boot, following the logic
+/// grub-mkconfig writes into grub.cfg: a pending next_entry always wins, then
+/// saved_entry if GRUB_DEFAULT=saved, otherwise GRUB_DEFAULT itself.
+/// An empty saved_entry falls back to the first entry.
+pub fn effective_default(grub_default: &str, env: &GrubEnv) -> (DefaultSource, String) {
+    if let Some(next) = env.get("next_entry").filter(|v| !v.is_empty()) {
+        return (DefaultSource::NextEntry, next.to_string());
+    }
+    if is_saved(grub_default) {
+        let saved = env.get("saved_entry").filter(|v| !v.is_empty()).unwrap_or("0");
+        return (DefaultSource::SavedEntry, saved.to_string());
+    }
+    let value = grub_default.trim_matches('"').trim_matches('\'');
+    (DefaultSource::GrubDefault, if value.is_empty() { "0".to_string() } else { value.to_string() })
+}
+
+pub fn is_saved(grub_default: &str) -> bool {
+    grub_default.trim_matches('"').trim_matches('\'') == "saved"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(body: &str) -> Vec<u8> {
+        let mut bytes = format!("{}{}", HEADER, body).into_bytes();
+        bytes.resize(DEFAULT_SIZE, b'#');
+        bytes
+    }
+
+    #[test]
+    fn parse_grub_editenv_block() {
+        let env = GrubEnv::parse(&block("saved_entry=gnulinux-simple-1234\nnext_entry=\n")).unwrap();
+        assert_eq!(env.get("saved_entry"), Some("gnulinux-simple-1234"));
+        assert_eq!(env.get("next_entry"), Some(""));
+        assert_eq!(env.get("missing"), None);
+        assert_eq!(env.vars.len(), 2);
+    }
+
+    #[test]
+    fn invalid_header() {
+        assert!(GrubEnv::parse(b"saved_entry=0\n").is_err());
+        assert!(GrubEnv::parse(&[0xff, 0xfe]).is_err());
+    }
+
+    #[test]
+    fn round_trip_keeps_size_and_order() {
+        let original = block("saved_entry=0\nboot_success=1\n");
+        let mut env = GrubEnv::parse(&original).unwrap();
+        assert_eq!(env.to_block().unwrap(), original);
+
+        env.set("saved_entry", "1>2");
+        env.set("recordfail", "1");
+        env.unset("boot_success");
+        let bytes = env.to_block().unwrap();
+        assert_eq!(bytes.len(), DEFAULT_SIZE);
+        assert_eq!(bytes, block("saved_entry=1>2\nrecordfail=1\n"));
+    }
+
+    #[test]
+    fn escapes() {
+        let mut env = GrubEnv::empty(PathBuf::new());
+        env.set("value", "a\\b\nc");
+        let bytes = env.to_block().unwrap();
+        assert!(bytes.starts_with(format!("{}value=a\\\\b\\\nc\n#", HEADER).as_bytes()));
+        let parsed = GrubEnv::parse(&bytes).unwrap();
+        assert_eq!(parsed.get("value"), Some("a\\b\nc"));
+    }
+
+    #[test]
+    fn block_too_small() {
+        let mut env = GrubEnv::parse(&block("")).unwrap();
+        env.set("big", &"x".repeat(DEFAULT_SIZE));
+        assert!(env.to_block().is_err());
+    }
+
+    #[test]
+    fn default_source() {
+        let mut env = GrubEnv::empty(PathBuf::new());
+        assert_eq!(effective_default("", &env), (DefaultSource::GrubDefault, "0".to_string()));
+        assert_eq!(effective_default("\"1>0\"", &env), (DefaultSource::GrubDefault, "1>0".to_string()));
+        assert_eq!(effective_default("saved", &env), (DefaultSource::SavedEntry, "0".to_string()));
+        env.set("saved_entry", "2");
+        assert_eq!(effective_default("saved", &env), (DefaultSource::SavedEntry, "2".to_string()));
+        env.set("next_entry", "1");
+        assert_eq!(effective_default("saved", &env), (DefaultSource::NextEntry, "1".to_string()));
+    }
+}
@@ -1,21 +1,44 @@
 use std::fs;
 use std::io;
-use std::path::Path;
-use crate::kernel_info::{list_kernel_files, get_current_kernel, is_kernel_in_use};
+use std::path::{Path, PathBuf};
+use crate::kernel_info::{list_kernel_files, get_current_kernel};
+use serde::Serialize;
+use crate::grub::{self, Entry};
+use crate::grub_config::GrubConfig;
+use crate::grubenv::{self, GrubEnv};
+use crate::paths::Paths;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct KernelToClean {
     pub version: String,
     pub files: Vec<String>,
     pub size: u64,
     pub in_use: bool,
+    // Booted by the GRUB_DEFAULT entry, removing it would break the next boot
+    pub is_default: bool,
 }
 
-pub fn scan_unused_kernels() -> Vec<KernelToClean> {
+// File name of the kernel image the default entry boots, if it can be resolved
+pub fn default_kernel_image(paths: &Paths, menu: &Entry) -> Option<String> {
+    let config = GrubConfig::load(paths).ok()?;
+    // With GRUB_DEFAULT=saved the default lives in grubenv
+    let default = if grubenv::is_saved(&config.grub_default) {
+        GrubEnv::load(paths).ok()?.get("saved_entry").unwrap_or("0").to_string()
+    } else {
+        config.grub_default
+    };
+    let path = crate::grub_validate::resolve_default(menu, &default)?;
+    let linux = grub::try_get_entry(menu, &path)?.boot.linux.as_ref()?;
+    Path::new(linux).file_name()?.to_str().map(|s| s.to_string())
+}
+
+pub fn scan_unused_kernels(paths: &Paths, menu: &Entry) -> Vec<KernelToClean> {
     let mut kernels_to_clean = Vec::new();
-    let current_kernel = get_current_kernel();
+    // The running kernel only matters when managing the host itself
+    let current_kernel = if paths.is_host() { get_current_kernel() } else { None };
+    let default_image = default_kernel_image(paths, menu);
     
-    for kernel_info in list_kernel_files() {
+    for kernel_info in list_kernel_files(paths) {
         let version = kernel_info.version.clone();
         let in_use = current_kernel.as_ref()
             .map(|k| k == &version)
@@ -24,28 +47,21 @@ pub fn scan_unused_kernels() -> Vec<KernelToClean> {
         if !in_use {
             let mut files = Vec::new();
             let mut total_size = 0u64;
-            
-            // Find all files related to this kernel
-            let boot_dir = Path::new("/boot");
-            if let Ok(entries) = fs::read_dir(boot_dir) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-                        if file_name.contains(&version) {
-                            if let Ok(metadata) = fs::metadata(&path) {
-                                total_size += metadata.len();
-                                files.push(path.to_string_lossy().to_string());
-                            }
-                        }
-                    }
+            for path in kernel_files(paths, &version) {
+                if let Ok(metadata) = fs::metadata(&path) {
+                    total_size += metadata.len();
+                    files.push(path.to_string_lossy().to_string());
                 }
             }
             
+            let is_default = default_image.as_deref() == Some(format!("vmlinuz-{}", version).as_str());
+            
             kernels_to_clean.push(KernelToClean {
                 version,
                 files,
                 size: total_size,
                 in_use,
+                is_default,
             });
         }
     }
@@ -54,24 +70,52 @@ pub fn scan_unused_kernels() -> Vec<KernelToClean> {
     kernels_to_clean
 }
 
-pub fn delete_kernel_files(kernel_version: &str) -> io::Result<()> {
-    let boot_dir = Path::new("/boot");
-    
-    if let Ok(entries) = fs::read_dir(boot_dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-                if file_name.contains(kernel_version) {
-                    if path.is_file() {
-                        fs::remove_file(&path)?;
-                    } else if path.is_dir() {
-                        fs::remove_dir_all(&path)?;
-                    }
-                }
-            }
-        }
+// Files that belong to exactly this kernel version: its files in /boot and
+// the Boot Loader Specification entries booting it. Names are compared
+// whole, 5.15.0-9 must not take vmlinuz-5.15.0-91-generic along.
+fn kernel_files(paths: &Paths, kernel_version: &str) -> Vec<PathBuf> {
+    let image = format!("vmlinuz-{}", kernel_version);
+    let names = [
+        image.clone(),
+        format!("initrd.img-{}", kernel_version),
+        format!("initramfs-{}.img", kernel_version),
+        format!("System.map-{}", kernel_version),
+        format!("config-{}", kernel_version),
+        // FIPS checksum on Fedora/RHEL
+        format!(".{}.hmac", image),
+    ];
+    let mut files: Vec<PathBuf> = names.iter()
+        .map(|name| paths.boot_dir().join(name))
+        .filter(|path| path.is_file())
+        .collect();
+    files.extend(bls_entries_booting(paths, &image));
+    files
+}
+
+// /boot/loader/entries/*.conf whose `linux` line loads `image`
+fn bls_entries_booting(paths: &Paths, image: &str) -> Vec<PathBuf> {
+    let Ok(dir) = fs::read_dir(paths.bls_entries_dir()) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<PathBuf> = dir.flatten()
+        .map(|e| e.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "conf") && path.is_file())
+        .filter(|path| {
+            fs::read_to_string(path).is_ok_and(|content| content.lines().any(|line| {
+                let mut words = line.split_whitespace();
+                words.next() == Some("linux")
+                    && words.next().and_then(|linux| Path::new(linux).file_name()).is_some_and(|name| name == image)
+            }))
+        })
+        .collect();
+    entries.sort();
+    entries
+}
+
+pub fn delete_kernel_files(paths: &Paths, kernel_version: &str) -> io::Result<()> {
+    for path in kernel_files(paths, kernel_version) {
+        fs::remove_file(&path)?;
     }
-    
     Ok(())
 }
 
@@ -88,3 +132,43 @@ pub fn format_size(bytes: u64) -> String {
     format!("{:.2} {}", size, UNITS[unit_idx])
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_of_one_version() {
+        let root = std::env::temp_dir().join(format!("grublist-test-kernels-{}", std::process::id()));
+        let paths = Paths::new(&root);
+        fs::create_dir_all(paths.bls_entries_dir()).unwrap();
+        for version in ["5.14.0-9.el9.x86_64", "5.14.0-91.el9.x86_64"] {
+            for name in [format!("vmlinuz-{}", version), format!(".vmlinuz-{}.hmac", version),
+                format!("initramfs-{}.img", version), format!("System.map-{}", version), format!("config-{}", version)] {
+                fs::write(paths.boot_dir().join(name), "x").unwrap();
+            }
+            fs::write(paths.bls_entries_dir().join(format!("abcd-{}.conf", version)),
+                format!("title Red Hat Enterprise Linux ({})\nversion {}\nlinux /vmlinuz-{}\ninitrd /initramfs-{}.img\n",
+                    version, version, version, version)).unwrap();
+        }
+        // Rescue entry with its own image
+        fs::write(paths.bls_entries_dir().join("abcd-0-rescue.conf"), "linux /vmlinuz-0-rescue-abcd\n").unwrap();
+
+        let names: Vec<String> = kernel_files(&paths, "5.14.0-9.el9.x86_64").iter()
+            .map(|p| paths.display(p))
+            .collect();
+        assert_eq!(names, vec![
+            "/boot/vmlinuz-5.14.0-9.el9.x86_64",
+            "/boot/initramfs-5.14.0-9.el9.x86_64.img",
+            "/boot/System.map-5.14.0-9.el9.x86_64",
+            "/boot/config-5.14.0-9.el9.x86_64",
+            "/boot/.vmlinuz-5.14.0-9.el9.x86_64.hmac",
+            "/boot/loader/entries/abcd-5.14.0-9.el9.x86_64.conf",
+        ]);
+
+        delete_kernel_files(&paths, "5.14.0-9.el9.x86_64").unwrap();
+        assert!(kernel_files(&paths, "5.14.0-9.el9.x86_64").is_empty());
+        assert_eq!(kernel_files(&paths, "5.14.0-91.el9.x86_64").len(), 6);
+        assert!(paths.bls_entries_dir().join("abcd-0-rescue.conf").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
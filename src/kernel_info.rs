@@ -1,9 +1,11 @@
 use std::fs;
-use std::io;
 use std::path::Path;
 use regex::Regex;
+use serde::Serialize;
+use crate::grub::Entry;
+use crate::paths::Paths;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct KernelInfo {
     pub version: String,
     pub release: String,
@@ -11,16 +13,21 @@ pub struct KernelInfo {
     pub path: String,
 }
 
-pub fn get_kernel_version_from_entry(entry_name: &str) -> Option<KernelInfo> {
+pub fn get_kernel_version_from_entry(paths: &Paths, entry: &Entry) -> Option<KernelInfo> {
+    // Prefer the kernel image the entry actually boots
+    if let Some(info) = entry.boot.linux.as_deref().and_then(|linux| kernel_info_from_image(paths, linux)) {
+        return Some(info);
+    }
+    
     // Try to extract kernel version from entry name
+    let entry_name = entry.name.as_str();
     // Entry names often contain kernel version like "Ubuntu, with Linux 5.15.0-91-generic"
     let version_re = Regex::new(r"(\d+\.\d+\.\d+[-\w]*)").ok()?;
     if let Some(caps) = version_re.captures(entry_name) {
         let version = caps.get(1)?.as_str().to_string();
         
         // Try to find corresponding vmlinuz file
-        let boot_dir = Path::new("/boot");
-        if let Ok(entries) = fs::read_dir(boot_dir) {
+        if let Ok(entries) = fs::read_dir(paths.boot_dir()) {
             for entry in entries.flatten() {
                 let path = entry.path();
                 if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
@@ -41,11 +48,28 @@ pub fn get_kernel_version_from_entry(entry_name: &str) -> Option<KernelInfo> {
     None
 }
 
-pub fn list_kernel_files() -> Vec<KernelInfo> {
+// The linux path in grub.cfg is relative to the partition GRUB reads it from,
+// which is "/" when /boot is a separate partition, so match on the file name only
+fn kernel_info_from_image(paths: &Paths, linux_path: &str) -> Option<KernelInfo> {
+    let file_name = Path::new(linux_path).file_name()?.to_str()?;
+    let path = paths.boot_dir().join(file_name);
+    if !path.exists() {
+        return None;
+    }
+    
+    let version = file_name.strip_prefix("vmlinuz-").unwrap_or(file_name).to_string();
+    Some(KernelInfo {
+        version: version.clone(),
+        release: version,
+        arch: "x86_64".to_string(), // Default, could be improved
+        path: path.to_string_lossy().to_string(),
+    })
+}
+
+pub fn list_kernel_files(paths: &Paths) -> Vec<KernelInfo> {
     let mut kernels = Vec::new();
-    let boot_dir = Path::new("/boot");
     
-    if let Ok(entries) = fs::read_dir(boot_dir) {
+    if let Ok(entries) = fs::read_dir(paths.boot_dir()) {
         for entry in entries.flatten() {
             let path = entry.path();
             if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
@@ -80,11 +104,3 @@ pub fn get_current_kernel() -> Option<String> {
     }
     None
 }
-
-pub fn is_kernel_in_use(kernel_version: &str) -> bool {
-    if let Some(current) = get_current_kernel() {
-        return current == kernel_version;
-    }
-    false
-}
-
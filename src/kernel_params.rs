@@ -0,0 +1,426 @@
+// Built-in knowledge of common kernel command line parameters.
+//
+// Used to complete parameter names, show what a parameter does and warn
+// about typos, malformed values and parameters that contradict each other.
+// The kernel ignores (or passes to init) what it doesn't know, so nothing
+// here stops a parameter from being saved.
+
+use crate::grub_config::{kernel_parameter_count, split_parameter};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueKind {
+    // No value (`quiet`)
+    Flag,
+    // No value, or one of the listed ones (`nosmt`, `nosmt=force`)
+    FlagOr(&'static [&'static str]),
+    Integer { min: i64, max: i64 },
+    // Exactly one of the listed values
+    Choice(&'static [&'static str]),
+    // Comma separated list of the listed values
+    ChoiceList(&'static [&'static str]),
+    // CPU list like 1,3-5, optionally preceded by flags (`isolcpus=nohz,domain,2-7`)
+    CpuList,
+    // Number with an optional K/M/G suffix
+    Size,
+    Text,
+}
+
+impl ValueKind {
+    pub fn describe(&self) -> String {
+        match self {
+            ValueKind::Flag => "flag, no value".to_string(),
+            ValueKind::FlagOr(values) => format!("flag, or ={}", values.join("|")),
+            ValueKind::Integer { min, max } => format!("integer {}..{}", min, max),
+            ValueKind::Choice(values) => format!("one of {}", values.join(", ")),
+            ValueKind::ChoiceList(values) => format!("comma separated list of {}", values.join(", ")),
+            ValueKind::CpuList => "CPU list, e.g. 1,3-5".to_string(),
+            ValueKind::Size => "size, e.g. 512M".to_string(),
+            ValueKind::Text => "text".to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct KernelParam {
+    pub name: &'static str,
+    pub value: ValueKind,
+    // Meant to be given more than once (`console=`)
+    pub repeatable: bool,
+    pub help: &'static str,
+}
+
+const fn param(name: &'static str, value: ValueKind, help: &'static str) -> KernelParam {
+    KernelParam { name, value, repeatable: false, help }
+}
+
+const BOOL: ValueKind = ValueKind::Choice(&["0", "1"]);
+const MAX: i64 = i64::MAX;
+
+pub const PARAMS: &[KernelParam] = &[
+    // Boot and root filesystem
+    param("root", ValueKind::Text, "Root filesystem: /dev/sda2, UUID=..., PARTUUID=... or LABEL=..."),
+    param("rootfstype", ValueKind::Text, "Filesystem type of the root filesystem, e.g. ext4."),
+    param("rootflags", ValueKind::Text, "Mount options for the root filesystem."),
+    param("rootwait", ValueKind::Flag, "Wait (forever) for the root device to appear."),
+    param("ro", ValueKind::Flag, "Mount the root filesystem read-only at first (normal with an initrd)."),
+    param("rw", ValueKind::Flag, "Mount the root filesystem read-write."),
+    param("init", ValueKind::Text, "Program run as init instead of /sbin/init, e.g. /bin/sh."),
+    param("single", ValueKind::Flag, "Boot into single user (rescue) mode."),
+    param("emergency", ValueKind::Flag, "Boot into the emergency shell."),
+    param("resume", ValueKind::Text, "Swap device holding the hibernation image."),
+    param("noresume", ValueKind::Flag, "Don't resume from hibernation."),
+    param("fsck.mode", ValueKind::Choice(&["auto", "force", "skip"]), "systemd: whether to check filesystems at boot."),
+    param("fsck.repair", ValueKind::Choice(&["preen", "yes", "no"]), "systemd: how to repair filesystem errors."),
+    param("systemd.unit", ValueKind::Text, "systemd: target to boot, e.g. rescue.target or multi-user.target."),
+    param("rd.break", ValueKind::FlagOr(&["cmdline", "pre-udev", "pre-trigger", "initqueue", "pre-mount", "mount", "pre-pivot", "cleanup"]), "dracut: drop to a shell in the initramfs."),
+
+    // Console and logging
+    param("quiet", ValueKind::Flag, "Only print important kernel messages during boot."),
+    param("splash", ValueKind::FlagOr(&["silent", "verbose"]), "Show a graphical boot splash (Plymouth)."),
+    param("rhgb", ValueKind::Flag, "Fedora/RHEL: show the graphical boot splash (Plymouth)."),
+    param("debug", ValueKind::Flag, "Print all kernel messages during boot."),
+    param("loglevel", ValueKind::Integer { min: 0, max: 7 }, "Console log level: only messages more important than this are printed."),
+    param("dyndbg", ValueKind::Text, "Enable dynamic debug messages, e.g. dyndbg=\"file foo.c +p\" (quoted because of the spaces)."),
+    param("ignore_loglevel", ValueKind::Flag, "Print all kernel messages regardless of the log level."),
+    KernelParam {
+        name: "console",
+        value: ValueKind::Text,
+        repeatable: true,
+        help: "Output console, e.g. tty0 or ttyS0,115200n8. May be given more than once; the last one is /dev/console.",
+    },
+    param("fbcon", ValueKind::Text, "Framebuffer console options, e.g. nodefer or rotate:1."),
+    param("earlyprintk", ValueKind::Text, "Early boot messages, e.g. serial,ttyS0,115200 or vga."),
+    param("panic", ValueKind::Integer { min: -1, max: MAX }, "Seconds before rebooting after a panic, 0 waits forever, -1 reboots at once."),
+
+    // Graphics
+    param("nomodeset", ValueKind::Flag, "Don't load kernel mode setting graphics drivers, use firmware graphics."),
+    param("i915.modeset", BOOL, "Intel graphics kernel mode setting."),
+    param("amdgpu.modeset", BOOL, "AMD graphics kernel mode setting."),
+    param("nouveau.modeset", BOOL, "nouveau (NVIDIA) kernel mode setting."),
+    param("nvidia_drm.modeset", BOOL, "NVIDIA proprietary driver kernel mode setting, needed for Wayland."),
+    param("video", ValueKind::Text, "Framebuffer mode, e.g. 1920x1080 or HDMI-A-1:1920x1080@60."),
+
+    // CPU and security
+    param("mitigations", ValueKind::Choice(&["off", "auto", "auto,nosmt"]), "CPU vulnerability mitigations. off is faster but unsafe."),
+    param("spectre_v2", ValueKind::Choice(&["on", "off", "auto", "retpoline", "retpoline,generic", "retpoline,lfence", "eibrs", "eibrs,retpoline", "eibrs,lfence", "ibrs"]), "Spectre variant 2 mitigation."),
+    param("nosmt", ValueKind::FlagOr(&["force"]), "Disable simultaneous multithreading (Hyper-Threading)."),
+    param("nokaslr", ValueKind::Flag, "Disable kernel address space layout randomization."),
+    param("maxcpus", ValueKind::Integer { min: 0, max: MAX }, "Maximum number of CPUs brought up at boot."),
+    param("nr_cpus", ValueKind::Integer { min: 1, max: MAX }, "Maximum number of CPUs the kernel supports."),
+    param("nosmp", ValueKind::Flag, "Use a single CPU."),
+    param("isolcpus", ValueKind::CpuList, "CPUs kept away from the scheduler, e.g. nohz,domain,2-7."),
+    param("nohz_full", ValueKind::CpuList, "CPUs that run without the scheduler tick when possible."),
+    param("rcu_nocbs", ValueKind::CpuList, "CPUs whose RCU callbacks are offloaded to other CPUs."),
+    param("irqaffinity", ValueKind::CpuList, "CPUs handling interrupts by default."),
+    param("preempt", ValueKind::Choice(&["none", "voluntary", "full"]), "Preemption model (kernels built with PREEMPT_DYNAMIC)."),
+    param("threadirqs", ValueKind::Flag, "Run interrupt handlers in threads."),
+    param("intel_pstate", ValueKind::Choice(&["disable", "active", "passive", "no_hwp", "force", "hwp_only", "support_acpi_ppc", "per_cpu_perf_limits"]), "Intel CPU frequency driver mode."),
+    param("amd_pstate", ValueKind::Choice(&["disable", "passive", "active", "guided"]), "AMD CPU frequency driver mode."),
+    param("processor.max_cstate", ValueKind::Integer { min: 0, max: 9 }, "Deepest ACPI C-state used."),
+    param("intel_idle.max_cstate", ValueKind::Integer { min: 0, max: 9 }, "Deepest C-state used by intel_idle, 0 disables the driver."),
+    param("idle", ValueKind::Choice(&["poll", "halt", "nomwait"]), "CPU idle method."),
+    param("tsc", ValueKind::Choice(&["reliable", "noirqtime", "unstable", "nowatchdog"]), "Time stamp counter handling."),
+    param("clocksource", ValueKind::Text, "Clock source, e.g. tsc, hpet or acpi_pm."),
+    param("selinux", BOOL, "Enable (1) or disable (0) SELinux."),
+    param("enforcing", BOOL, "SELinux enforcing (1) or permissive (0) mode."),
+    param("apparmor", BOOL, "Enable (1) or disable (0) AppArmor."),
+    param("security", ValueKind::Text, "Major security module to enable, e.g. selinux or apparmor."),
+    param("lsm", ValueKind::Text, "Order of security modules, comma separated."),
+    param("audit", ValueKind::Choice(&["0", "1", "off", "on"]), "Kernel audit subsystem."),
+    param("lockdown", ValueKind::Choice(&["integrity", "confidentiality"]), "Kernel lockdown mode."),
+    param("module.sig_enforce", BOOL, "Only load signed kernel modules."),
+
+    // IOMMU and virtualization
+    param("iommu", ValueKind::Choice(&["off", "force", "noforce", "biomerge", "panic", "nopanic", "merge", "nomerge", "soft", "pt", "nopt"]), "IOMMU mode. pt passes devices through to improve performance."),
+    param("intel_iommu", ValueKind::ChoiceList(&["on", "off", "igfx_off", "forcedac", "strict", "sp_off", "sm_on", "sm_off", "tboot_noforce"]), "Intel VT-d IOMMU, on to use PCI passthrough."),
+    param("amd_iommu", ValueKind::ChoiceList(&["off", "on", "fullflush", "force_isolation", "force_enable", "pgtbl_v1", "pgtbl_v2", "irtcachedis", "nohugepages", "v2_pgsizes_only"]), "AMD IOMMU options."),
+    param("iommu.passthrough", BOOL, "Pass DMA through the IOMMU untranslated."),
+    param("iommu.strict", BOOL, "Invalidate IOMMU TLBs synchronously."),
+    param("vfio-pci.ids", ValueKind::Text, "PCI vendor:device IDs bound to vfio-pci for passthrough, e.g. 10de:1b80,10de:10f0."),
+    param("kvm.ignore_msrs", BOOL, "Ignore guest accesses to unhandled MSRs."),
+
+    // Memory
+    param("mem", ValueKind::Size, "Use only this much memory."),
+    param("memmap", ValueKind::Text, "Mark a memory region, e.g. 64M@1G or 4G$8G."),
+    param("hugepages", ValueKind::Integer { min: 0, max: MAX }, "Number of huge pages reserved at boot (of hugepagesz)."),
+    param("hugepagesz", ValueKind::Size, "Size of the huge pages reserved by the following hugepages=, e.g. 2M or 1G."),
+    param("default_hugepagesz", ValueKind::Size, "Default huge page size."),
+    param("transparent_hugepage", ValueKind::Choice(&["always", "madvise", "never"]), "Transparent huge pages."),
+    param("crashkernel", ValueKind::Text, "Memory reserved for kdump, e.g. 256M or auto."),
+    param("zswap.enabled", BOOL, "Compressed swap cache."),
+    param("cgroup_no_v1", ValueKind::Text, "cgroup v1 controllers to disable, or all."),
+    param("systemd.unified_cgroup_hierarchy", BOOL, "systemd: use cgroup v2 (1) or v1 (0)."),
+
+    // Hardware
+    KernelParam {
+        name: "acpi_osi",
+        value: ValueKind::Text,
+        repeatable: true,
+        help: "Change the OS the firmware is told it runs on, e.g. acpi_osi=! acpi_osi=\"Windows 2015\".",
+    },
+    param("acpi_backlight", ValueKind::Choice(&["vendor", "video", "native", "none"]), "Which driver controls the backlight."),
+    param("nowatchdog", ValueKind::Flag, "Disable the soft and hard lockup detectors."),
+    param("nmi_watchdog", ValueKind::Choice(&["0", "1", "panic", "nopanic"]), "Hard lockup detector."),
+    param("pti", ValueKind::Choice(&["on", "off", "auto"]), "Page table isolation (Meltdown mitigation)."),
+    param("nopti", ValueKind::Flag, "Same as pti=off."),
+    param("vsyscall", ValueKind::Choice(&["emulate", "xonly", "none"]), "Legacy vsyscall page handling."),
+    param("module_blacklist", ValueKind::Text, "Comma separated modules that must not be loaded."),
+    param("efi", ValueKind::Text, "EFI options, e.g. noruntime."),
+    param("noefi", ValueKind::Flag, "Don't use EFI runtime services."),
+    param("acpi", ValueKind::Choice(&["off", "force", "strict", "noirq", "rsdt", "copy_dsdt", "nocmcff"]), "ACPI mode."),
+    param("noapic", ValueKind::Flag, "Don't use the IO-APIC."),
+    param("nolapic", ValueKind::Flag, "Don't use the local APIC."),
+    param("pci", ValueKind::Text, "PCI options, e.g. noaer, nomsi or realloc."),
+    param("pcie_aspm", ValueKind::Choice(&["off", "force"]), "PCIe Active State Power Management."),
+    param("nvme_core.default_ps_max_latency_us", ValueKind::Integer { min: 0, max: MAX }, "Deepest NVMe power state by latency, 0 disables APST."),
+    param("usbcore.autosuspend", ValueKind::Integer { min: -1, max: MAX }, "Seconds before idle USB devices are suspended, -1 disables."),
+    param("net.ifnames", BOOL, "Predictable network interface names (0 for eth0 style)."),
+    param("biosdevname", BOOL, "Network interface names from the firmware (Dell)."),
+    param("ipv6.disable", BOOL, "Disable IPv6."),
+];
+
+// The kernel treats - and _ in parameter names alike
+fn normalize(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+pub fn lookup(name: &str) -> Option<&'static KernelParam> {
+    let name = normalize(name);
+    PARAMS.iter().find(|p| normalize(p.name) == name)
+}
+
+/// Known parameters whose name starts with `prefix`, for completion
+pub fn complete(prefix: &str) -> Vec<&'static KernelParam> {
+    let prefix = normalize(prefix);
+    PARAMS.iter().filter(|p| normalize(p.name).starts_with(&prefix)).collect()
+}
+
+/// Warning about a single `name[=value]` parameter, if any
+pub fn check_param(param: &str) -> Option<String> {
+    if param.matches('"').count() % 2 == 1 {
+        return Some(format!("{}: unbalanced double quote, the rest of the command line becomes part of it", param));
+    }
+    let (name, value) = split_parameter(param);
+    // GRUB variables like $vt_handoff are expanded at boot
+    if name.starts_with('$') {
+        return None;
+    }
+    let Some(known) = lookup(&name) else {
+        // Module parameters (module.param) can't all be known
+        if name.contains('.') {
+            return None;
+        }
+        return Some(match suggest(&name) {
+            Some(similar) => format!("{}: unknown parameter, did you mean {}?", name, similar),
+            None => format!("{}: unknown parameter", name),
+        });
+    };
+
+    let problem = match (known.value, value.as_deref()) {
+        (ValueKind::Flag, Some(_)) => Some("takes no value".to_string()),
+        (ValueKind::Flag | ValueKind::FlagOr(_), None) => None,
+        (_, None) => Some(format!("needs a value ({})", known.value.describe())),
+        (kind, Some(value)) => (!value_matches(kind, value))
+            .then(|| format!("invalid value '{}', expected {}", value, kind.describe())),
+    };
+    problem.map(|p| format!("{}: {}", name, p))
+}
+
+fn value_matches(kind: ValueKind, value: &str) -> bool {
+    match kind {
+        ValueKind::Flag => false,
+        ValueKind::FlagOr(values) | ValueKind::Choice(values) => values.contains(&value),
+        ValueKind::Integer { min, max } => value.parse::<i64>().is_ok_and(|n| n >= min && n <= max),
+        ValueKind::ChoiceList(values) => value.split(',').all(|v| values.contains(&v)),
+        ValueKind::CpuList => {
+            // Leading flags (isolcpus=nohz,domain,managed_irq,...) are skipped
+            let mut parts = value.split(',').peekable();
+            while parts.peek().is_some_and(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_lowercase() || c == '_')) {
+                parts.next();
+            }
+            let ranges: Vec<&str> = parts.collect();
+            !ranges.is_empty() && ranges.iter().all(|range| {
+                // 2-7, 0-15:2/4 (used CPUs per group), N for the last CPU
+                let range = range.split(':').next().unwrap_or_default();
+                range.split('-').count() <= 2
+                    && range.split('-').all(|n| n == "N" || (!n.is_empty() && n.chars().all(|c| c.is_ascii_digit())))
+            })
+        }
+        ValueKind::Size => {
+            let digits = value.trim_end_matches(['K', 'M', 'G', 'T', 'k', 'm', 'g', 't']);
+            !digits.is_empty() && digits.len() + 1 >= value.len() && digits.chars().all(|c| c.is_ascii_digit())
+        }
+        ValueKind::Text => !value.is_empty(),
+    }
+}
+
+// Pairs that contradict each other: a bare name matches any value
+const CONFLICTS: &[(&str, &str)] = &[
+    ("ro", "rw"),
+    ("quiet", "debug"),
+    ("resume", "noresume"),
+    ("nosmp", "maxcpus"),
+    ("nomodeset", "i915.modeset=1"),
+    ("nomodeset", "amdgpu.modeset=1"),
+    ("nomodeset", "nouveau.modeset=1"),
+    ("nomodeset", "nvidia_drm.modeset=1"),
+    ("mitigations=off", "spectre_v2=on"),
+    ("selinux=0", "enforcing=1"),
+    ("iommu=off", "intel_iommu=on"),
+    ("iommu=off", "amd_iommu=on"),
+];
+
+/// Warnings for a whole command line: per parameter problems, parameters
+/// given twice and combinations that contradict each other
+pub fn check(params: &[String]) -> Vec<String> {
+    // Init arguments after `--` are not the kernel's
+    let params = &params[..kernel_parameter_count(params)];
+    let mut warnings: Vec<String> = params.iter().filter_map(|p| check_param(p)).collect();
+
+    let parsed: Vec<(String, Option<String>)> = params.iter()
+        .map(|p| {
+            let (name, value) = split_parameter(p);
+            (normalize(&name), value)
+        })
+        .collect();
+
+    for (i, (name, value)) in parsed.iter().enumerate() {
+        if parsed[..i].iter().any(|(earlier, _)| earlier == name) {
+            continue;
+        }
+        let repeatable = lookup(name).is_some_and(|p| p.repeatable)
+            // hugepagesz=2M hugepages=512 hugepagesz=1G hugepages=4 is valid
+            || name == "hugepages" || name == "hugepagesz";
+        let values: Vec<&Option<String>> = parsed.iter().filter(|(n, _)| n == name).map(|(_, v)| v).collect();
+        if values.len() > 1 && !repeatable {
+            if values.iter().all(|v| *v == value) {
+                warnings.push(format!("{} is given {} times", name, values.len()));
+            } else {
+                let last = values.last().and_then(|v| v.as_deref()).unwrap_or("");
+                warnings.push(format!("{} is given {} times with different values, the last one ({}) wins", name, values.len(), last));
+            }
+        }
+    }
+
+    let present = |pattern: &str| {
+        let (name, value) = split_parameter(pattern);
+        let name = normalize(&name);
+        // The last occurrence is the one that counts
+        parsed.iter().rev().find(|(n, _)| *n == name)
+            .is_some_and(|(_, v)| value.is_none() || *v == value)
+    };
+    for (a, b) in CONFLICTS {
+        if present(a) && present(b) {
+            warnings.push(format!("{} and {} contradict each other", a, b));
+        }
+    }
+    warnings
+}
+
+// Closest known name within two edits, for typos like `nomodset`
+fn suggest(name: &str) -> Option<&'static str> {
+    let name = normalize(name);
+    PARAMS.iter()
+        .map(|p| (edit_distance(&name, &normalize(p.name)), p.name))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(previous + 1).min(row[j] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn single_parameters() {
+        assert_eq!(check_param("quiet"), None);
+        assert_eq!(check_param("rhgb"), None);
+        assert_eq!(check_param("quiet=1"), Some("quiet: takes no value".to_string()));
+        assert_eq!(check_param("nosmt=force"), None);
+        assert_eq!(check_param("loglevel"), Some("loglevel: needs a value (integer 0..7)".to_string()));
+        assert_eq!(
+            check_param("loglevel=8"),
+            Some("loglevel: invalid value '8', expected integer 0..7".to_string())
+        );
+        assert_eq!(check_param("isolcpus=nohz,domain,2-7"), None);
+        assert_eq!(check_param("hugepagesz=1G"), None);
+        assert!(check_param("dyndbg=\"file foo.c +p").unwrap().contains("unbalanced double quote"));
+        // GRUB variables and module parameters are not known in advance
+        assert_eq!(check_param("$vt_handoff"), None);
+        assert_eq!(check_param("snd_hda_intel.power_save=0"), None);
+    }
+
+    #[test]
+    fn typos() {
+        assert_eq!(
+            check_param("nomodset"),
+            Some("nomodset: unknown parameter, did you mean nomodeset?".to_string())
+        );
+        assert_eq!(check_param("frobnicate"), Some("frobnicate: unknown parameter".to_string()));
+    }
+
+    #[test]
+    fn dash_and_underscore() {
+        assert!(lookup("ignore-loglevel").is_some());
+        assert_eq!(check_param("nvidia-drm.modeset=1"), None);
+        assert_eq!(
+            check(&params(&["nomodeset", "nvidia-drm.modeset=1"])),
+            vec!["nomodeset and nvidia_drm.modeset=1 contradict each other"]
+        );
+        assert_eq!(
+            check(&params(&["vfio-pci.ids=10de:1b80", "vfio_pci.ids=10de:1b80"])),
+            vec!["vfio_pci.ids is given 2 times"]
+        );
+    }
+
+    #[test]
+    fn duplicates() {
+        assert_eq!(
+            check(&params(&["loglevel=3", "quiet", "loglevel=4"])),
+            vec!["loglevel is given 2 times with different values, the last one (4) wins"]
+        );
+        // Meant to be repeated
+        assert!(check(&params(&["console=tty0", "console=ttyS0,115200n8"])).is_empty());
+        assert!(check(&params(&["hugepagesz=2M", "hugepages=512", "hugepagesz=1G", "hugepages=4"])).is_empty());
+    }
+
+    #[test]
+    fn conflicts() {
+        assert_eq!(check(&params(&["ro", "quiet", "rw"])), vec!["ro and rw contradict each other"]);
+        assert_eq!(
+            check(&params(&["mitigations=off", "spectre_v2=on"])),
+            vec!["mitigations=off and spectre_v2=on contradict each other"]
+        );
+        assert!(check(&params(&["mitigations=auto", "spectre_v2=on"])).is_empty());
+        // Only the last value counts
+        assert_eq!(
+            check(&params(&["selinux=0", "selinux=1", "enforcing=1"])),
+            vec!["selinux is given 2 times with different values, the last one (1) wins"]
+        );
+    }
+
+    #[test]
+    fn init_arguments() {
+        // Everything after `--` goes to init
+        assert!(check(&params(&["quiet", "--", "debug", "single", "frobnicate", "quiet"])).is_empty());
+    }
+}
@@ -1,14 +1,30 @@
-mod colorprint;
 mod grub;
+mod grub_script;
+mod grub_locate;
 mod grub_config;
+mod diff;
+mod default_grub;
+mod grub_settings;
+mod kernel_params;
+mod profiles;
 mod kernel_info;
 mod kernel_cleanup;
 mod custom_names;
+mod custom_entries;
+mod boot_once;
 mod backup_manager;
 mod grub_validate;
 mod boot_time;
+mod paths;
+mod safe_write;
+mod grubenv;
+mod trial;
+mod export;
+mod grub_mkconfig;
+mod cli;
 
 use grub::{Entry, EntryType, load_grub, get_entry, try_get_entry};
+use paths::Paths;
 use ratatui::{
     backend::CrosstermBackend,
     crossterm::{
@@ -19,16 +35,51 @@ use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
     Frame, Terminal,
 };
+use std::collections::HashMap;
 use std::io::{self, stdout};
+use std::path::PathBuf;
 
 fn main() -> io::Result<()> {
-    let entry = match load_grub() {
+    let args = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("{}", cli::USAGE);
+            std::process::exit(cli::EXIT_USAGE);
+        }
+    };
+
+    let mut paths = args.paths;
+    let location = grub_locate::locate_grub_cfg(&paths);
+
+    // Commands report a missing grub.cfg themselves, not all of them need it
+    if !args.command.is_empty() || args.json {
+        if let Some(location) = location {
+            paths.set_grub_cfg(location.path);
+        }
+        std::process::exit(cli::run(&paths, &args.command, args.json));
+    }
+
+    let location = match location {
+        Some(location) => location,
+        None => {
+            eprintln!("LoadGrub Failed. No grub.cfg found, tried:");
+            for candidate in grub_locate::candidates(&paths) {
+                eprintln!("  {}", paths.display(&candidate));
+            }
+            return Ok(());
+        }
+    };
+    let grub_cfg_source = location.describe(&paths);
+    paths.set_grub_cfg(location.path);
+
+    let entry = match load_grub(&paths) {
         Some(e) => e,
         None => {
-            eprintln!("LoadGrub Failed. \"/boot/grub/grub.cfg\" Not Found.");
+            eprintln!("LoadGrub Failed. \"{}\" could not be read.", grub_cfg_source);
             return Ok(());
         }
     };
@@ -40,7 +91,7 @@ fn main() -> io::Result<()> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new(entry);
+    let mut app = App::new(paths, entry, grub_cfg_source);
     let result = app.run(&mut terminal);
 
     // Restore terminal
@@ -55,14 +106,17 @@ fn main() -> io::Result<()> {
     result
 }
 
+// Unchanged lines shown around each change in a preview
+const PREVIEW_CONTEXT: usize = 3;
+
 struct App {
+    paths: Paths,
     entry: Entry,
+    grub_cfg_source: String,
     state: AppState,
     state_stack: Vec<AppState>,
-    search_query: String,
-    search_results: Vec<Vec<usize>>,
-    search_selected: usize,
-    bcolors: colorprint::Bcolors,
+    // File edited settings are saved to, toggled with `t`
+    save_target: grub_config::SaveTarget,
 }
 
 #[derive(Clone)]
@@ -70,16 +124,11 @@ enum AppState {
     MainMenu {
         selected: usize,
     },
-    SearchMode {
-        selected: usize,
-    },
     SelectBootEntry {
         path: Vec<usize>,
         selected: usize,
-        action: Option<SelectBootEntryAction>,
     },
     SelectBootEntrySearch {
-        path: Vec<usize>,
         query: String,
         results: Vec<Vec<usize>>,
         selected: usize,
@@ -105,6 +154,20 @@ enum AppState {
         path: Vec<usize>,
         entry_name: String,
     },
+    ConfirmBootOnce {
+        path: Vec<usize>,
+        entry_name: String,
+    },
+    ConfirmApply {
+        // What was saved, shown above the prompt
+        changes: Vec<String>,
+    },
+    // Extra kernel parameters for a one-time boot
+    BootOnceParams {
+        path: Vec<usize>,
+        entry_name: String,
+        input_buffer: String,
+    },
     Message {
         title: String,
         content: Vec<String>,
@@ -126,6 +189,8 @@ enum AppState {
     BackupManager {
         backups: Vec<backup_manager::BackupInfo>,
         selected: usize,
+        // Backup to compare the selected one with (m), else the current configuration
+        marked: Option<PathBuf>,
     },
     ValidateGrub {
         result: Option<grub_validate::ValidationResult>,
@@ -136,10 +201,71 @@ enum AppState {
     },
     EditAllGrubParams {
         params: Vec<(String, String)>,
+        // File that set each parameter
+        sources: HashMap<String, PathBuf>,
         selected: usize,
         input_mode: GrubConfigInputMode,
         input_buffer: String,
     },
+    ParameterProfiles {
+        profiles: profiles::Profiles,
+        // 0 is the default command line (no profile)
+        selected: usize,
+    },
+    CustomEntries {
+        entries: Vec<Entry>,
+        selected: usize,
+        input_mode: CustomEntryInput,
+        input_buffer: String,
+    },
+    // Diff of the files a write changes, done once confirmed
+    PreviewChanges {
+        files: Vec<(PathBuf, Vec<diff::DiffLine>)>,
+        scroll: usize,
+        action: PendingAction,
+    },
+    // Read-only diff between two versions of the configuration
+    BackupDiff {
+        title: String,
+        summary: Vec<String>,
+        files: Vec<(PathBuf, Vec<diff::DiffLine>)>,
+        scroll: usize,
+    },
+    // Choose the parts of a backup to restore
+    RestoreComponents {
+        backup: backup_manager::BackupInfo,
+        components: Vec<(backup_manager::Component, bool)>,
+        selected: usize,
+    },
+}
+
+// What a confirmed PreviewChanges does
+#[derive(Clone)]
+enum PendingAction {
+    // Write the settings file, then offer to regenerate grub.cfg
+    Save {
+        write: grub_config::PendingWrite,
+        // Recorded in the backup taken before writing
+        action: String,
+        changes: Vec<String>,
+    },
+    // Write one setting edited in the list of all settings and go back to it
+    SaveParam {
+        write: grub_config::PendingWrite,
+        key: String,
+    },
+    RestoreBackup {
+        backup: PathBuf,
+        components: Vec<backup_manager::Component>,
+    },
+    SwitchProfile {
+        profiles: profiles::Profiles,
+        name: Option<String>,
+    },
+    SavedEntry {
+        saved_entry: String,
+        entry_name: String,
+    },
 }
 
 #[derive(Clone)]
@@ -150,18 +276,19 @@ enum MessageType {
 }
 
 #[derive(PartialEq, Clone)]
-enum SelectBootEntryAction {
-    ViewKernelInfo,
-    Rename,
+enum GrubConfigInputMode {
+    None,
+    EditValue,
+    SelectTimeoutStyle,
+    // Choosing one of a setting's allowed values with Left/Right
+    SelectOption,
 }
 
 #[derive(PartialEq, Clone)]
-enum GrubConfigInputMode {
+enum CustomEntryInput {
     None,
-    EditTimeout,
-    SelectTimeoutStyle,
-    EditLinuxParams,
-    EditLinuxDefaultParams,
+    EditParams,
+    Rename,
 }
 
 #[derive(PartialEq)]
@@ -175,15 +302,20 @@ enum InputMode {
 }
 
 impl App {
-    fn new(entry: Entry) -> Self {
+    fn new(paths: Paths, entry: Entry, grub_cfg_source: String) -> Self {
+        // Keep using grublist.cfg once settings have been saved there
+        let save_target = if paths.grublist_fragment().exists() {
+            grub_config::SaveTarget::Fragment
+        } else {
+            grub_config::SaveTarget::Main
+        };
         App {
+            paths,
             entry,
+            grub_cfg_source,
             state: AppState::MainMenu { selected: 0 },
             state_stack: Vec::new(),
-            search_query: String::new(),
-            search_results: Vec::new(),
-            search_selected: 0,
-            bcolors: colorprint::Bcolors::new(),
+            save_target,
         }
     }
 
@@ -226,9 +358,8 @@ impl App {
                 // Extract state to avoid borrowing conflicts
                 let state_snapshot = match &self.state {
                     AppState::MainMenu { selected } => (0, *selected),
-                    AppState::SearchMode { selected } => (1, *selected),
-                    AppState::SelectBootEntry { path, selected, .. } => (2, *selected),
-                    AppState::SelectBootEntrySearch { path: _, query: _, results: _, selected } => (3, *selected),
+                    AppState::SelectBootEntry { selected, .. } => (2, *selected),
+                    AppState::SelectBootEntrySearch { selected, .. } => (3, *selected),
                     AppState::ConfigureGrub { selected, .. } => (4, *selected),
                     AppState::EditParameterList { selected, .. } => (5, *selected),
                     AppState::ViewDefaultEntry => (6, 0),
@@ -241,6 +372,14 @@ impl App {
                     AppState::ValidateGrub { .. } => (14, 0),
                     AppState::BootTimeStats { selected, .. } => (15, *selected),
                     AppState::EditAllGrubParams { selected, .. } => (16, *selected),
+                    AppState::ConfirmBootOnce { .. } => (17, 0),
+                    AppState::ConfirmApply { .. } => (18, 0),
+                    AppState::ParameterProfiles { selected, .. } => (19, *selected),
+                    AppState::CustomEntries { selected, .. } => (20, *selected),
+                    AppState::BootOnceParams { .. } => (21, 0),
+                    AppState::PreviewChanges { .. } => (22, 0),
+                    AppState::RestoreComponents { selected, .. } => (23, *selected),
+                    AppState::BackupDiff { .. } => (24, 0),
                 };
 
                 match state_snapshot.0 {
@@ -250,7 +389,7 @@ impl App {
                             KeyCode::Up => {
                                 if let AppState::MainMenu { selected } = &mut self.state {
                                     if *selected == 0 {
-                                        *selected = 5;
+                                        *selected = 7;
                                     } else {
                                         *selected -= 1;
                                     }
@@ -258,7 +397,7 @@ impl App {
                             }
                             KeyCode::Down => {
                                 if let AppState::MainMenu { selected } = &mut self.state {
-                                    *selected = (*selected + 1) % 6;
+                                    *selected = (*selected + 1) % 8;
                                 }
                             }
                             KeyCode::Enter | KeyCode::Right => {
@@ -268,56 +407,6 @@ impl App {
                             _ => {}
                         }
                     }
-                    1 => { // SearchMode
-                        match key.code {
-                            KeyCode::Esc => {
-                                self.state = AppState::MainMenu { selected: 0 };
-                                self.search_query.clear();
-                                self.search_results.clear();
-                            }
-                            KeyCode::Up => {
-                                if let AppState::SearchMode { selected } = &mut self.state {
-                                    if !self.search_results.is_empty() {
-                                        if *selected == 0 {
-                                            *selected = self.search_results.len() - 1;
-                                        } else {
-                                            *selected -= 1;
-                                        }
-                                    }
-                                }
-                            }
-                            KeyCode::Down => {
-                                if let AppState::SearchMode { selected } = &mut self.state {
-                                    if !self.search_results.is_empty() {
-                                        *selected = (*selected + 1) % self.search_results.len();
-                                    }
-                                }
-                            }
-                            KeyCode::Enter => {
-                                if !self.search_results.is_empty() && state_snapshot.1 < self.search_results.len() {
-                                    self.state = AppState::MainMenu { selected: 0 };
-                                    self.search_query.clear();
-                                    self.search_results.clear();
-                                }
-                            }
-                            KeyCode::Backspace => {
-                                self.search_query.pop();
-                                self.update_search_results();
-                                if let AppState::SearchMode { selected } = &mut self.state {
-                                    *selected = 0;
-                                }
-                            }
-                            _ => {
-                                if let Some(c) = Self::key_to_char(&key) {
-                                    self.search_query.push(c);
-                                    self.update_search_results();
-                                    if let AppState::SearchMode { selected } = &mut self.state {
-                                        *selected = 0;
-                                    }
-                                }
-                            }
-                        }
-                    }
                     2 => { // SelectBootEntry
                         match key.code {
                             KeyCode::Esc => {
@@ -355,7 +444,7 @@ impl App {
                             }
                             KeyCode::Enter | KeyCode::Right => {
                                 // Enter/Right: navigate into submenu only
-                                if let AppState::SelectBootEntry { path, selected, .. } = &self.state {
+                                if let AppState::SelectBootEntry { path, .. } = &self.state {
                                     let entry_ref = if path.is_empty() {
                                         &self.entry
                                     } else {
@@ -387,7 +476,7 @@ impl App {
                             }
                             KeyCode::Char('i') | KeyCode::Char('I') => {
                                 // View kernel info
-                                if let AppState::SelectBootEntry { path, selected, .. } = &self.state {
+                                if let AppState::SelectBootEntry { path, .. } = &self.state {
                                     let entry_ref = if path.is_empty() {
                                         &self.entry
                                     } else {
@@ -398,8 +487,7 @@ impl App {
                                         if child.entry_type == EntryType::MenuEntry {
                                             let mut result_path = path.clone();
                                             result_path.push(state_snapshot.1);
-                                            let entry_name = child.name.clone();
-                                            let kernel_info = kernel_info::get_kernel_version_from_entry(&entry_name);
+                                            let kernel_info = kernel_info::get_kernel_version_from_entry(&self.paths, child);
                                             self.navigate_to(AppState::ViewKernelInfo {
                                                 path: result_path,
                                                 kernel_info,
@@ -410,7 +498,7 @@ impl App {
                             }
                             KeyCode::Char('y') | KeyCode::Char('Y') => {
                                 // Confirm set default boot entry
-                                if let AppState::SelectBootEntry { path, selected, action } = &self.state {
+                                if let AppState::SelectBootEntry { path, .. } = &self.state {
                                     let entry_ref = if path.is_empty() {
                                         &self.entry
                                     } else {
@@ -422,21 +510,17 @@ impl App {
                                             let mut result_path = path.clone();
                                             result_path.push(state_snapshot.1);
                                             let entry_name = child.name.clone();
-                                            
-                                            // Only allow if action is None (set default mode)
-                                            if action.is_none() {
-                                                self.navigate_to(AppState::ConfirmSetDefaultEntry {
-                                                    path: result_path,
-                                                    entry_name,
-                                                }, true);
-                                            }
+                                            self.navigate_to(AppState::ConfirmSetDefaultEntry {
+                                                path: result_path,
+                                                entry_name,
+                                            }, true);
                                         }
                                     }
                                 }
                             }
                             KeyCode::Char('e') | KeyCode::Char('E') => {
                                 // Edit boot entry name
-                                if let AppState::SelectBootEntry { path, selected, action } = &self.state {
+                                if let AppState::SelectBootEntry { path, .. } = &self.state {
                                     let entry_ref = if path.is_empty() {
                                         &self.entry
                                     } else {
@@ -448,29 +532,80 @@ impl App {
                                             let mut result_path = path.clone();
                                             result_path.push(state_snapshot.1);
                                             let entry_name = child.name.clone();
-                                            
-                                            // Only allow if action is None (set default mode)
-                                            if action.is_none() {
-                                                let custom_names = custom_names::CustomNames::load();
-                                                let current_name = custom_names.get_custom_name(&result_path)
-                                                    .cloned()
-                                                    .unwrap_or_else(|| entry_name.clone());
-                                                self.navigate_to(AppState::RenameBootEntry {
-                                                    path: result_path,
-                                                    original_name: entry_name,
-                                                    input_buffer: current_name,
+                                            let custom_names = custom_names::CustomNames::load(&self.paths);
+                                            let current_name = custom_names.get_custom_name(&result_path)
+                                                .cloned()
+                                                .unwrap_or_else(|| entry_name.clone());
+                                            self.navigate_to(AppState::RenameBootEntry {
+                                                path: result_path,
+                                                original_name: entry_name,
+                                                input_buffer: current_name,
+                                            }, true);
+                                        }
+                                    }
+                                }
+                            }
+                            KeyCode::Char('b') | KeyCode::Char('B') => {
+                                // Boot entry once at next restart
+                                if let AppState::SelectBootEntry { path, .. } = &self.state {
+                                    let entry_ref = if path.is_empty() {
+                                        &self.entry
+                                    } else {
+                                        get_entry(&self.entry, path)
+                                    };
+                                    if state_snapshot.1 < entry_ref.children.len() {
+                                        let child = &entry_ref.children[state_snapshot.1];
+                                        if child.entry_type == EntryType::MenuEntry {
+                                            let mut result_path = path.clone();
+                                            result_path.push(state_snapshot.1);
+                                            let entry_name = child.name.clone();
+                                            self.navigate_to(AppState::ConfirmBootOnce {
+                                                path: result_path,
+                                                entry_name,
+                                            }, true);
+                                        }
+                                    }
+                                }
+                            }
+                            KeyCode::Char('c') | KeyCode::Char('C') => {
+                                // Copy the entry to give it its own kernel parameters
+                                if let AppState::SelectBootEntry { path, .. } = &self.state {
+                                    let entry_ref = if path.is_empty() {
+                                        &self.entry
+                                    } else {
+                                        get_entry(&self.entry, path)
+                                    };
+                                    if state_snapshot.1 < entry_ref.children.len() {
+                                        let child = &entry_ref.children[state_snapshot.1];
+                                        let mut entries = custom_entries::load(&self.paths);
+                                        match custom_entries::copy_entry(child, &entries) {
+                                            Ok(copy) => {
+                                                let input_buffer = grub_config::join_parameters(&custom_entries::kernel_params(&copy));
+                                                entries.push(copy);
+                                                self.navigate_to(AppState::CustomEntries {
+                                                    selected: entries.len() - 1,
+                                                    entries,
+                                                    input_mode: CustomEntryInput::EditParams,
+                                                    input_buffer,
                                                 }, true);
                                             }
+                                            Err(e) => {
+                                                self.state = AppState::Message {
+                                                    title: "Error".to_string(),
+                                                    content: vec![format!("Cannot copy entry: {}", e)],
+                                                    message_type: MessageType::Error,
+                                                };
+                                            }
                                         }
                                     }
                                 }
                             }
                             _ => {
                                 if let Some(c) = Self::key_to_char(&key) {
-                                    if let AppState::SelectBootEntry { path, action, .. } = &self.state {
-                                        // Don't start search for i, y, e keys
-                                        if c != 'i' && c != 'I' && c != 'y' && c != 'Y' && c != 'e' && c != 'E' {
-                                            self.start_boot_entry_search(c, path.clone());
+                                    if let AppState::SelectBootEntry { .. } = &self.state {
+                                        // Don't start search for i, y, e, b, c keys
+                                        if !matches!(c, 'i' | 'I' | 'y' | 'Y' | 'e' | 'E' | 'b' | 'B' | 'c' | 'C') {
+                                            self.start_boot_entry_search(c);
                                         }
                                     }
                                 }
@@ -599,7 +734,7 @@ impl App {
                                                 }
                                                 2 => {
                                                     // Edit timeout
-                                                    *input_mode = GrubConfigInputMode::EditTimeout;
+                                                    *input_mode = GrubConfigInputMode::EditValue;
                                                     *input_buffer = timeout.clone();
                                                 }
                                                 3 => {
@@ -609,19 +744,9 @@ impl App {
                                                 }
                                                 4 => {
                                                     // View/Edit All Parameters
-                                                    match grub_config::GrubConfig::load() {
-                                                        Ok(config) => {
-                                                            let mut params: Vec<(String, String)> = config.get_all_params()
-                                                                .iter()
-                                                                .map(|(k, v)| (k.clone(), v.clone()))
-                                                                .collect();
-                                                            params.sort_by(|a, b| a.0.cmp(&b.0));
-                                                            self.navigate_to(AppState::EditAllGrubParams {
-                                                                params,
-                                                                selected: 0,
-                                                                input_mode: GrubConfigInputMode::None,
-                                                                input_buffer: String::new(),
-                                                            }, true);
+                                                    match self.all_params_state(None) {
+                                                        Ok(state) => {
+                                                            self.navigate_to(state, true);
                                                         }
                                                         Err(e) => {
                                                             self.state = AppState::Message {
@@ -634,7 +759,7 @@ impl App {
                                                 }
                                                 5 => {
                                                     // Validate GRUB Config
-                                                    match grub_validate::validate_grub_config() {
+                                                    match grub_validate::validate_grub_config(&self.paths) {
                                                         Ok(result) => {
                                                             self.navigate_to(AppState::ValidateGrub {
                                                                 result: Some(result),
@@ -651,7 +776,7 @@ impl App {
                                                 }
                                                 6 => {
                                                     // Save
-                                                    let mut config = match grub_config::GrubConfig::load() {
+                                                    let mut config = match grub_config::GrubConfig::load(&self.paths) {
                                                         Ok(c) => c,
                                                         Err(e) => {
                                                             self.state = AppState::Message {
@@ -662,22 +787,24 @@ impl App {
                                                             continue;
                                                         }
                                                     };
-                                                    config.grub_cmdline_linux = grub_config::join_parameters(linux_params);
-                                                    config.grub_cmdline_linux_default = grub_config::join_parameters(linux_default_params);
-                                                    config.grub_timeout = timeout.clone();
-                                                    config.grub_timeout_style = timeout_style.clone();
+                                                    config.set("GRUB_CMDLINE_LINUX", grub_config::join_parameters(linux_params));
+                                                    config.set("GRUB_CMDLINE_LINUX_DEFAULT", grub_config::join_parameters(linux_default_params));
+                                                    config.set("GRUB_TIMEOUT", timeout.clone());
+                                                    config.set("GRUB_TIMEOUT_STYLE", timeout_style.clone());
                                                     
-                                                    match config.save() {
-                                                        Ok(_) => {
+                                                    match config.prepare_save(self.save_target) {
+                                                        Ok(Some(write)) => {
+                                                            self.preview(write.path.clone(), write.diff(), PendingAction::Save {
+                                                                write,
+                                                                action: "configure GRUB settings".to_string(),
+                                                                changes: vec!["Configuration saved successfully!".to_string()],
+                                                            });
+                                                        }
+                                                        Ok(None) => {
                                                             self.state = AppState::Message {
-                                                                title: "Success".to_string(),
-                                                                content: vec![
-                                                                    "Configuration saved successfully!".to_string(),
-                                                                    "".to_string(),
-                                                                    "Please run:".to_string(),
-                                                                    "  sudo update-grub".to_string(),
-                                                                ],
-                                                                message_type: MessageType::Success,
+                                                                title: "No Changes".to_string(),
+                                                                content: vec!["The configuration is unchanged, nothing was saved.".to_string()],
+                                                                message_type: MessageType::Info,
                                                             };
                                                         }
                                                         Err(e) => {
@@ -696,7 +823,12 @@ impl App {
                                                 _ => {}
                                             }
                                         }
-                                        GrubConfigInputMode::EditTimeout => {
+                                        GrubConfigInputMode::EditValue
+                                            if !input_buffer.trim().is_empty()
+                                                && grub_settings::validate("GRUB_TIMEOUT", input_buffer.trim()).is_err() => {
+                                            // Keep editing, the item shows what is accepted
+                                        }
+                                        GrubConfigInputMode::EditValue => {
                                             if !input_buffer.trim().is_empty() {
                                                 *timeout = input_buffer.trim().to_string();
                                             }
@@ -712,7 +844,7 @@ impl App {
                                         }
                                         GrubConfigInputMode::SelectTimeoutStyle => {
                                             let style = input_buffer.trim().to_lowercase();
-                                            if style == "menu" || style == "hidden" || style == "countdown" {
+                                            if grub_settings::validate("GRUB_TIMEOUT_STYLE", &style).is_ok() {
                                                 *timeout_style = style;
                                             }
                                             *input_mode = GrubConfigInputMode::None;
@@ -729,6 +861,22 @@ impl App {
                                     }
                                 }
                             }
+                            KeyCode::Char('t') if matches!(&self.state, AppState::ConfigureGrub { input_mode: GrubConfigInputMode::None, .. }) => {
+                                self.save_target = self.save_target.toggle();
+                            }
+                            KeyCode::Char('p') if matches!(&self.state, AppState::ConfigureGrub { input_mode: GrubConfigInputMode::None, .. }) => {
+                                match profiles::Profiles::load(&self.paths) {
+                                    Ok(profiles) => self.navigate_to(AppState::ParameterProfiles {
+                                        profiles,
+                                        selected: 0,
+                                    }, true),
+                                    Err(e) => self.navigate_to(AppState::Message {
+                                        title: "Error".to_string(),
+                                        content: vec![e],
+                                        message_type: MessageType::Error,
+                                    }, true),
+                                }
+                            }
                             _ => {
                                 if let AppState::ConfigureGrub { input_mode, input_buffer, .. } = &mut self.state {
                                     if *input_mode != GrubConfigInputMode::None {
@@ -755,8 +903,8 @@ impl App {
                                         let params = params.clone();
                                         let is_linux = title == "Edit GRUB_CMDLINE_LINUX";
                                         
-                                        match grub_config::GrubConfig::load() {
-                                            Ok(mut config) => {
+                                        match grub_config::GrubConfig::load(&self.paths) {
+                                            Ok(config) => {
                                                 let linux_params = grub_config::parse_parameters(&config.grub_cmdline_linux);
                                                 let linux_default_params = grub_config::parse_parameters(&config.grub_cmdline_linux_default);
                                                 
@@ -849,8 +997,8 @@ impl App {
                                                         let params = params.clone();
                                                         let is_linux = title == "Edit GRUB_CMDLINE_LINUX";
                                                         
-                                                        match grub_config::GrubConfig::load() {
-                                                            Ok(mut config) => {
+                                                        match grub_config::GrubConfig::load(&self.paths) {
+                                                            Ok(config) => {
                                                                 let linux_params = grub_config::parse_parameters(&config.grub_cmdline_linux);
                                                                 let linux_default_params = grub_config::parse_parameters(&config.grub_cmdline_linux_default);
                                                                 
@@ -888,7 +1036,7 @@ impl App {
                                                     if let AppState::EditParameterList { title, .. } = &self.state {
                                                         let is_linux = title == "Edit GRUB_CMDLINE_LINUX";
                                                         
-                                                        match grub_config::GrubConfig::load() {
+                                                        match grub_config::GrubConfig::load(&self.paths) {
                                                             Ok(config) => {
                                                                 let linux_params = grub_config::parse_parameters(&config.grub_cmdline_linux);
                                                                 let linux_default_params = grub_config::parse_parameters(&config.grub_cmdline_linux_default);
@@ -915,8 +1063,8 @@ impl App {
                                                         let params = params.clone();
                                                         let is_linux = title == "Edit GRUB_CMDLINE_LINUX";
                                                         
-                                                        match grub_config::GrubConfig::load() {
-                                                            Ok(mut config) => {
+                                                        match grub_config::GrubConfig::load(&self.paths) {
+                                                            Ok(config) => {
                                                                 let linux_params = grub_config::parse_parameters(&config.grub_cmdline_linux);
                                                                 let linux_default_params = grub_config::parse_parameters(&config.grub_cmdline_linux_default);
                                                                 
@@ -953,7 +1101,7 @@ impl App {
                                                 if let AppState::EditParameterList { title, .. } = &self.state {
                                                     let is_linux = title == "Edit GRUB_CMDLINE_LINUX";
                                                     
-                                                    match grub_config::GrubConfig::load() {
+                                                    match grub_config::GrubConfig::load(&self.paths) {
                                                         Ok(config) => {
                                                             let linux_params = grub_config::parse_parameters(&config.grub_cmdline_linux);
                                                             let linux_default_params = grub_config::parse_parameters(&config.grub_cmdline_linux_default);
@@ -1014,7 +1162,9 @@ impl App {
                                             } else {
                                                 grub_config::format_parameter(&name, Some(input_buffer.trim()))
                                             };
-                                            params.push(param);
+                                            // Before `--`, parameters after it go to init
+                                            let at = grub_config::kernel_parameter_count(params);
+                                            params.insert(at, param);
                                             *input_mode = InputMode::None;
                                             *input_buffer = String::new();
                                         }
@@ -1037,6 +1187,24 @@ impl App {
                                     }
                                 }
                             }
+                            KeyCode::Tab => {
+                                // Complete a parameter name from the known ones
+                                if let AppState::EditParameterList { input_mode: InputMode::AddName, input_buffer, .. } = &mut self.state {
+                                    let names: Vec<&str> = kernel_params::complete(input_buffer).iter().map(|p| p.name).collect();
+                                    if let Some(first) = names.first() {
+                                        // Extend to the longest prefix shared by all matches
+                                        let mut common = first.to_string();
+                                        for name in &names[1..] {
+                                            while !name.starts_with(&common) {
+                                                common.pop();
+                                            }
+                                        }
+                                        if common.len() > input_buffer.len() {
+                                            *input_buffer = common;
+                                        }
+                                    }
+                                }
+                            }
                             _ => {
                                 if let Some(c) = Self::key_to_char(&key) {
                                     if let AppState::EditParameterList { input_mode, input_buffer, .. } = &mut self.state {
@@ -1067,22 +1235,29 @@ impl App {
                                         .map(|x| x.to_string())
                                         .collect::<Vec<_>>()
                                         .join(">");
+                                    // Prefer the stable menuentry ID path, numeric paths shift when kernels change
+                                    let default_value = grub::entry_id_path(&self.entry, path).unwrap_or(p_str);
                                     
-                                    match grub_config::GrubConfig::load() {
+                                    match grub_config::GrubConfig::load(&self.paths) {
                                         Ok(mut config) => {
-                                            config.grub_default = format!("\"{}\"", p_str);
+                                            config.set("GRUB_DEFAULT", default_value.clone());
                                             
-                                            match config.save() {
-                                                Ok(_) => {
-                                                    self.state = AppState::Message {
-                                                        title: "Success".to_string(),
-                                                        content: vec![
+                                            match config.prepare_save(self.save_target) {
+                                                Ok(Some(write)) => {
+                                                    self.preview(write.path.clone(), write.diff(), PendingAction::Save {
+                                                        write,
+                                                        action: format!("set default entry {}", default_value),
+                                                        changes: vec![
                                                             "Default boot entry set successfully!".to_string(),
-                                                            "".to_string(),
-                                                            "Please run:".to_string(),
-                                                            "  sudo update-grub".to_string(),
+                                                            format!("GRUB_DEFAULT=\"{}\"", default_value),
                                                         ],
-                                                        message_type: MessageType::Success,
+                                                    });
+                                                }
+                                                Ok(None) => {
+                                                    self.state = AppState::Message {
+                                                        title: "No Changes".to_string(),
+                                                        content: vec![format!("GRUB_DEFAULT is already \"{}\".", default_value)],
+                                                        message_type: MessageType::Info,
                                                     };
                                                 }
                                                 Err(e) => {
@@ -1108,12 +1283,147 @@ impl App {
                                     }
                                 }
                             }
+                            KeyCode::Char('s') | KeyCode::Char('S') => {
+                                if let AppState::ConfirmSetDefaultEntry { path, entry_name } = &self.state {
+                                    let p_str: String = path.iter()
+                                        .map(|x| x.to_string())
+                                        .collect::<Vec<_>>()
+                                        .join(">");
+                                    let saved_entry = grub::entry_id_path(&self.entry, path).unwrap_or(p_str);
+                                    let entry_name = entry_name.clone();
+
+                                    // GRUB_DEFAULT may have to change to saved, show that first
+                                    let result = grub_config::GrubConfig::load(&self.paths).and_then(|mut config| {
+                                        if grubenv::is_saved(&config.grub_default) {
+                                            return Ok(None);
+                                        }
+                                        config.set("GRUB_DEFAULT", "saved".to_string());
+                                        config.prepare_save(self.save_target)
+                                    });
+                                    match result {
+                                        Ok(Some(write)) => {
+                                            self.preview(write.path.clone(), write.diff(), PendingAction::SavedEntry { saved_entry, entry_name });
+                                        }
+                                        Ok(None) => self.set_saved_entry(&saved_entry, &entry_name),
+                                        Err(e) => {
+                                            self.state = AppState::Message {
+                                                title: "Error".to_string(),
+                                                content: vec![format!("Error saving configuration: {}", e)],
+                                                message_type: MessageType::Error,
+                                            };
+                                        }
+                                    }
+                                }
+                            }
                             KeyCode::Char('n') | KeyCode::Char('N') => {
                                 self.navigate_back();
                             }
                             _ => {}
                         }
                     }
+                    17 => { // ConfirmBootOnce
+                        match key.code {
+                            KeyCode::Esc | KeyCode::Char('n') | KeyCode::Char('N') => {
+                                self.navigate_back();
+                            }
+                            KeyCode::Char('y') | KeyCode::Char('Y') => {
+                                if let AppState::ConfirmBootOnce { path, entry_name } = &self.state {
+                                    let p_str: String = path.iter()
+                                        .map(|x| x.to_string())
+                                        .collect::<Vec<_>>()
+                                        .join(">");
+                                    let next_entry = grub::entry_id_path(&self.entry, path).unwrap_or(p_str);
+                                    
+                                    match grubenv::set_next_entry(&self.paths, &next_entry) {
+                                        Ok(_) => {
+                                            self.state = AppState::Message {
+                                                title: "Success".to_string(),
+                                                content: vec![
+                                                    format!("'{}' will be booted once at the next restart.", entry_name),
+                                                    "".to_string(),
+                                                    format!("next_entry={}", next_entry),
+                                                    "No update-grub needed, later boots use the default entry again.".to_string(),
+                                                ],
+                                                message_type: MessageType::Success,
+                                            };
+                                        }
+                                        Err(e) => {
+                                            self.state = AppState::Message {
+                                                title: "Error".to_string(),
+                                                content: vec![format!("Error writing grubenv: {}", e)],
+                                                message_type: MessageType::Error,
+                                            };
+                                        }
+                                    }
+                                }
+                            }
+                            KeyCode::Char('p') | KeyCode::Char('P') => {
+                                if let AppState::ConfirmBootOnce { path, entry_name } = &self.state {
+                                    let child = get_entry(&self.entry, path);
+                                    if child.boot.linux.is_some() {
+                                        self.state = AppState::BootOnceParams {
+                                            path: path.clone(),
+                                            entry_name: entry_name.clone(),
+                                            input_buffer: String::new(),
+                                        };
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    18 => { // ConfirmApply
+                        match key.code {
+                            KeyCode::Esc | KeyCode::Char('n') | KeyCode::Char('N') => {
+                                if let AppState::ConfirmApply { changes } = &self.state {
+                                    let mut content = changes.clone();
+                                    if !content.is_empty() {
+                                        content.push("".to_string());
+                                    }
+                                    content.push("grub.cfg was not regenerated.".to_string());
+                                    content.push("Use 'Apply Changes' from the main menu when ready.".to_string());
+                                    self.state = AppState::Message {
+                                        title: "Not Applied".to_string(),
+                                        content,
+                                        message_type: MessageType::Info,
+                                    };
+                                }
+                            }
+                            KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
+                                match grub_mkconfig::apply(&self.paths) {
+                                    Ok(result) => {
+                                        // Show the regenerated menu from now on
+                                        if let Some(entry) = load_grub(&self.paths) {
+                                            self.entry = entry;
+                                        }
+                                        let mut content = vec![
+                                            format!("{} regenerated ({} entries).", self.paths.display(&result.grub_cfg), result.entries),
+                                            format!("Command: {}", result.command),
+                                            "".to_string(),
+                                        ];
+                                        // The tail of the generator output lists the kernels it found
+                                        let lines: Vec<&str> = result.output.lines().collect();
+                                        content.extend(lines.iter().skip(lines.len().saturating_sub(12)).map(|l| l.to_string()));
+                                        self.state = AppState::Message {
+                                            title: "Success".to_string(),
+                                            content,
+                                            message_type: MessageType::Success,
+                                        };
+                                    }
+                                    Err(e) => {
+                                        let mut content = vec!["Failed to regenerate grub.cfg:".to_string(), "".to_string()];
+                                        content.extend(e.lines().map(|l| l.to_string()));
+                                        self.state = AppState::Message {
+                                            title: "Error".to_string(),
+                                            content,
+                                            message_type: MessageType::Error,
+                                        };
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
                     9 => { // Message
                         match key.code {
                             KeyCode::Esc | KeyCode::Enter => {
@@ -1158,10 +1468,10 @@ impl App {
                             KeyCode::Enter => {
                                 if let AppState::CleanupKernels { kernels, selected } = &self.state {
                                     if let Some(kernel) = kernels.get(*selected) {
-                                        if !kernel.in_use {
-                                            match kernel_cleanup::delete_kernel_files(&kernel.version) {
+                                        if !kernel.in_use && !kernel.is_default {
+                                            match kernel_cleanup::delete_kernel_files(&self.paths, &kernel.version) {
                                                 Ok(_) => {
-                                                    let mut new_kernels = kernel_cleanup::scan_unused_kernels();
+                                                    let new_kernels = kernel_cleanup::scan_unused_kernels(&self.paths, &self.entry);
                                                     let new_selected = (*selected).min(new_kernels.len().saturating_sub(1));
                                                     self.state = AppState::CleanupKernels {
                                                         kernels: new_kernels,
@@ -1190,9 +1500,9 @@ impl App {
                             }
                             KeyCode::Enter => {
                                 if let AppState::RenameBootEntry { path, input_buffer, .. } = &self.state {
-                                    let mut custom_names = custom_names::CustomNames::load();
+                                    let mut custom_names = custom_names::CustomNames::load(&self.paths);
                                     custom_names.set_custom_name(path, input_buffer.clone());
-                                    if let Err(e) = custom_names.save() {
+                                    if let Err(e) = custom_names.save(&self.paths) {
                                         self.state = AppState::Message {
                                             title: "Error".to_string(),
                                             content: vec![format!("Failed to save custom name: {}", e)],
@@ -1227,7 +1537,7 @@ impl App {
                                 self.navigate_back();
                             }
                             KeyCode::Up => {
-                                if let AppState::BackupManager { backups, selected } = &mut self.state {
+                                if let AppState::BackupManager { backups, selected, .. } = &mut self.state {
                                     if backups.is_empty() {
                                         return Ok(());
                                     }
@@ -1239,7 +1549,7 @@ impl App {
                                 }
                             }
                             KeyCode::Down => {
-                                if let AppState::BackupManager { backups, selected } = &mut self.state {
+                                if let AppState::BackupManager { backups, selected, .. } = &mut self.state {
                                     if backups.is_empty() {
                                         return Ok(());
                                     }
@@ -1247,43 +1557,55 @@ impl App {
                                 }
                             }
                             KeyCode::Enter => {
-                                if let AppState::BackupManager { backups, selected } = &self.state {
+                                if let AppState::BackupManager { backups, selected, .. } = &self.state {
                                     if let Some(backup) = backups.get(*selected) {
-                                        match backup_manager::restore_backup(&backup.path) {
-                                            Ok(_) => {
-                                                self.state = AppState::Message {
-                                                    title: "Success".to_string(),
-                                                    content: vec![
-                                                        "Backup restored successfully!".to_string(),
-                                                        "".to_string(),
-                                                        "Please run:".to_string(),
-                                                        "  sudo update-grub".to_string(),
-                                                    ],
-                                                    message_type: MessageType::Success,
-                                                };
-                                            }
-                                            Err(e) => {
-                                                self.state = AppState::Message {
-                                                    title: "Error".to_string(),
-                                                    content: vec![format!("Failed to restore backup: {}", e)],
-                                                    message_type: MessageType::Error,
-                                                };
-                                            }
+                                        let components = backup.components();
+                                        if components.len() > 1 {
+                                            self.navigate_to(AppState::RestoreComponents {
+                                                backup: backup.clone(),
+                                                components: components.into_iter().map(|c| (c, true)).collect(),
+                                                selected: 0,
+                                            }, true);
+                                        } else {
+                                            let backup_path = backup.path.clone();
+                                            self.preview_restore(backup_path, components);
                                         }
                                     }
                                 }
                             }
+                            KeyCode::Char('m') | KeyCode::Char('M') => {
+                                if let AppState::BackupManager { backups, selected, marked } = &mut self.state {
+                                    if let Some(backup) = backups.get(*selected) {
+                                        *marked = match marked {
+                                            Some(path) if *path == backup.path => None,
+                                            _ => Some(backup.path.clone()),
+                                        };
+                                    }
+                                }
+                            }
+                            KeyCode::Char('v') | KeyCode::Char('V') => {
+                                if let AppState::BackupManager { backups, selected, marked } = &self.state {
+                                    if let Some(backup) = backups.get(*selected) {
+                                        let other = marked.as_ref()
+                                            .filter(|path| **path != backup.path)
+                                            .and_then(|path| backups.iter().find(|b| b.path == *path))
+                                            .cloned();
+                                        let backup = backup.clone();
+                                        self.show_backup_diff(&backup, other.as_ref());
+                                    }
+                                }
+                            }
                             KeyCode::Char('d') | KeyCode::Char('D') => {
-                                if let AppState::BackupManager { backups, selected } = &self.state {
+                                if let AppState::BackupManager { backups, selected, .. } = &self.state {
                                     if let Some(backup) = backups.get(*selected) {
                                         match backup_manager::delete_backup(&backup.path) {
                                             Ok(_) => {
-                                                let new_backups = backup_manager::list_backups();
+                                                let new_backups = backup_manager::list_backups(&self.paths);
                                                 let new_selected = (*selected).min(new_backups.len().saturating_sub(1));
-                                                self.state = AppState::BackupManager {
-                                                    backups: new_backups,
-                                                    selected: new_selected,
-                                                };
+                                                if let AppState::BackupManager { backups, selected, .. } = &mut self.state {
+                                                    *backups = new_backups;
+                                                    *selected = new_selected;
+                                                }
                                             }
                                             Err(e) => {
                                                 self.state = AppState::Message {
@@ -1299,30 +1621,309 @@ impl App {
                             _ => {}
                         }
                     }
-                    14 => { // ValidateGrub
-                        match key.code {
-                            KeyCode::Esc | KeyCode::Enter | KeyCode::Left => {
-                                self.navigate_back();
-                            }
-                            _ => {}
-                        }
-                    }
-                    15 => { // BootTimeStats
+                    19 => { // ParameterProfiles
                         match key.code {
                             KeyCode::Esc | KeyCode::Left => {
                                 self.navigate_back();
                             }
-                            KeyCode::Enter => {
-                                // Enter does nothing in BootTimeStats, just go back
-                                self.navigate_back();
-                            }
                             KeyCode::Up => {
-                                if let AppState::BootTimeStats { entries, selected } = &mut self.state {
-                                    if !entries.is_empty() {
-                                        if *selected == 0 {
-                                            *selected = entries.len() - 1;
-                                        } else {
-                                            *selected -= 1;
+                                if let AppState::ParameterProfiles { profiles, selected } = &mut self.state {
+                                    if *selected == 0 {
+                                        *selected = profiles.profiles.len();
+                                    } else {
+                                        *selected -= 1;
+                                    }
+                                }
+                            }
+                            KeyCode::Down => {
+                                if let AppState::ParameterProfiles { profiles, selected } = &mut self.state {
+                                    *selected = (*selected + 1) % (profiles.profiles.len() + 1);
+                                }
+                            }
+                            KeyCode::Enter | KeyCode::Right => {
+                                if let AppState::ParameterProfiles { profiles, selected } = &mut self.state {
+                                    let name = match *selected {
+                                        0 => None,
+                                        i => profiles.profiles.get(i - 1).map(|p| p.name.clone()),
+                                    };
+                                    if name.is_none() && profiles.active.is_none() {
+                                        // Already on the default command line
+                                        continue;
+                                    }
+                                    let result = grub_config::GrubConfig::load(&self.paths).and_then(|mut config| {
+                                        profiles.stage(&mut config, name.as_deref())?;
+                                        config.prepare_save(self.save_target)
+                                    });
+                                    match result {
+                                        Ok(Some(write)) => {
+                                            let profiles = profiles.clone();
+                                            self.preview(write.path.clone(), write.diff(), PendingAction::SwitchProfile { profiles, name });
+                                        }
+                                        // The file already reads as the profile would make it
+                                        Ok(None) => {
+                                            let profiles = profiles.clone();
+                                            self.switch_profile(profiles, name.as_deref());
+                                        }
+                                        Err(e) => {
+                                            self.state = AppState::Message {
+                                                title: "Error".to_string(),
+                                                content: vec![format!("Error switching profile: {}", e)],
+                                                message_type: MessageType::Error,
+                                            };
+                                        }
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    20 => { // CustomEntries
+                        let editing = matches!(&self.state, AppState::CustomEntries { input_mode, .. } if *input_mode != CustomEntryInput::None);
+                        match key.code {
+                            KeyCode::Esc if editing => {
+                                // Drop the edit, and a copy that was never saved
+                                if let AppState::CustomEntries { entries, selected, input_mode, input_buffer } = &mut self.state {
+                                    *entries = custom_entries::load(&self.paths);
+                                    *selected = (*selected).min(entries.len().saturating_sub(1));
+                                    *input_mode = CustomEntryInput::None;
+                                    input_buffer.clear();
+                                }
+                            }
+                            KeyCode::Esc | KeyCode::Left if !editing => {
+                                self.navigate_back();
+                            }
+                            KeyCode::Up if !editing => {
+                                if let AppState::CustomEntries { entries, selected, .. } = &mut self.state {
+                                    if !entries.is_empty() {
+                                        if *selected == 0 {
+                                            *selected = entries.len() - 1;
+                                        } else {
+                                            *selected -= 1;
+                                        }
+                                    }
+                                }
+                            }
+                            KeyCode::Down if !editing => {
+                                if let AppState::CustomEntries { entries, selected, .. } = &mut self.state {
+                                    if !entries.is_empty() {
+                                        *selected = (*selected + 1) % entries.len();
+                                    }
+                                }
+                            }
+                            KeyCode::Enter if editing => {
+                                let AppState::CustomEntries { entries, selected, input_mode, input_buffer } = &self.state else {
+                                    continue;
+                                };
+                                let mut entries = entries.clone();
+                                let Some(entry) = entries.get_mut(*selected) else {
+                                    continue;
+                                };
+                                let change = match input_mode {
+                                    CustomEntryInput::Rename if input_buffer.trim().is_empty() => continue,
+                                    CustomEntryInput::Rename => {
+                                        entry.name = input_buffer.trim().to_string();
+                                        format!("Custom entry renamed to '{}'", entry.name)
+                                    }
+                                    _ => {
+                                        custom_entries::set_kernel_params(entry, &grub_config::parse_parameters(input_buffer));
+                                        format!("Kernel parameters of '{}': {}", entry.name, input_buffer.trim())
+                                    }
+                                };
+                                self.save_custom_entries(&entries, change);
+                            }
+                            KeyCode::Enter | KeyCode::Right | KeyCode::Char('p') | KeyCode::Char('P') if !editing => {
+                                if let AppState::CustomEntries { entries, selected, input_mode, input_buffer } = &mut self.state {
+                                    if let Some(entry) = entries.get(*selected) {
+                                        *input_buffer = grub_config::join_parameters(&custom_entries::kernel_params(entry));
+                                        *input_mode = CustomEntryInput::EditParams;
+                                    }
+                                }
+                            }
+                            KeyCode::Char('r') | KeyCode::Char('R') if !editing => {
+                                if let AppState::CustomEntries { entries, selected, input_mode, input_buffer } = &mut self.state {
+                                    if let Some(entry) = entries.get(*selected) {
+                                        *input_buffer = entry.name.clone();
+                                        *input_mode = CustomEntryInput::Rename;
+                                    }
+                                }
+                            }
+                            KeyCode::Char('d') | KeyCode::Char('D') if !editing => {
+                                if let AppState::CustomEntries { entries, selected, .. } = &self.state {
+                                    if *selected < entries.len() {
+                                        let mut entries = entries.clone();
+                                        let removed = entries.remove(*selected);
+                                        self.save_custom_entries(&entries, format!("Custom entry '{}' removed", removed.name));
+                                    }
+                                }
+                            }
+                            KeyCode::Backspace if editing => {
+                                if let AppState::CustomEntries { input_buffer, .. } = &mut self.state {
+                                    input_buffer.pop();
+                                }
+                            }
+                            _ if editing => {
+                                if let Some(c) = Self::key_to_char(&key) {
+                                    if let AppState::CustomEntries { input_buffer, .. } = &mut self.state {
+                                        input_buffer.push(c);
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    21 => { // BootOnceParams
+                        match key.code {
+                            KeyCode::Esc => {
+                                self.navigate_back();
+                            }
+                            KeyCode::Enter => {
+                                let AppState::BootOnceParams { path, entry_name, input_buffer } = &self.state else {
+                                    continue;
+                                };
+                                let extra = grub_config::parse_parameters(input_buffer);
+                                if extra.is_empty() {
+                                    continue;
+                                }
+                                let entry = get_entry(&self.entry, path);
+                                let command_line = format!("linux {} {}",
+                                    entry.boot.linux.as_deref().unwrap_or(""),
+                                    grub_config::join_parameters(&boot_once::command_line(entry, &extra)));
+                                let entry_name = entry_name.clone();
+                                self.state = match boot_once::schedule(&self.paths, entry, &extra) {
+                                    Ok(true) => AppState::ConfirmApply {
+                                        changes: vec![
+                                            format!("'{}' will be booted once with: {}", entry_name, grub_config::join_parameters(&extra)),
+                                            format!("Installed {}; grub.cfg has to be regenerated once for the", self.paths.display(&self.paths.boot_once_script())),
+                                            "one-time entry to exist, otherwise the next boot falls back to the first entry.".to_string(),
+                                        ],
+                                    },
+                                    Ok(false) => AppState::Message {
+                                        title: "Success".to_string(),
+                                        content: vec![
+                                            format!("'{}' will be booted once at the next restart with:", entry_name),
+                                            "".to_string(),
+                                            command_line,
+                                            "".to_string(),
+                                            format!("next_entry={}, later boots are unchanged.", boot_once::ENTRY_ID),
+                                        ],
+                                        message_type: MessageType::Success,
+                                    },
+                                    Err(e) => AppState::Message {
+                                        title: "Error".to_string(),
+                                        content: vec![format!("Error setting up one-time boot: {}", e)],
+                                        message_type: MessageType::Error,
+                                    },
+                                };
+                            }
+                            KeyCode::Backspace => {
+                                if let AppState::BootOnceParams { input_buffer, .. } = &mut self.state {
+                                    input_buffer.pop();
+                                }
+                            }
+                            _ => {
+                                if let Some(c) = Self::key_to_char(&key) {
+                                    if let AppState::BootOnceParams { input_buffer, .. } = &mut self.state {
+                                        input_buffer.push(c);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    22 => { // PreviewChanges
+                        match key.code {
+                            KeyCode::Esc | KeyCode::Char('n') | KeyCode::Char('N') => {
+                                self.navigate_back();
+                            }
+                            KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown | KeyCode::Home | KeyCode::End => {
+                                if let AppState::PreviewChanges { files, scroll, .. } = &mut self.state {
+                                    let last = Self::preview_lines(&self.paths, files).len().saturating_sub(1);
+                                    *scroll = Self::scrolled(key.code, *scroll, last);
+                                }
+                            }
+                            KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => {
+                                if let AppState::PreviewChanges { action, .. } = &self.state {
+                                    let action = action.clone();
+                                    self.confirm_pending(action);
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    23 => { // RestoreComponents
+                        match key.code {
+                            KeyCode::Esc | KeyCode::Left => {
+                                self.navigate_back();
+                            }
+                            KeyCode::Up => {
+                                if let AppState::RestoreComponents { components, selected, .. } = &mut self.state {
+                                    *selected = (*selected + components.len() - 1) % components.len();
+                                }
+                            }
+                            KeyCode::Down => {
+                                if let AppState::RestoreComponents { components, selected, .. } = &mut self.state {
+                                    *selected = (*selected + 1) % components.len();
+                                }
+                            }
+                            KeyCode::Char(' ') => {
+                                if let AppState::RestoreComponents { components, selected, .. } = &mut self.state {
+                                    if let Some((_, checked)) = components.get_mut(*selected) {
+                                        *checked = !*checked;
+                                    }
+                                }
+                            }
+                            KeyCode::Enter => {
+                                if let AppState::RestoreComponents { backup, components, .. } = &self.state {
+                                    let backup_path = backup.path.clone();
+                                    let components: Vec<_> = components.iter()
+                                        .filter(|(_, checked)| *checked)
+                                        .map(|(component, _)| *component)
+                                        .collect();
+                                    if !components.is_empty() {
+                                        self.preview_restore(backup_path, components);
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    24 => { // BackupDiff
+                        match key.code {
+                            KeyCode::Esc | KeyCode::Left | KeyCode::Enter => {
+                                self.navigate_back();
+                            }
+                            KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown | KeyCode::Home | KeyCode::End => {
+                                if let AppState::BackupDiff { files, scroll, .. } = &mut self.state {
+                                    let last = Self::preview_lines(&self.paths, files).len().saturating_sub(1);
+                                    *scroll = Self::scrolled(key.code, *scroll, last);
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    14 => { // ValidateGrub
+                        match key.code {
+                            KeyCode::Esc | KeyCode::Enter | KeyCode::Left => {
+                                self.navigate_back();
+                            }
+                            _ => {}
+                        }
+                    }
+                    15 => { // BootTimeStats
+                        match key.code {
+                            KeyCode::Esc | KeyCode::Left => {
+                                self.navigate_back();
+                            }
+                            KeyCode::Enter => {
+                                // Enter does nothing in BootTimeStats, just go back
+                                self.navigate_back();
+                            }
+                            KeyCode::Up => {
+                                if let AppState::BootTimeStats { entries, selected } = &mut self.state {
+                                    if !entries.is_empty() {
+                                        if *selected == 0 {
+                                            *selected = entries.len() - 1;
+                                        } else {
+                                            *selected -= 1;
                                         }
                                     }
                                 }
@@ -1339,94 +1940,94 @@ impl App {
                     }
                     16 => { // EditAllGrubParams
                         match key.code {
+                            KeyCode::Left | KeyCode::Right if matches!(&self.state, AppState::EditAllGrubParams { input_mode: GrubConfigInputMode::SelectOption, .. }) => {
+                                // Cycle through the values the setting accepts
+                                if let AppState::EditAllGrubParams { params, selected, input_buffer, .. } = &mut self.state {
+                                    let options = params.get(*selected)
+                                        .and_then(|(key, _)| grub_settings::lookup(key))
+                                        .and_then(|setting| setting.value_type.options())
+                                        .unwrap_or(&[]);
+                                    if !options.is_empty() {
+                                        let current = options.iter().position(|o| o == input_buffer).unwrap_or(0);
+                                        let next = if key.code == KeyCode::Right {
+                                            (current + 1) % options.len()
+                                        } else {
+                                            (current + options.len() - 1) % options.len()
+                                        };
+                                        *input_buffer = options[next].to_string();
+                                    }
+                                }
+                            }
                             KeyCode::Esc | KeyCode::Left => {
                                 self.navigate_back();
                             }
                             KeyCode::Up => {
                                 if let AppState::EditAllGrubParams { params, selected, input_mode, .. } = &mut self.state {
-                                    if *input_mode == GrubConfigInputMode::None {
-                                        if !params.is_empty() {
-                                            if *selected == 0 {
-                                                *selected = params.len() - 1;
-                                            } else {
-                                                *selected -= 1;
-                                            }
+                                    if *input_mode == GrubConfigInputMode::None && !params.is_empty() {
+                                        if *selected == 0 {
+                                            *selected = params.len() - 1;
+                                        } else {
+                                            *selected -= 1;
                                         }
                                     }
                                 }
                             }
                             KeyCode::Down => {
                                 if let AppState::EditAllGrubParams { params, selected, input_mode, .. } = &mut self.state {
-                                    if *input_mode == GrubConfigInputMode::None {
-                                        if !params.is_empty() {
-                                            *selected = (*selected + 1) % params.len();
-                                        }
+                                    if *input_mode == GrubConfigInputMode::None && !params.is_empty() {
+                                        *selected = (*selected + 1) % params.len();
                                     }
                                 }
                             }
                             KeyCode::Enter => {
-                                if let AppState::EditAllGrubParams { params, selected, input_mode, input_buffer } = &mut self.state {
+                                if let AppState::EditAllGrubParams { params, selected, input_mode, input_buffer, .. } = &mut self.state {
                                     match input_mode {
-                                        GrubConfigInputMode::None => {
-                                            if !params.is_empty() && *selected < params.len() {
-                                                *input_mode = GrubConfigInputMode::EditTimeout;
-                                                *input_buffer = params[*selected].1.clone();
+                                        GrubConfigInputMode::None if !params.is_empty() && *selected < params.len() => {
+                                            let (key, value) = &params[*selected];
+                                            // Settings with a fixed set of values get a selector
+                                            match grub_settings::lookup(key).and_then(|s| s.value_type.options()) {
+                                                Some(options) => {
+                                                    *input_mode = GrubConfigInputMode::SelectOption;
+                                                    *input_buffer = if options.contains(&value.as_str()) {
+                                                        value.clone()
+                                                    } else {
+                                                        options[0].to_string()
+                                                    };
+                                                }
+                                                None => {
+                                                    *input_mode = GrubConfigInputMode::EditValue;
+                                                    *input_buffer = value.clone();
+                                                }
                                             }
                                         }
-                                        GrubConfigInputMode::EditTimeout => {
-                                            // Save the edited value
-                                            if *selected < params.len() {
-                                                params[*selected].1 = input_buffer.clone();
-                                                *input_mode = GrubConfigInputMode::None;
-                                                *input_buffer = String::new();
-                                                
-                                                // Save to config
-                                                match grub_config::GrubConfig::load() {
-                                                    Ok(mut config) => {
-                                                        config.set(&params[*selected].0, input_buffer.clone());
-                                                        if let Err(e) = config.save() {
-                                                            self.state = AppState::Message {
-                                                                title: "Error".to_string(),
-                                                                content: vec![format!("Error saving parameter: {}", e)],
-                                                                message_type: MessageType::Error,
-                                                            };
-                                                        } else {
-                                                            // Reload parameters to reflect changes
-                                                            match grub_config::GrubConfig::load() {
-                                                                Ok(updated_config) => {
-                                                                    let mut updated_params: Vec<(String, String)> = updated_config.get_all_params()
-                                                                        .iter()
-                                                                        .map(|(k, v)| (k.clone(), v.clone()))
-                                                                        .collect();
-                                                                    updated_params.sort_by(|a, b| a.0.cmp(&b.0));
-                                                                    // Find the same parameter index
-                                                                    let new_selected = updated_params.iter()
-                                                                        .position(|(k, _)| k == &params[*selected].0)
-                                                                        .unwrap_or(*selected);
-                                                                    self.state = AppState::EditAllGrubParams {
-                                                                        params: updated_params,
-                                                                        selected: new_selected,
-                                                                        input_mode: GrubConfigInputMode::None,
-                                                                        input_buffer: String::new(),
-                                                                    };
-                                                                }
-                                                                Err(e) => {
-                                                                    self.state = AppState::Message {
-                                                                        title: "Error".to_string(),
-                                                                        content: vec![format!("Error reloading config: {}", e)],
-                                                                        message_type: MessageType::Error,
-                                                                    };
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                    Err(e) => {
-                                                        self.state = AppState::Message {
-                                                            title: "Error".to_string(),
-                                                            content: vec![format!("Error loading config: {}", e)],
-                                                            message_type: MessageType::Error,
-                                                        };
-                                                    }
+                                        GrubConfigInputMode::EditValue | GrubConfigInputMode::SelectOption
+                                            if *selected < params.len() && grub_settings::validate(&params[*selected].0, input_buffer).is_err() => {
+                                            // Keep editing, the error is shown below the list
+                                        }
+                                        GrubConfigInputMode::EditValue | GrubConfigInputMode::SelectOption if *selected < params.len() => {
+                                            // Show the change to the file before saving it
+                                            let key = params[*selected].0.clone();
+                                            let value = input_buffer.clone();
+                                            let result = grub_config::GrubConfig::load(&self.paths).and_then(|mut config| {
+                                                config.set(&key, value.clone());
+                                                config.prepare_save(self.save_target)
+                                            });
+                                            match result {
+                                                Ok(Some(write)) => {
+                                                    self.preview(write.path.clone(), write.diff(), PendingAction::SaveParam { write, key });
+                                                }
+                                                Ok(None) => {
+                                                    // Same value as before, nothing to write
+                                                    params[*selected].1 = value;
+                                                    *input_mode = GrubConfigInputMode::None;
+                                                    *input_buffer = String::new();
+                                                }
+                                                Err(e) => {
+                                                    self.state = AppState::Message {
+                                                        title: "Error".to_string(),
+                                                        content: vec![format!("Error saving parameter: {}", e)],
+                                                        message_type: MessageType::Error,
+                                                    };
                                                 }
                                             }
                                         }
@@ -1434,15 +2035,34 @@ impl App {
                                     }
                                 }
                             }
-                            KeyCode::Char(c) => {
+                            KeyCode::Char('t') if matches!(&self.state, AppState::EditAllGrubParams { input_mode: GrubConfigInputMode::None, .. }) => {
+                                self.save_target = self.save_target.toggle();
+                            }
+                            KeyCode::Char('a') if matches!(&self.state, AppState::EditAllGrubParams { input_mode: GrubConfigInputMode::None, .. }) => {
+                                // Edits are saved one by one, apply them all at once
+                                self.navigate_to(AppState::ConfirmApply {
+                                    changes: Vec::new(),
+                                }, true);
+                            }
+                            KeyCode::Backspace => {
                                 if let AppState::EditAllGrubParams { input_mode, input_buffer, .. } = &mut self.state {
-                                    if *input_mode == GrubConfigInputMode::EditTimeout {
+                                    if *input_mode == GrubConfigInputMode::EditValue {
+                                        input_buffer.pop();
+                                    }
+                                }
+                            }
+                            KeyCode::Char(c) => {
+                                if let AppState::EditAllGrubParams { params, selected, input_mode, input_buffer, .. } = &mut self.state {
+                                    if *input_mode == GrubConfigInputMode::EditValue {
+                                        let integer = params.get(*selected)
+                                            .and_then(|(key, _)| grub_settings::lookup(key))
+                                            .is_some_and(|s| matches!(s.value_type, grub_settings::ValueType::Integer { .. }));
                                         if c == '\n' || c == '\r' {
                                             // Enter key handled above
                                         } else if c == '\x08' || c == '\x7f' {
                                             // Backspace
                                             input_buffer.pop();
-                                        } else {
+                                        } else if !integer || c.is_ascii_digit() || c == '-' {
                                             input_buffer.push(c);
                                         }
                                     }
@@ -1458,6 +2078,262 @@ impl App {
         Ok(())
     }
 
+    // Show what a write changes, it is done once confirmed
+    fn preview(&mut self, path: PathBuf, diff: Vec<diff::DiffLine>, action: PendingAction) {
+        self.navigate_to(AppState::PreviewChanges { files: vec![(path, diff)], scroll: 0, action }, true);
+    }
+
+    fn preview_restore(&mut self, backup: PathBuf, components: Vec<backup_manager::Component>) {
+        match backup_manager::restore_diff(&self.paths, &backup, &components) {
+            Ok(diffs) if !diffs.is_empty() => {
+                let files = diffs.into_iter().map(|file| (file.path, file.diff)).collect();
+                self.navigate_to(AppState::PreviewChanges {
+                    files,
+                    scroll: 0,
+                    action: PendingAction::RestoreBackup { backup, components },
+                }, true);
+            }
+            Ok(_) => {
+                self.state = AppState::Message {
+                    title: "No Changes".to_string(),
+                    content: vec![format!("{} is the same as the current configuration.", self.paths.display(&backup))],
+                    message_type: MessageType::Info,
+                };
+            }
+            Err(e) => {
+                self.state = AppState::Message {
+                    title: "Error".to_string(),
+                    content: vec![format!("Failed to read backup: {}", e)],
+                    message_type: MessageType::Error,
+                };
+            }
+        }
+    }
+
+    // Diff from `backup` to `other`, or to the current configuration; the
+    // older of two backups is the old side
+    fn show_backup_diff(&mut self, backup: &backup_manager::BackupInfo, other: Option<&backup_manager::BackupInfo>) {
+        let name = |b: &backup_manager::BackupInfo| b.path.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let (old, new, title) = match other {
+            Some(other) => {
+                let (old, new) = if other.modified < backup.modified { (other, backup) } else { (backup, other) };
+                (old, Some(new), format!("{} → {}", name(old), name(new)))
+            }
+            None => (backup, None, format!("{} → current configuration", name(backup))),
+        };
+        match backup_manager::compare(&self.paths, Some(&old.path), new.map(|b| b.path.as_path())) {
+            Ok(diffs) => {
+                let mut summary = backup_manager::summarize(&self.paths, &diffs);
+                if summary.is_empty() {
+                    summary.push("No differences".to_string());
+                }
+                let files = diffs.into_iter().map(|file| (file.path, file.diff)).collect();
+                self.navigate_to(AppState::BackupDiff { title, summary, files, scroll: 0 }, true);
+            }
+            Err(e) => {
+                self.state = AppState::Message {
+                    title: "Error".to_string(),
+                    content: vec![format!("Failed to read backup: {}", e)],
+                    message_type: MessageType::Error,
+                };
+            }
+        }
+    }
+
+    fn styled_diff(paths: &paths::Paths, files: &[(PathBuf, Vec<diff::DiffLine>)]) -> Vec<Line<'static>> {
+        Self::preview_lines(paths, files).into_iter()
+            .map(|line| {
+                let style = match line.chars().next() {
+                    Some('+') => Style::default().fg(Color::Green),
+                    Some('-') => Style::default().fg(Color::Red),
+                    Some('@') => Style::default().fg(Color::Cyan),
+                    Some('=') => Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                    _ => Style::default().fg(Color::White),
+                };
+                Line::styled(line, style)
+            })
+            .collect()
+    }
+
+    fn scrolled(code: KeyCode, scroll: usize, last: usize) -> usize {
+        match code {
+            KeyCode::Up => scroll.saturating_sub(1),
+            KeyCode::Down => scroll + 1,
+            KeyCode::PageUp => scroll.saturating_sub(10),
+            KeyCode::PageDown => scroll + 10,
+            KeyCode::Home => 0,
+            _ => last,
+        }
+        .min(last)
+    }
+
+    // Unified diff of each file, under its path when there are several
+    fn preview_lines(paths: &paths::Paths, files: &[(PathBuf, Vec<diff::DiffLine>)]) -> Vec<String> {
+        if let [(_, diff)] = files {
+            return diff::unified(diff, PREVIEW_CONTEXT);
+        }
+        let mut lines = Vec::new();
+        for (path, diff) in files {
+            lines.push(format!("=== {}", paths.display(path)));
+            lines.extend(diff::unified(diff, PREVIEW_CONTEXT));
+        }
+        lines
+    }
+
+    fn confirm_pending(&mut self, action: PendingAction) {
+        match action {
+            PendingAction::Save { write, action, changes } => {
+                self.state = match write.write(&action) {
+                    Ok(_) => AppState::ConfirmApply { changes },
+                    Err(e) => AppState::Message {
+                        title: "Error".to_string(),
+                        content: vec![format!("Error saving configuration: {}", e)],
+                        message_type: MessageType::Error,
+                    },
+                };
+            }
+            PendingAction::SaveParam { write, key } => {
+                let result = write.write(&format!("edit {}", key)).and_then(|_| self.all_params_state(Some(&key)));
+                match result {
+                    Ok(state) => {
+                        // Replace the list still in edit mode
+                        self.pop_state();
+                        self.state = state;
+                    }
+                    Err(e) => {
+                        self.state = AppState::Message {
+                            title: "Error".to_string(),
+                            content: vec![format!("Error saving parameter: {}", e)],
+                            message_type: MessageType::Error,
+                        };
+                    }
+                }
+            }
+            PendingAction::RestoreBackup { backup, components } => {
+                self.state = match backup_manager::restore_backup(&self.paths, &backup, &components) {
+                    // Regenerating would replace the grub.cfg just restored
+                    Ok(_) if components.contains(&backup_manager::Component::GrubCfg) => AppState::Message {
+                        title: "Success".to_string(),
+                        content: vec![
+                            "Backup restored successfully!".to_string(),
+                            format!("{} was restored too, no update-grub needed.", self.paths.display(&self.paths.grub_cfg())),
+                        ],
+                        message_type: MessageType::Success,
+                    },
+                    Ok(_) => AppState::ConfirmApply {
+                        changes: vec!["Backup restored successfully!".to_string()],
+                    },
+                    Err(e) => AppState::Message {
+                        title: "Error".to_string(),
+                        content: vec![format!("Failed to restore backup: {}", e)],
+                        message_type: MessageType::Error,
+                    },
+                };
+            }
+            PendingAction::SwitchProfile { profiles, name } => {
+                self.switch_profile(profiles, name.as_deref());
+            }
+            PendingAction::SavedEntry { saved_entry, entry_name } => {
+                self.set_saved_entry(&saved_entry, &entry_name);
+            }
+        }
+    }
+
+    fn switch_profile(&mut self, mut profiles: profiles::Profiles, name: Option<&str>) {
+        let result = grub_config::GrubConfig::load(&self.paths)
+            .and_then(|mut config| profiles.switch(&self.paths, &mut config, name, self.save_target));
+        self.state = match result {
+            Ok(changes) => AppState::ConfirmApply { changes },
+            Err(e) => AppState::Message {
+                title: "Error".to_string(),
+                content: vec![format!("Error switching profile: {}", e)],
+                message_type: MessageType::Error,
+            },
+        };
+    }
+
+    // Every effective setting, sorted, with `key` selected
+    fn all_params_state(&self, key: Option<&str>) -> Result<AppState, String> {
+        let config = grub_config::GrubConfig::load(&self.paths)?;
+        let mut params: Vec<(String, String)> = config.get_all_params()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        params.sort_by(|a, b| a.0.cmp(&b.0));
+        let selected = key.and_then(|key| params.iter().position(|(k, _)| k == key)).unwrap_or(0);
+        let sources = Self::param_sources(&config, &params);
+        Ok(AppState::EditAllGrubParams {
+            params,
+            sources,
+            selected,
+            input_mode: GrubConfigInputMode::None,
+            input_buffer: String::new(),
+        })
+    }
+
+    // Write saved_entry to grubenv, and GRUB_DEFAULT=saved if needed
+    fn set_saved_entry(&mut self, saved_entry: &str, entry_name: &str) {
+        match grub_config::GrubConfig::load(&self.paths) {
+            Ok(mut config) => {
+                let mut content = Vec::new();
+                let result = grubenv::set_saved_entry(&self.paths, saved_entry).and_then(|_| {
+                    content.push(format!("'{}' is now the saved default entry.", entry_name));
+                    content.push("".to_string());
+                    content.push(format!("saved_entry={}", saved_entry));
+                    // saved_entry is only read by grub.cfg generated with GRUB_DEFAULT=saved
+                    if grubenv::is_saved(&config.grub_default) {
+                        content.push("No update-grub needed.".to_string());
+                        Ok(false)
+                    } else {
+                        let previous = config.grub_default.clone();
+                        config.set("GRUB_DEFAULT", "saved".to_string());
+                        config.save_to(self.save_target, "set saved default entry")?;
+                        content.push(format!("GRUB_DEFAULT changed from {} to saved.", previous));
+                        Ok(true)
+                    }
+                });
+
+                if config.get("GRUB_SAVEDEFAULT").map(|v| v.trim_matches('"')) == Some("true") {
+                    content.push("".to_string());
+                    content.push("Note: GRUB_SAVEDEFAULT=true, choosing another entry".to_string());
+                    content.push("at the boot menu will replace saved_entry.".to_string());
+                }
+
+                self.state = match result {
+                    // grub.cfg has to be regenerated once to read saved_entry
+                    Ok(true) => AppState::ConfirmApply { changes: content },
+                    Ok(false) => AppState::Message {
+                        title: "Success".to_string(),
+                        content,
+                        message_type: MessageType::Success,
+                    },
+                    Err(e) => AppState::Message {
+                        title: "Error".to_string(),
+                        content: vec![format!("Error setting saved entry: {}", e)],
+                        message_type: MessageType::Error,
+                    },
+                };
+            }
+            Err(e) => {
+                self.state = AppState::Message {
+                    title: "Error".to_string(),
+                    content: vec![
+                        format!("Error loading config: {}", e),
+                    ],
+                    message_type: MessageType::Error,
+                };
+            }
+        }
+    }
+
+    fn param_sources(config: &grub_config::GrubConfig, params: &[(String, String)]) -> HashMap<String, PathBuf> {
+        params.iter()
+            .filter_map(|(key, _)| config.source(key).map(|path| (key.clone(), path.to_path_buf())))
+            .collect()
+    }
+
     fn handle_main_menu_action(&mut self, selected: usize) -> io::Result<()> {
         match selected {
             0 => {
@@ -1465,7 +2341,6 @@ impl App {
                 self.navigate_to(AppState::SelectBootEntry {
                     path: vec![],
                     selected: 0,
-                    action: None,
                 }, true);
             }
             1 => {
@@ -1474,7 +2349,7 @@ impl App {
             }
             2 => {
                 // Configure GRUB Settings
-                match grub_config::GrubConfig::load() {
+                match grub_config::GrubConfig::load(&self.paths) {
                     Ok(config) => {
                         let linux_params = grub_config::parse_parameters(&config.grub_cmdline_linux);
                         let linux_default_params = grub_config::parse_parameters(&config.grub_cmdline_linux_default);
@@ -1489,14 +2364,17 @@ impl App {
                         }, true);
                     }
                     Err(e) => {
-                        // TODO: Show error message
-                        return Ok(());
+                        self.navigate_to(AppState::Message {
+                            title: "Error".to_string(),
+                            content: vec![e],
+                            message_type: MessageType::Error,
+                        }, true);
                     }
                 }
             }
             3 => {
                 // Cleanup Old Kernels
-                let kernels = kernel_cleanup::scan_unused_kernels();
+                let kernels = kernel_cleanup::scan_unused_kernels(&self.paths, &self.entry);
                 self.navigate_to(AppState::CleanupKernels {
                     kernels,
                     selected: 0,
@@ -1504,10 +2382,11 @@ impl App {
             }
             4 => {
                 // Backup Manager
-                let backups = backup_manager::list_backups();
+                let backups = backup_manager::list_backups(&self.paths);
                 self.navigate_to(AppState::BackupManager {
                     backups,
                     selected: 0,
+                    marked: None,
                 }, true);
             }
             5 => {
@@ -1518,34 +2397,51 @@ impl App {
                     selected: 0,
                 }, true);
             }
+            6 => {
+                // Custom Boot Entries
+                self.navigate_to(AppState::CustomEntries {
+                    entries: custom_entries::load(&self.paths),
+                    selected: 0,
+                    input_mode: CustomEntryInput::None,
+                    input_buffer: String::new(),
+                }, true);
+            }
+            7 => {
+                // Apply Changes
+                self.navigate_to(AppState::ConfirmApply {
+                    changes: Vec::new(),
+                }, true);
+            }
             _ => {}
         }
         Ok(())
     }
 
-    fn start_search(&mut self, c: char) {
-        self.search_query.clear();
-        self.search_query.push(c);
-        self.update_search_results();
-        self.state = AppState::SearchMode { selected: 0 };
+    // Write the custom entries script and offer to regenerate grub.cfg
+    fn save_custom_entries(&mut self, entries: &[Entry], change: String) {
+        self.state = match custom_entries::save(&self.paths, entries) {
+            Ok(_) => AppState::ConfirmApply {
+                changes: vec![change, format!("Saved to {}", self.paths.display(&self.paths.custom_entries_script()))],
+            },
+            Err(e) => AppState::Message {
+                title: "Error".to_string(),
+                content: vec![format!("Failed to write {}: {}", self.paths.display(&self.paths.custom_entries_script()), e)],
+                message_type: MessageType::Error,
+            },
+        };
     }
 
-    fn start_boot_entry_search(&mut self, c: char, path: Vec<usize>) {
+    fn start_boot_entry_search(&mut self, c: char) {
         let mut query = String::new();
         query.push(c);
         let results = self.collect_all_matches(&query);
         self.navigate_to(AppState::SelectBootEntrySearch {
-            path,
             query,
             results,
             selected: 0,
         }, true);
     }
 
-    fn update_search_results(&mut self) {
-        self.search_results = self.collect_all_matches(&self.search_query);
-    }
-
     fn collect_all_matches(&self, query: &str) -> Vec<Vec<usize>> {
         if query.is_empty() {
             return Vec::new();
@@ -1617,13 +2513,15 @@ impl App {
                     ListItem::new("🧹 Cleanup Old Kernels"),
                     ListItem::new("💾 Backup Manager"),
                     ListItem::new("⏱ Boot Time Statistics"),
+                    ListItem::new("🧩 Custom Boot Entries"),
+                    ListItem::new("🔄 Apply Changes (Regenerate grub.cfg)"),
                 ]
                 .into_iter()
                 .map(|item| item.style(Style::default().fg(Color::White)))
                 .collect();
 
                 let list = List::new(items)
-                    .block(Block::default().borders(Borders::ALL).title("Main Menu"))
+                    .block(Block::default().borders(Borders::ALL).title(format!("Main Menu - {}", self.grub_cfg_source)))
                     .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
                     .highlight_symbol(">> ");
 
@@ -1631,43 +2529,7 @@ impl App {
                 state.select(Some(*selected));
                 f.render_stateful_widget(list, chunks[1], &mut state);
             }
-            AppState::SearchMode { selected } => {
-                let chunks = Layout::default()
-                    .direction(Direction::Vertical)
-                    .constraints([
-                        Constraint::Length(3),
-                        Constraint::Min(0),
-                    ])
-                    .split(chunks[1]);
-
-                let search_input = Paragraph::new(self.search_query.as_str())
-                    .block(Block::default().borders(Borders::ALL).title("Search"))
-                    .style(Style::default().fg(Color::Yellow));
-                f.render_widget(search_input, chunks[0]);
-
-                let items: Vec<ListItem> = self.search_results
-                    .iter()
-                    .map(|path| {
-                        let entry_ref = get_entry(&self.entry, path);
-                        let tag = match entry_ref.entry_type {
-                            EntryType::Submenu => "[+] ",
-                            EntryType::MenuEntry => "[●] ",
-                            EntryType::Root => "",
-                        };
-                        ListItem::new(format!("{}{}", tag, entry_ref.name))
-                    })
-                    .collect();
-
-                let list = List::new(items)
-                    .block(Block::default().borders(Borders::ALL).title(format!("Search Results ({} found)", self.search_results.len())))
-                    .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
-                    .highlight_symbol(">> ");
-
-                let mut state = ListState::default();
-                state.select(Some(*selected));
-                f.render_stateful_widget(list, chunks[1], &mut state);
-            }
-            AppState::SelectBootEntry { path, selected, action: _ } => {
+            AppState::SelectBootEntry { path, selected } => {
                 let entry_ref = if path.is_empty() {
                     &self.entry
                 } else {
@@ -1686,7 +2548,7 @@ impl App {
                     .collect();
 
                 let list = List::new(items)
-                    .block(Block::default().borders(Borders::ALL).title("Select Boot Entry to Set as Default"))
+                    .block(Block::default().borders(Borders::ALL).title("Select Boot Entry (y: Set Default, b: Boot Once, i: Info, e: Rename, c: Copy with Own Parameters)"))
                     .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
                     .highlight_symbol(">> ");
 
@@ -1694,7 +2556,7 @@ impl App {
                 state.select(Some(*selected));
                 f.render_stateful_widget(list, chunks[1], &mut state);
             }
-            AppState::SelectBootEntrySearch { path: _, query, results, selected } => {
+            AppState::SelectBootEntrySearch { query, results, selected } => {
                 let chunks = Layout::default()
                     .direction(Direction::Vertical)
                     .constraints([
@@ -1742,7 +2604,13 @@ impl App {
                 
                 // Add timeout configuration items
                 let timeout_display = match input_mode {
-                    GrubConfigInputMode::EditTimeout => input_buffer.clone(),
+                    GrubConfigInputMode::EditValue => match grub_settings::validate("GRUB_TIMEOUT", input_buffer.trim()) {
+                        Err(_) if !input_buffer.trim().is_empty() => {
+                            let expected = grub_settings::lookup("GRUB_TIMEOUT").map(|s| s.value_type.describe()).unwrap_or_default();
+                            format!("{}  (must be {})", input_buffer, expected)
+                        }
+                        _ => input_buffer.clone(),
+                    },
                     _ => timeout.clone(),
                 };
                 items.push(ListItem::new(format!("3. GRUB_TIMEOUT: {}", timeout_display)));
@@ -1755,7 +2623,8 @@ impl App {
                 
                 items.push(ListItem::new("5. View/Edit All Parameters"));
                 items.push(ListItem::new("6. Validate GRUB Config"));
-                items.push(ListItem::new("7. Save"));
+                items.push(ListItem::new(format!("7. Save to {} (t to switch)",
+                    self.paths.display(&self.save_target.path(&self.paths)))));
                 items.push(ListItem::new("8. Cancel"));
                 
                 let items: Vec<ListItem> = items
@@ -1764,7 +2633,7 @@ impl App {
                     .collect();
 
                 let list = List::new(items)
-                    .block(Block::default().borders(Borders::ALL).title("Configure GRUB Settings"))
+                    .block(Block::default().borders(Borders::ALL).title("Configure GRUB Settings (p: parameter profiles)"))
                     .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
                     .highlight_symbol(">> ");
 
@@ -1778,6 +2647,7 @@ impl App {
                     .constraints([
                         Constraint::Length(3),
                         Constraint::Min(0),
+                        Constraint::Length(7),
                     ])
                     .split(chunks[1]);
 
@@ -1799,7 +2669,7 @@ impl App {
                             "Options".to_string()
                         }
                     }
-                    InputMode::AddName => "Enter parameter name".to_string(),
+                    InputMode::AddName => "Enter parameter name (Tab to complete)".to_string(),
                     InputMode::AddValue(name) => format!("Enter value for {} (or leave empty)", name),
                     InputMode::DeleteIndex => format!("Enter parameter number to delete [1-{}]", params.len()),
                 };
@@ -1810,15 +2680,15 @@ impl App {
                 f.render_widget(input_widget, chunks[0]);
 
                 // Parameter list
+                let kernel_count = grub_config::kernel_parameter_count(params);
                 let mut items: Vec<ListItem> = params.iter()
                     .enumerate()
                     .map(|(i, param)| {
                         let (name, value) = grub_config::split_parameter(param);
-                        let text = if let Some(val) = value {
-                            format!("  {}. {}={}", i + 1, name, val)
-                        } else {
-                            format!("  {}. {}", i + 1, name)
-                        };
+                        let mut text = format!("  {}. {}", i + 1, grub_config::format_parameter(&name, value.as_deref()));
+                        if i > kernel_count {
+                            text.push_str("  (passed to init)");
+                        }
                         ListItem::new(text)
                     })
                     .collect();
@@ -1838,9 +2708,59 @@ impl App {
                 let mut state = ListState::default();
                 state.select(Some(*selected));
                 f.render_stateful_widget(list, chunks[1], &mut state);
+
+                // Suggestions while typing a name, otherwise what the parameter
+                // does and what looks wrong with the value or the whole list
+                let warning = Style::default().fg(Color::Yellow);
+                let describe = |name: &str| match kernel_params::lookup(name) {
+                    Some(known) => format!("{} ({}): {}", known.name, known.value.describe(), known.help),
+                    None => format!("{}: not a known kernel parameter", name),
+                };
+                let (info_title, info): (&str, Vec<Line>) = match input_mode {
+                    InputMode::AddName => {
+                        let matches = kernel_params::complete(input_buffer.trim());
+                        let lines = matches.iter()
+                            .take(5)
+                            .map(|p| Line::from(format!("{} - {}", p.name, p.help)))
+                            .collect();
+                        ("Suggestions", lines)
+                    }
+                    InputMode::AddValue(name) => {
+                        let mut lines = vec![Line::from(describe(name))];
+                        let param = if input_buffer.trim().is_empty() {
+                            name.clone()
+                        } else {
+                            grub_config::format_parameter(name, Some(input_buffer.trim()))
+                        };
+                        lines.extend(kernel_params::check_param(&param).map(|w| Line::styled(w, warning)));
+                        ("Parameter", lines)
+                    }
+                    InputMode::EditValue(idx) if *idx < params.len() => {
+                        let (name, _) = grub_config::split_parameter(&params[*idx]);
+                        let mut lines = vec![Line::from(describe(&name))];
+                        if !input_buffer.trim().is_empty() {
+                            let param = grub_config::format_parameter(&name, Some(input_buffer.trim()));
+                            lines.extend(kernel_params::check_param(&param).map(|w| Line::styled(w, warning)));
+                        }
+                        ("Parameter", lines)
+                    }
+                    _ => {
+                        let mut lines: Vec<Line> = kernel_params::check(params).into_iter()
+                            .map(|w| Line::styled(format!("⚠ {}", w), warning))
+                            .collect();
+                        if let Some(param) = params.get(*selected) {
+                            lines.insert(0, Line::from(describe(&grub_config::split_parameter(param).0)));
+                        }
+                        ("Parameter", lines)
+                    }
+                };
+                let info_widget = Paragraph::new(info)
+                    .block(Block::default().borders(Borders::ALL).title(info_title))
+                    .wrap(Wrap { trim: true });
+                f.render_widget(info_widget, chunks[2]);
             }
             AppState::ViewDefaultEntry => {
-                let config = match grub_config::GrubConfig::load() {
+                let config = match grub_config::GrubConfig::load(&self.paths) {
                     Ok(c) => c,
                     Err(e) => {
                         let error_text = Paragraph::new(format!("Error loading config: {}", e))
@@ -1851,36 +2771,72 @@ impl App {
                     }
                 };
 
-                let entry_ref = if config.grub_default == "\"saved\"" || config.grub_default == "saved" {
-                    "GRUB_DEFAULT=saved (current boot entry)".to_string()
-                } else {
-                    // Try to find entry by path
-                    let path_str = config.grub_default.trim_matches('"');
-                    let path: Vec<usize> = path_str.split('>')
-                        .filter_map(|s| s.parse().ok())
-                        .collect();
-                    if let Some(entry) = try_get_entry(&self.entry, &path) {
-                        entry.name.clone()
+                // An unreadable grubenv is treated as empty, like GRUB does
+                let (env, env_error) = match grubenv::GrubEnv::load(&self.paths) {
+                    Ok(env) => (env, None),
+                    Err(e) => (grubenv::GrubEnv::empty(self.paths.grubenv()), Some(e)),
+                };
+                let (source, effective) = grubenv::effective_default(&config.grub_default, &env);
+
+                // Try to find entry by numeric path, ID or title
+                let entry_name = |spec: &str| -> String {
+                    match grub_validate::resolve_default(&self.entry, spec)
+                        .and_then(|path| try_get_entry(&self.entry, &path)) {
+                        Some(entry) => entry.name.clone(),
+                        None => "(no matching entry)".to_string(),
+                    }
+                };
+
+                let mechanism = |label: &str, value: Option<&str>, this: grubenv::DefaultSource| -> Vec<Line> {
+                    let active = source == this;
+                    let style = if active {
+                        Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)
                     } else {
-                        config.grub_default.clone()
+                        Style::default().fg(Color::Blue)
+                    };
+                    let mut lines = vec![Line::from(vec![
+                        Span::styled(format!("{}: ", label), style),
+                        Span::raw(value.unwrap_or("(unset)").to_string()),
+                        Span::styled(if active { "  <- wins at next boot" } else { "" }, style),
+                    ])];
+                    if let Some(value) = value.filter(|v| !v.is_empty() && !grubenv::is_saved(v)) {
+                        lines.push(Line::from(format!("    {}", entry_name(value))));
                     }
+                    lines
                 };
 
-                let content = vec![
+                let mut content = vec![
                     Line::from("Current Default Boot Entry:"),
                     Line::from(""),
+                ];
+                content.extend(mechanism("next_entry", env.get("next_entry"), grubenv::DefaultSource::NextEntry));
+                if let Some(once) = boot_once::pending(&env) {
+                    content.push(Line::from(format!("    one-time: linux {} {}", once.linux, grub_config::join_parameters(&once.params))));
+                }
+                content.extend(mechanism("saved_entry", env.get("saved_entry"), grubenv::DefaultSource::SavedEntry));
+                content.extend(mechanism("GRUB_DEFAULT", Some(&config.grub_default), grubenv::DefaultSource::GrubDefault));
+                if let Some(e) = env_error {
+                    content.push(Line::from(Span::styled(e, Style::default().fg(Color::Red))));
+                }
+                if let Some(v) = config.get("GRUB_SAVEDEFAULT") {
+                    content.push(Line::from(vec![
+                        Span::styled("GRUB_SAVEDEFAULT: ", Style::default().fg(Color::Blue)),
+                        Span::raw(v.clone()),
+                    ]));
+                }
+                content.extend(vec![
+                    Line::from(""),
                     Line::from(vec![
-                        Span::styled("GRUB_DEFAULT: ", Style::default().fg(Color::Blue)),
-                        Span::raw(&config.grub_default),
+                        Span::styled("Next boot: ", Style::default().fg(Color::Green)),
+                        Span::raw(entry_name(&effective)),
                     ]),
-                    Line::from(""),
                     Line::from(vec![
-                        Span::styled("Entry: ", Style::default().fg(Color::Green)),
-                        Span::raw(&entry_ref),
+                        Span::styled("Chosen by: ", Style::default().fg(Color::Green)),
+                        Span::raw(source.label()),
                     ]),
                     Line::from(""),
                     Line::from("Press ESC or Enter to return"),
-                ];
+                ]);
 
                 let info = Paragraph::new(content)
                     .block(Block::default().borders(Borders::ALL).title("View Default Boot Entry"))
@@ -1892,8 +2848,16 @@ impl App {
                     .map(|x| x.to_string())
                     .collect::<Vec<_>>()
                     .join(">");
+                let entry_id = grub::entry_id_path(&self.entry, path)
+                    .unwrap_or_else(|| "(none, numeric path will be used)".to_string());
+                let config = grub_config::GrubConfig::load(&self.paths).ok();
+                let grub_default = config.as_ref().map(|c| c.grub_default.clone()).unwrap_or_default();
+                let save_default = config.as_ref()
+                    .and_then(|c| c.get("GRUB_SAVEDEFAULT"))
+                    .map(|v| v.trim_matches('"') == "true")
+                    .unwrap_or(false);
                 
-                let content = vec![
+                let mut content = vec![
                     Line::from(""),
                     Line::from(vec![
                         Span::styled("Set '", Style::default().fg(Color::White)),
@@ -1905,6 +2869,75 @@ impl App {
                         Span::styled("Path: ", Style::default().fg(Color::Blue)),
                         Span::raw(&p_str),
                     ]),
+                    Line::from(vec![
+                        Span::styled("ID: ", Style::default().fg(Color::Blue)),
+                        Span::raw(entry_id),
+                    ]),
+                    Line::from(vec![
+                        Span::styled("GRUB_DEFAULT: ", Style::default().fg(Color::Blue)),
+                        Span::raw(grub_default.clone()),
+                    ]),
+                    Line::from(""),
+                    Line::from(vec![
+                        Span::styled("y", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+                        Span::styled(": write GRUB_DEFAULT (regenerates grub.cfg)", Style::default().fg(Color::White)),
+                    ]),
+                    Line::from(vec![
+                        Span::styled("s", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+                        Span::styled(": write saved_entry to grubenv (grub-set-default)", Style::default().fg(Color::White)),
+                    ]),
+                ];
+                if !grubenv::is_saved(&grub_default) {
+                    content.push(Line::from(Span::styled(
+                        "   also switches GRUB_DEFAULT to saved",
+                        Style::default().fg(Color::Yellow),
+                    )));
+                }
+                if save_default {
+                    content.push(Line::from(Span::styled(
+                        "GRUB_SAVEDEFAULT=true: the entry picked at boot replaces saved_entry",
+                        Style::default().fg(Color::Yellow),
+                    )));
+                }
+                content.push(Line::from(""));
+                content.push(Line::from(vec![
+                    Span::styled("Confirm [", Style::default().fg(Color::White)),
+                    Span::styled("y", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+                    Span::styled("/", Style::default().fg(Color::White)),
+                    Span::styled("s", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+                    Span::styled("/", Style::default().fg(Color::White)),
+                    Span::styled("n", Style::default().fg(Color::Red)),
+                    Span::styled("]: ", Style::default().fg(Color::White)),
+                ]));
+
+                let dialog = Paragraph::new(content)
+                    .block(Block::default().borders(Borders::ALL).title("Confirm"))
+                    .alignment(Alignment::Center);
+                f.render_widget(dialog, chunks[1]);
+            }
+            AppState::ConfirmBootOnce { path, entry_name } => {
+                let next_entry = grub::entry_id_path(&self.entry, path).unwrap_or_else(|| {
+                    path.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(">")
+                });
+                
+                let content = vec![
+                    Line::from(""),
+                    Line::from(vec![
+                        Span::styled("Boot '", Style::default().fg(Color::White)),
+                        Span::styled(entry_name.as_str(), Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                        Span::styled("' once at the next restart?", Style::default().fg(Color::White)),
+                    ]),
+                    Line::from(""),
+                    Line::from(vec![
+                        Span::styled("next_entry: ", Style::default().fg(Color::Blue)),
+                        Span::raw(next_entry),
+                    ]),
+                    Line::from(vec![
+                        Span::styled("grubenv: ", Style::default().fg(Color::Blue)),
+                        Span::raw(self.paths.display(&self.paths.grubenv())),
+                    ]),
+                    Line::from(""),
+                    Line::from("Press p to add kernel parameters for this boot only."),
                     Line::from(""),
                     Line::from(vec![
                         Span::styled("Confirm [", Style::default().fg(Color::White)),
@@ -1916,7 +2949,46 @@ impl App {
                 ];
 
                 let dialog = Paragraph::new(content)
-                    .block(Block::default().borders(Borders::ALL).title("Confirm"))
+                    .block(Block::default().borders(Borders::ALL).title("Confirm Boot Once"))
+                    .alignment(Alignment::Center);
+                f.render_widget(dialog, chunks[1]);
+            }
+            AppState::ConfirmApply { changes } => {
+                let mut content: Vec<Line> = changes.iter().map(|c| Line::from(c.as_str())).collect();
+                if !content.is_empty() {
+                    content.push(Line::from(""));
+                }
+                content.push(Line::from("grub.cfg must be regenerated for changes to take effect."));
+                content.push(Line::from(""));
+                match grub_mkconfig::find_generator(&self.paths) {
+                    Some(generator) => {
+                        content.push(Line::from(vec![
+                            Span::styled("Command: ", Style::default().fg(Color::Blue)),
+                            Span::raw(generator.command_line(&self.paths)),
+                        ]));
+                        content.push(Line::from(vec![
+                            Span::styled("Target: ", Style::default().fg(Color::Blue)),
+                            Span::raw(self.paths.display(&self.paths.grub_cfg())),
+                        ]));
+                    }
+                    None => {
+                        content.push(Line::from(Span::styled(
+                            "No grub-mkconfig, grub2-mkconfig or update-grub found",
+                            Style::default().fg(Color::Red),
+                        )));
+                    }
+                }
+                content.push(Line::from(""));
+                content.push(Line::from(vec![
+                    Span::styled("Regenerate now? [", Style::default().fg(Color::White)),
+                    Span::styled("Y", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+                    Span::styled("/", Style::default().fg(Color::White)),
+                    Span::styled("n", Style::default().fg(Color::Red)),
+                    Span::styled("]: ", Style::default().fg(Color::White)),
+                ]));
+
+                let dialog = Paragraph::new(content)
+                    .block(Block::default().borders(Borders::ALL).title("Apply Changes"))
                     .alignment(Alignment::Center);
                 f.render_widget(dialog, chunks[1]);
             }
@@ -1954,6 +3026,45 @@ impl App {
                     ]));
                 }
                 
+                // What GRUB will actually boot for this entry
+                let boot = &entry.boot;
+                if boot.linux.is_some() || boot.chainloader.is_some() {
+                    content.push(Line::from(""));
+                }
+                if let Some(linux) = &boot.linux {
+                    content.push(Line::from(vec![
+                        Span::styled("Linux: ", Style::default().fg(Color::Blue)),
+                        Span::raw(linux.as_str()),
+                    ]));
+                    content.push(Line::from(vec![
+                        Span::styled("Command Line: ", Style::default().fg(Color::Blue)),
+                        Span::raw(boot.cmdline()),
+                    ]));
+                }
+                if !boot.initrd.is_empty() {
+                    content.push(Line::from(vec![
+                        Span::styled("Initrd: ", Style::default().fg(Color::Blue)),
+                        Span::raw(boot.initrd.join(" ")),
+                    ]));
+                }
+                if let Some(uuid) = &boot.search_uuid {
+                    content.push(Line::from(vec![
+                        Span::styled("Root UUID: ", Style::default().fg(Color::Blue)),
+                        Span::raw(uuid.as_str()),
+                    ]));
+                } else if let Some(root) = &boot.root {
+                    content.push(Line::from(vec![
+                        Span::styled("Root: ", Style::default().fg(Color::Blue)),
+                        Span::raw(root.as_str()),
+                    ]));
+                }
+                if let Some(target) = &boot.chainloader {
+                    content.push(Line::from(vec![
+                        Span::styled("Chainloader: ", Style::default().fg(Color::Blue)),
+                        Span::raw(target.as_str()),
+                    ]));
+                }
+                
                 let info = Paragraph::new(content)
                     .block(Block::default().borders(Borders::ALL).title("Kernel Information"))
                     .alignment(Alignment::Left);
@@ -1966,7 +3077,13 @@ impl App {
                     kernels.iter()
                         .map(|k| {
                             let size_str = kernel_cleanup::format_size(k.size);
-                            let status = if k.in_use { "⚠ IN USE" } else { "✓ Safe to remove" };
+                            let status = if k.in_use {
+                                "⚠ IN USE"
+                            } else if k.is_default {
+                                "⚠ DEFAULT ENTRY"
+                            } else {
+                                "✓ Safe to remove"
+                            };
                             ListItem::new(format!("{} - {} ({}) - {}", 
                                 k.version, size_str, k.files.len(), status))
                         })
@@ -1984,7 +3101,7 @@ impl App {
                 }
                 f.render_stateful_widget(list, chunks[1], &mut state);
             }
-            AppState::RenameBootEntry { path, original_name, input_buffer } => {
+            AppState::RenameBootEntry { original_name, input_buffer, .. } => {
                 let content = vec![
                     Line::from(""),
                     Line::from(vec![
@@ -2005,7 +3122,7 @@ impl App {
                     .alignment(Alignment::Left);
                 f.render_widget(info, chunks[1]);
             }
-            AppState::BackupManager { backups, selected } => {
+            AppState::BackupManager { backups, selected, marked } => {
                 let items: Vec<ListItem> = if backups.is_empty() {
                     vec![ListItem::new("No backups found")]
                 } else {
@@ -2016,13 +3133,25 @@ impl App {
                             let name = b.path.file_name()
                                 .and_then(|n| n.to_str())
                                 .unwrap_or("unknown");
-                            ListItem::new(format!("{} - {} - {}", name, size_str, time_str))
+                            let details = match &b.manifest {
+                                Some(m) if m.changed_keys.is_empty() => format!("    {}: {}", m.user, m.action),
+                                Some(m) => format!("    {}: {} ({})", m.user, m.action, m.changed_keys.join(", ")),
+                                None => format!("    legacy backup of {}", self.paths.display(&self.paths.default_grub())),
+                            };
+                            let mark = if marked.as_ref() == Some(&b.path) { "* " } else { "" };
+                            ListItem::new(vec![
+                                Line::from(format!("{}{} - {} - {}", mark, name, size_str, time_str)),
+                                Line::styled(details, Style::default().fg(Color::DarkGray)),
+                            ])
                         })
                         .collect()
                 };
                 
                 let list = List::new(items)
-                    .block(Block::default().borders(Borders::ALL).title("Backup Manager"))
+                    .block(Block::default().borders(Borders::ALL).title(format!(
+                        "Backup Manager: {} ({}) - Enter: Restore, v: Diff, m: Mark to compare, d: Delete",
+                        self.paths.display(&self.paths.backup_dir()),
+                        backup_manager::Retention::load(&self.paths).describe())))
                     .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
                     .highlight_symbol(">> ");
                 
@@ -2032,6 +3161,121 @@ impl App {
                 }
                 f.render_stateful_widget(list, chunks[1], &mut state);
             }
+            AppState::ParameterProfiles { profiles, selected } => {
+                let active = profiles.active.as_ref().map(|a| a.name.as_str());
+                let marker = |is_active: bool| if is_active { "* " } else { "  " };
+                let mut items = vec![ListItem::new(format!("{}default (no profile)", marker(active.is_none())))];
+                for profile in &profiles.profiles {
+                    let mut diff: Vec<String> = profile.remove.iter().map(|p| format!("-{}", p)).collect();
+                    diff.extend(profile.add.iter().map(|p| format!("+{}", p)));
+                    let mut lines = vec![Line::from(format!("{}{}  {}: {}",
+                        marker(active == Some(profile.name.as_str())),
+                        profile.name,
+                        profile.key,
+                        diff.join(" ")))];
+                    if !profile.description.is_empty() {
+                        lines.push(Line::styled(format!("    {}", profile.description), Style::default().fg(Color::DarkGray)));
+                    }
+                    items.push(ListItem::new(lines));
+                }
+
+                let list = List::new(items)
+                    .block(Block::default().borders(Borders::ALL).title(format!(
+                        "Parameter Profiles (Enter: switch, saves to {}; profiles in {})",
+                        self.paths.display(&self.save_target.path(&self.paths)),
+                        self.paths.display(&self.paths.profiles()))))
+                    .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                    .highlight_symbol(">> ");
+
+                let mut state = ListState::default();
+                state.select(Some(*selected));
+                f.render_stateful_widget(list, chunks[1], &mut state);
+            }
+            AppState::CustomEntries { entries, selected, input_mode, input_buffer } => {
+                let editing = *input_mode != CustomEntryInput::None;
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([
+                        Constraint::Min(0),
+                        Constraint::Length(if editing { 6 } else { 0 }),
+                    ])
+                    .split(chunks[1]);
+
+                let items: Vec<ListItem> = if entries.is_empty() {
+                    vec![ListItem::new("No custom entries. Press c on a boot entry in Set Default Boot Entry to copy it.")]
+                } else {
+                    entries.iter()
+                        .map(|entry| {
+                            let command = format!("    linux {} {}",
+                                entry.boot.linux.as_deref().unwrap_or(""),
+                                grub_config::join_parameters(&custom_entries::kernel_params(entry)));
+                            ListItem::new(vec![
+                                Line::from(entry.name.clone()),
+                                Line::styled(command, Style::default().fg(Color::DarkGray)),
+                            ])
+                        })
+                        .collect()
+                };
+                let list = List::new(items)
+                    .block(Block::default().borders(Borders::ALL).title(format!(
+                        "Custom Boot Entries in {} (Enter: Edit Parameters, r: Rename, d: Delete)",
+                        self.paths.display(&self.paths.custom_entries_script()))))
+                    .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                    .highlight_symbol(">> ");
+                let mut state = ListState::default();
+                if !entries.is_empty() {
+                    state.select(Some(*selected));
+                }
+                f.render_stateful_widget(list, chunks[0], &mut state);
+
+                if editing {
+                    let mut lines = vec![Line::from(input_buffer.clone())];
+                    let title = if *input_mode == CustomEntryInput::Rename {
+                        "Title (Enter: Save, Esc: Cancel)"
+                    } else {
+                        lines.extend(kernel_params::check(&grub_config::parse_parameters(input_buffer)).into_iter()
+                            .map(|w| Line::styled(format!("⚠ {}", w), Style::default().fg(Color::Yellow))));
+                        "Kernel Parameters (Enter: Save, Esc: Cancel)"
+                    };
+                    let input = Paragraph::new(lines)
+                        .block(Block::default().borders(Borders::ALL).title(title))
+                        .wrap(Wrap { trim: false });
+                    f.render_widget(input, chunks[1]);
+                }
+            }
+            AppState::BootOnceParams { path, entry_name, input_buffer } => {
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([
+                        Constraint::Length(3),
+                        Constraint::Min(0),
+                    ])
+                    .split(chunks[1]);
+
+                let input = Paragraph::new(input_buffer.as_str())
+                    .block(Block::default().borders(Borders::ALL).title(format!(
+                        "Kernel parameters for booting '{}' once (Enter: Confirm, Esc: Cancel)", entry_name)));
+                f.render_widget(input, chunks[0]);
+
+                let entry = get_entry(&self.entry, path);
+                let params = boot_once::command_line(entry, &grub_config::parse_parameters(input_buffer));
+                let mut content = vec![
+                    Line::from(""),
+                    Line::from(format!("linux {} {}", entry.boot.linux.as_deref().unwrap_or(""), grub_config::join_parameters(&params))),
+                    Line::from(""),
+                    Line::from("e.g. systemd.unit=rescue.target or init=/bin/bash; a name=value replaces the entry's own value."),
+                    Line::from("The parameters are cleared by GRUB when the entry boots."),
+                ];
+                let warnings = kernel_params::check(&params);
+                if !warnings.is_empty() {
+                    content.push(Line::from(""));
+                    content.extend(warnings.into_iter().map(|w| Line::styled(format!("⚠ {}", w), Style::default().fg(Color::Yellow))));
+                }
+                let preview = Paragraph::new(content)
+                    .block(Block::default().borders(Borders::ALL).title("Next Boot"))
+                    .wrap(Wrap { trim: false });
+                f.render_widget(preview, chunks[1]);
+            }
             AppState::ValidateGrub { result } => {
                 let mut content = vec![Line::from("")];
                 
@@ -2100,7 +3344,7 @@ impl App {
                 }
                 f.render_stateful_widget(list, chunks[1], &mut state);
             }
-            AppState::EditAllGrubParams { params, selected, input_mode, input_buffer } => {
+            AppState::EditAllGrubParams { params, sources, selected, input_mode, input_buffer } => {
                 let items: Vec<ListItem> = if params.is_empty() {
                     vec![ListItem::new("No parameters found")]
                 } else {
@@ -2108,25 +3352,144 @@ impl App {
                         .enumerate()
                         .map(|(idx, (key, value))| {
                             let display_value = match input_mode {
-                                GrubConfigInputMode::EditTimeout if idx == *selected => input_buffer.clone(),
+                                GrubConfigInputMode::EditValue if idx == *selected => input_buffer.clone(),
+                                GrubConfigInputMode::SelectOption if idx == *selected => format!("< {} >", input_buffer),
                                 _ => value.clone(),
                             };
-                            ListItem::new(format!("{} = {}", key, display_value))
+                            // Only drop-ins are named, most settings come from the main file
+                            match sources.get(key).filter(|path| **path != self.paths.default_grub()) {
+                                Some(path) => ListItem::new(format!("{} = {}    [{}]", key, display_value, self.paths.display(path))),
+                                None => ListItem::new(format!("{} = {}", key, display_value)),
+                            }
                         })
                         .collect()
                 };
                 
+                let title = format!("All GRUB Parameters, saving to {} (Enter to edit, t to switch file, a to apply, Esc to return)",
+                    self.paths.display(&self.save_target.path(&self.paths)));
                 let list = List::new(items)
                     .block(Block::default()
                         .borders(Borders::ALL)
-                        .title("All GRUB Parameters (Enter to edit, Esc to return)"))
+                        .title(title))
                     .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
                     .highlight_symbol(">> ");
                 
+                let areas = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([
+                        Constraint::Min(0),
+                        Constraint::Length(5),
+                    ])
+                    .split(chunks[1]);
+                
                 let mut state = ListState::default();
                 if !params.is_empty() {
                     state.select(Some(*selected));
                 }
+                f.render_stateful_widget(list, areas[0], &mut state);
+                
+                // Help for the selected setting, and why an edited value can't be saved
+                let mut help: Vec<Line> = Vec::new();
+                let mut help_title = "Help".to_string();
+                if let Some((key, _)) = params.get(*selected) {
+                    match grub_settings::lookup(key) {
+                        Some(setting) => {
+                            help_title = format!("{} ({})", key, setting.value_type.describe());
+                            help.push(Line::from(setting.help));
+                        }
+                        None => help.push(Line::from(format!("{} is not a known GRUB setting, its value is not checked.", key))),
+                    }
+                    match input_mode {
+                        GrubConfigInputMode::EditValue => {
+                            if let Err(e) = grub_settings::validate(key, input_buffer) {
+                                help.push(Line::styled(e, Style::default().fg(Color::Red)));
+                            }
+                        }
+                        GrubConfigInputMode::SelectOption => {
+                            help.push(Line::styled("Left/Right to choose, Enter to save", Style::default().fg(Color::Yellow)));
+                        }
+                        _ => {}
+                    }
+                }
+                let help_widget = Paragraph::new(help)
+                    .block(Block::default().borders(Borders::ALL).title(help_title))
+                    .wrap(Wrap { trim: true });
+                f.render_widget(help_widget, areas[1]);
+            }
+            AppState::PreviewChanges { files, scroll, action } => {
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([
+                        Constraint::Min(0),
+                        Constraint::Length(3),
+                    ])
+                    .split(chunks[1]);
+
+                let lines = Self::styled_diff(&self.paths, files);
+                let (added, removed) = files.iter()
+                    .map(|(_, diff)| diff::count_changes(diff))
+                    .fold((0, 0), |total, (a, r)| (total.0 + a, total.1 + r));
+                let target = match files.as_slice() {
+                    [(path, _)] => self.paths.display(path),
+                    _ => format!("{} files", files.len()),
+                };
+                let title = match action {
+                    PendingAction::RestoreBackup { backup, .. } => format!("Restore {} to {} (+{} -{})",
+                        self.paths.display(backup), target, added, removed),
+                    _ => format!("Changes to {} (+{} -{})", target, added, removed),
+                };
+                let preview = Paragraph::new(lines)
+                    .block(Block::default().borders(Borders::ALL).title(title))
+                    .scroll((*scroll as u16, 0));
+                f.render_widget(preview, chunks[0]);
+
+                let help = Paragraph::new(Line::from(vec![
+                    Span::styled("Write these changes? [", Style::default().fg(Color::White)),
+                    Span::styled("Y", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+                    Span::styled("/", Style::default().fg(Color::White)),
+                    Span::styled("n", Style::default().fg(Color::Red)),
+                    Span::styled("]  ↑↓ PgUp PgDn Scroll", Style::default().fg(Color::White)),
+                ]))
+                .block(Block::default().borders(Borders::ALL))
+                .alignment(Alignment::Center);
+                f.render_widget(help, chunks[1]);
+            }
+            AppState::BackupDiff { title, summary, files, scroll } => {
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([
+                        Constraint::Length(summary.len().min(10) as u16 + 2),
+                        Constraint::Min(0),
+                    ])
+                    .split(chunks[1]);
+
+                let lines: Vec<Line> = summary.iter().map(|line| Line::from(line.as_str())).collect();
+                let summary = Paragraph::new(lines)
+                    .block(Block::default().borders(Borders::ALL).title(title.as_str()));
+                f.render_widget(summary, chunks[0]);
+
+                let diff = Paragraph::new(Self::styled_diff(&self.paths, files))
+                    .block(Block::default().borders(Borders::ALL).title("Diff (↑↓ PgUp PgDn Scroll, ESC Back)"))
+                    .scroll((*scroll as u16, 0));
+                f.render_widget(diff, chunks[1]);
+            }
+            AppState::RestoreComponents { backup, components, selected } => {
+                let items: Vec<ListItem> = components.iter()
+                    .map(|(component, checked)| {
+                        ListItem::new(format!("[{}] {:<10} {}",
+                            if *checked { "x" } else { " " },
+                            component.name(),
+                            component.describe(&self.paths)))
+                    })
+                    .collect();
+                let list = List::new(items)
+                    .block(Block::default().borders(Borders::ALL).title(format!(
+                        "Restore {} (Space: select, Enter: preview)",
+                        self.paths.display(&backup.path))))
+                    .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                    .highlight_symbol(">> ");
+                let mut state = ListState::default();
+                state.select(Some(*selected));
                 f.render_stateful_widget(list, chunks[1], &mut state);
             }
             AppState::Message { title, content, message_type } => {
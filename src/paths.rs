@@ -0,0 +1,140 @@
+use std::path::{Path, PathBuf};
+
+// Filesystem locations of the system being managed.
+//
+// Every module resolves its files through this instead of hard-coding host
+// paths, so grublist can work on a mounted chroot, an offline disk image or
+// a test fixture tree (`--root DIR`).
+#[derive(Debug, Clone)]
+pub struct Paths {
+    pub root: PathBuf,
+    // grub.cfg picked by discovery (see grub_locate), if any
+    grub_cfg: Option<PathBuf>,
+}
+
+impl Default for Paths {
+    fn default() -> Self {
+        Paths::new("/")
+    }
+}
+
+impl Paths {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Paths { root: root.into(), grub_cfg: None }
+    }
+
+    pub fn set_grub_cfg(&mut self, path: PathBuf) {
+        self.grub_cfg = Some(path);
+    }
+
+    /// True when managing the running system rather than a separate root
+    pub fn is_host(&self) -> bool {
+        self.root == Path::new("/")
+    }
+
+    /// Map an absolute path on the managed system to the local filesystem
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        self.root.join(path.trim_start_matches('/'))
+    }
+
+    /// Path as seen from inside the managed system, for messages
+    pub fn display(&self, path: &Path) -> String {
+        match path.strip_prefix(&self.root) {
+            Ok(rel) => format!("/{}", rel.display()),
+            Err(_) => path.display().to_string(),
+        }
+    }
+
+    pub fn boot_dir(&self) -> PathBuf {
+        self.resolve("/boot")
+    }
+
+    pub fn grub_cfg(&self) -> PathBuf {
+        self.grub_cfg.clone().unwrap_or_else(|| self.resolve("/boot/grub/grub.cfg"))
+    }
+
+    /// Directory holding grub.cfg and grubenv (/boot/grub or /boot/grub2)
+    pub fn grub_dir(&self) -> PathBuf {
+        let cfg = self.grub_cfg();
+        cfg.parent().map(Path::to_path_buf).unwrap_or_else(|| self.resolve("/boot/grub"))
+    }
+
+    pub fn grubenv(&self) -> PathBuf {
+        self.grub_dir().join("grubenv")
+    }
+
+    pub fn bls_entries_dir(&self) -> PathBuf {
+        self.resolve("/boot/loader/entries")
+    }
+
+    pub fn default_dir(&self) -> PathBuf {
+        self.resolve("/etc/default")
+    }
+
+    pub fn default_grub(&self) -> PathBuf {
+        self.resolve("/etc/default/grub")
+    }
+
+    /// Drop-in fragments sourced after /etc/default/grub
+    pub fn default_grub_d(&self) -> PathBuf {
+        self.resolve("/etc/default/grub.d")
+    }
+
+    /// Drop-in written by grublist when edits shouldn't touch the main file
+    pub fn grublist_fragment(&self) -> PathBuf {
+        self.default_grub_d().join("grublist.cfg")
+    }
+
+    /// Scripts run by grub-mkconfig
+    pub fn grub_d(&self) -> PathBuf {
+        self.resolve("/etc/grub.d")
+    }
+
+    /// Script holding the entries copied with their own kernel parameters
+    pub fn custom_entries_script(&self) -> PathBuf {
+        self.grub_d().join("42_grublist")
+    }
+
+    /// Script adding the entry used for one-time kernel parameters
+    pub fn boot_once_script(&self) -> PathBuf {
+        self.grub_d().join("43_grublist_once")
+    }
+
+    /// Script adding the boot counter of a trial change to grub.cfg
+    pub fn trial_script(&self) -> PathBuf {
+        self.grub_d().join("01_grublist_trial")
+    }
+
+    /// grub.cfg from before a trial change, loaded when the trial fails
+    pub fn trial_fallback_cfg(&self) -> PathBuf {
+        self.grub_dir().join("grublist-fallback.cfg")
+    }
+
+    /// The running trial change
+    pub fn trial_state(&self) -> PathBuf {
+        self.resolve("/var/lib/grublist/trial.json")
+    }
+
+    pub fn systemd_unit_dir(&self) -> PathBuf {
+        self.resolve("/etc/systemd/system")
+    }
+
+    pub fn custom_names(&self) -> PathBuf {
+        self.resolve("/etc/grublist-custom-names.json")
+    }
+
+    /// Kernel parameter profiles and the active one
+    pub fn profiles(&self) -> PathBuf {
+        self.resolve("/etc/grublist-profiles.json")
+    }
+
+    /// Snapshots taken before grublist changes a settings file
+    pub fn backup_dir(&self) -> PathBuf {
+        self.resolve("/var/lib/grublist/backups")
+    }
+
+    /// Held while grublist changes files on the managed system
+    pub fn lock_file(&self) -> PathBuf {
+        self.resolve("/run/lock/grublist.lock")
+    }
+}
@@ -0,0 +1,319 @@
+// Named kernel parameter profiles (e.g. "debug", "perf").
+//
+// A profile is a diff over a command line setting: parameters to add and
+// parameters to remove. Applying one leaves every other parameter alone, and
+// what it actually changed is recorded as the active profile, so switching
+// profiles or going back to the default first undoes exactly that.
+
+use std::fs;
+use std::io;
+use serde::{Deserialize, Serialize};
+use crate::grub_config::{self, GrubConfig, SaveTarget};
+use crate::kernel_params;
+use crate::paths::Paths;
+use crate::safe_write;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    // Setting the profile changes
+    #[serde(default = "default_key")]
+    pub key: String,
+    // `name[=value]` parameters to add; a value replaces the current one
+    #[serde(default)]
+    pub add: Vec<String>,
+    // `name` removes every occurrence, `name=value` only that one
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+fn default_key() -> String {
+    "GRUB_CMDLINE_LINUX_DEFAULT".to_string()
+}
+
+/// What an applied profile changed, to undo it later
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActiveProfile {
+    pub name: String,
+    pub key: String,
+    pub added: Vec<String>,
+    // Removed or replaced parameters
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profiles {
+    pub profiles: Vec<Profile>,
+    #[serde(default)]
+    pub active: Option<ActiveProfile>,
+}
+
+impl Profiles {
+    /// The saved profiles, or the built-in ones if none were saved yet. A
+    /// file that can't be read is an error: saving over it would lose the
+    /// user's profiles and the record of the active one.
+    pub fn load(paths: &Paths) -> Result<Self, String> {
+        let path = paths.profiles();
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse {}: {}", paths.display(&path), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Profiles {
+                profiles: built_in(),
+                active: None,
+            }),
+            Err(e) => Err(format!("Failed to read {}: {}", paths.display(&path), e)),
+        }
+    }
+
+    pub fn save(&self, paths: &Paths) -> io::Result<()> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(io::Error::other)?;
+        let _lock = safe_write::lock(paths)?;
+        safe_write::write(&paths.profiles(), content)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    /// Undo the active profile and apply `name`, or only undo it for None
+    /// (back to the default command line). Saves the changed setting to
+    /// `target` and records the new active profile. Returns the changes made.
+    pub fn switch(&mut self, paths: &Paths, config: &mut GrubConfig, name: Option<&str>, target: SaveTarget) -> Result<Vec<String>, String> {
+        let (changes, active) = self.stage(config, name)?;
+        let action = match name {
+            Some(name) => format!("profiles apply {}", name),
+            None => "profiles revert".to_string(),
+        };
+        // The new active profile is recorded first, a failed write must not
+        // leave a profile applied that revert doesn't know about. Reverting
+        // one that wasn't applied after all changes nothing.
+        let previous = std::mem::replace(&mut self.active, active);
+        if let Err(e) = self.save(paths) {
+            self.active = previous;
+            return Err(format!("Failed to write {}: {}", paths.display(&paths.profiles()), e));
+        }
+        if let Err(e) = config.save_to(target, &action) {
+            self.active = previous;
+            return match self.save(paths) {
+                Ok(()) => Err(e),
+                Err(re) => Err(format!("{}\nFailed to restore {}: {}", e, paths.display(&paths.profiles()), re)),
+            };
+        }
+        Ok(changes)
+    }
+
+    /// The settings part of switch(), without saving anything: changes
+    /// `config` and returns the changes made and the new active profile
+    pub fn stage(&self, config: &mut GrubConfig, name: Option<&str>) -> Result<(Vec<String>, Option<ActiveProfile>), String> {
+        let profile = match name {
+            Some(name) => Some(self.get(name).cloned().ok_or_else(|| format!("No profile named '{}'", name))?),
+            None => None,
+        };
+
+        let mut changes = Vec::new();
+        if let Some(active) = &self.active {
+            let mut params = grub_config::parse_parameters(config.get(&active.key).map(|s| s.as_str()).unwrap_or(""));
+            revert(active, &mut params);
+            config.set(&active.key, grub_config::join_parameters(&params));
+            changes.push(format!("Reverted profile '{}' on {}", active.name, active.key));
+        }
+
+        let mut active = None;
+        if let Some(profile) = &profile {
+            let mut params = grub_config::parse_parameters(config.get(&profile.key).map(|s| s.as_str()).unwrap_or(""));
+            let applied = apply(profile, &mut params);
+            config.set(&profile.key, grub_config::join_parameters(&params));
+            changes.push(format!("Applied profile '{}' to {}", profile.name, profile.key));
+            changes.extend(applied.removed.iter().map(|p| format!("  - {}", p)));
+            changes.extend(applied.added.iter().map(|p| format!("  + {}", p)));
+            active = Some(applied);
+        }
+        Ok((changes, active))
+    }
+}
+
+fn built_in() -> Vec<Profile> {
+    let params = |list: &[&str]| list.iter().map(|p| p.to_string()).collect();
+    vec![
+        Profile {
+            name: "debug".to_string(),
+            description: "Verbose kernel messages that stay visible across suspend".to_string(),
+            key: default_key(),
+            add: params(&["debug", "ignore_loglevel", "no_console_suspend"]),
+            remove: params(&["quiet", "splash"]),
+        },
+        Profile {
+            name: "perf".to_string(),
+            description: "Turn off CPU vulnerability mitigations (unsafe)".to_string(),
+            key: default_key(),
+            add: params(&["mitigations=off"]),
+            remove: Vec::new(),
+        },
+    ]
+}
+
+fn matches(param: &str, pattern: &str) -> bool {
+    let (name, value) = grub_config::split_parameter(param);
+    let (pattern_name, pattern_value) = grub_config::split_parameter(pattern);
+    name == pattern_name && (pattern_value.is_none() || value == pattern_value)
+}
+
+fn repeatable(name: &str) -> bool {
+    kernel_params::lookup(name).is_some_and(|p| p.repeatable)
+}
+
+/// Apply a profile's diff to the kernel parameters (init arguments after
+/// `--` are left alone) and return what changed
+pub fn apply(profile: &Profile, params: &mut Vec<String>) -> ActiveProfile {
+    let init_args = params.split_off(grub_config::kernel_parameter_count(params));
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+
+    for pattern in &profile.remove {
+        params.retain(|p| {
+            let hit = matches(p, pattern);
+            if hit {
+                removed.push(p.clone());
+            }
+            !hit
+        });
+    }
+    for param in &profile.add {
+        if params.contains(param) {
+            continue;
+        }
+        let (name, _) = grub_config::split_parameter(param);
+        if !repeatable(&name) {
+            // Replace the current value, e.g. mitigations=auto
+            params.retain(|p| {
+                let hit = grub_config::split_parameter(p).0 == name;
+                if hit {
+                    removed.push(p.clone());
+                }
+                !hit
+            });
+        }
+        params.push(param.clone());
+        added.push(param.clone());
+    }
+
+    params.extend(init_args);
+    ActiveProfile {
+        name: profile.name.clone(),
+        key: profile.key.clone(),
+        added,
+        removed,
+    }
+}
+
+/// Undo an applied profile. Parameters edited by hand since then win: an
+/// added parameter that was changed stays, and a removed one is only put
+/// back if nothing took its place.
+pub fn revert(active: &ActiveProfile, params: &mut Vec<String>) {
+    let init_args = params.split_off(grub_config::kernel_parameter_count(params));
+    for param in &active.added {
+        if let Some(idx) = params.iter().position(|p| p == param) {
+            params.remove(idx);
+        }
+    }
+    for param in &active.removed {
+        let (name, _) = grub_config::split_parameter(param);
+        let present = if repeatable(&name) {
+            params.contains(param)
+        } else {
+            params.iter().any(|p| grub_config::split_parameter(p).0 == name)
+        };
+        if !present {
+            params.push(param.clone());
+        }
+    }
+    params.extend(init_args);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|p| p.to_string()).collect()
+    }
+
+    fn profile(name: &str) -> Profile {
+        built_in().into_iter().find(|p| p.name == name).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut line = params("root=UUID=1234 ro quiet splash");
+        let active = apply(&profile("debug"), &mut line);
+        assert_eq!(line, params("root=UUID=1234 ro debug ignore_loglevel no_console_suspend"));
+        assert_eq!(active.added, params("debug ignore_loglevel no_console_suspend"));
+        assert_eq!(active.removed, params("quiet splash"));
+        revert(&active, &mut line);
+        assert_eq!(line, params("root=UUID=1234 ro quiet splash"));
+
+        // Removed parameters are put back at the end
+        let mut line = params("quiet root=UUID=1234");
+        let active = apply(&profile("debug"), &mut line);
+        revert(&active, &mut line);
+        assert_eq!(line, params("root=UUID=1234 quiet"));
+    }
+
+    #[test]
+    fn value_replaced() {
+        let mut line = params("quiet mitigations=auto");
+        let active = apply(&profile("perf"), &mut line);
+        assert_eq!(line, params("quiet mitigations=off"));
+        assert_eq!(active.removed, params("mitigations=auto"));
+        revert(&active, &mut line);
+        assert_eq!(line, params("quiet mitigations=auto"));
+
+        // Already there: nothing to undo
+        let mut line = params("mitigations=off");
+        let active = apply(&profile("perf"), &mut line);
+        assert!(active.added.is_empty() && active.removed.is_empty());
+
+        // Repeatable parameters are added next to the current ones
+        let serial = Profile {
+            name: "serial".to_string(),
+            description: String::new(),
+            key: default_key(),
+            add: params("console=ttyS0,115200n8"),
+            remove: Vec::new(),
+        };
+        let mut line = params("console=tty0");
+        let active = apply(&serial, &mut line);
+        assert_eq!(line, params("console=tty0 console=ttyS0,115200n8"));
+        revert(&active, &mut line);
+        assert_eq!(line, params("console=tty0"));
+    }
+
+    #[test]
+    fn hand_edits_win() {
+        let mut line = params("quiet mitigations=auto");
+        let active = apply(&profile("perf"), &mut line);
+        // Changed by hand after the profile was applied
+        line = params("quiet mitigations=auto,nosmt");
+        revert(&active, &mut line);
+        assert_eq!(line, params("quiet mitigations=auto,nosmt"));
+
+        let mut line = params("quiet splash");
+        let active = apply(&profile("debug"), &mut line);
+        line = params("quiet ignore_loglevel no_console_suspend");
+        revert(&active, &mut line);
+        assert_eq!(line, params("quiet splash"));
+    }
+
+    #[test]
+    fn init_arguments_untouched() {
+        let mut line = params("ro quiet -- quiet single");
+        let active = apply(&profile("debug"), &mut line);
+        assert_eq!(line, params("ro debug ignore_loglevel no_console_suspend -- quiet single"));
+        assert_eq!(active.removed, params("quiet"));
+        revert(&active, &mut line);
+        assert_eq!(line, params("ro quiet -- quiet single"));
+    }
+}
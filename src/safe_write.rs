@@ -0,0 +1,257 @@
+// Crash-safe writes for the files grublist manages.
+//
+// A file is never rewritten in place: the new content goes to a temporary
+// file in the same directory, which gets the mode, owner and SELinux label
+// of the file it replaces, is flushed to disk and renamed over it. A crash
+// leaves either the old or the new file, never a truncated one. Changes are
+// made while holding an advisory lock, so two grublist instances can't
+// interleave their edits.
+
+use std::ffi::CString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{fchown, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::paths::Paths;
+
+// How long to wait for another instance to finish (grub-mkconfig can take a while)
+const LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+const SELINUX_XATTR: &str = "security.selinux";
+
+/// Exclusive lock on the managed system, released when dropped
+pub struct Lock {
+    _file: File,
+}
+
+/// Wait for other grublist instances to finish changing files. The lock is
+/// not re-entrant: each call opens the lock file again, so a second call
+/// while this process still holds a `Lock` waits for itself and times out.
+pub fn lock(paths: &Paths) -> io::Result<Lock> {
+    lock_with_timeout(paths, LOCK_TIMEOUT)
+}
+
+fn lock_with_timeout(paths: &Paths, timeout: Duration) -> io::Result<Lock> {
+    let path = paths.lock_file();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(&path)?;
+
+    let start = Instant::now();
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+            return Ok(Lock { _file: file });
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::WouldBlock {
+            return Err(err);
+        }
+        if start.elapsed() > timeout {
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, format!(
+                "another grublist is changing the configuration ({} is locked)", paths.display(&path))));
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Replace `path` with `content`. A new file gets mode 0644.
+pub fn write(path: &Path, content: impl AsRef<[u8]>) -> io::Result<()> {
+    write_with_mode(path, content, 0o644)
+}
+
+/// Replace `path` with `content`, keeping the attributes of the file being
+/// replaced. A new file gets `mode`.
+pub fn write_with_mode(path: &Path, content: impl AsRef<[u8]>, mode: u32) -> io::Result<()> {
+    let path = target(path);
+    let temp = temp_path(&path);
+    let result = (|| {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&temp)?;
+        file.write_all(content.as_ref())?;
+        match fs::metadata(&path) {
+            Ok(_) => copy_attributes(&path, &file)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => file.set_permissions(fs::Permissions::from_mode(mode))?,
+            Err(e) => return Err(e),
+        }
+        file.sync_all()
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    rename(&temp, &path)
+}
+
+/// Rename a file that was written next to `path` over it, with the
+/// attributes of the file being replaced
+pub fn replace(temp: &Path, path: &Path) -> io::Result<()> {
+    let path = target(path);
+    let file = File::open(temp)?;
+    if path.exists() {
+        copy_attributes(&path, &file)?;
+    }
+    file.sync_all()?;
+    rename(temp, &path)
+}
+
+// Write through a symlink instead of replacing it
+fn target(path: &Path) -> PathBuf {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_symlink() => fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let temp = path.with_file_name(format!(".{}.grublist-tmp", name));
+    // Left over from a crash, the lock keeps other instances away from it
+    let _ = fs::remove_file(&temp);
+    temp
+}
+
+// Rename and flush the directory, so the rename itself survives a crash
+fn rename(temp: &Path, path: &Path) -> io::Result<()> {
+    if let Err(e) = fs::rename(temp, path) {
+        let _ = fs::remove_file(temp);
+        return Err(e);
+    }
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+fn copy_attributes(from: &Path, file: &File) -> io::Result<()> {
+    let meta = fs::metadata(from)?;
+    file.set_permissions(meta.permissions())?;
+    let current = file.metadata()?;
+    if (current.uid(), current.gid()) != (meta.uid(), meta.gid()) {
+        fchown(file, Some(meta.uid()), Some(meta.gid()))?;
+    }
+    if let Some(label) = selinux_label(from)? {
+        set_selinux_label(file, &label)?;
+    }
+    Ok(())
+}
+
+// None on systems without SELinux and on filesystems without labels
+fn selinux_label(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let name = CString::new(SELINUX_XATTR)?;
+    let mut buf = vec![0u8; 256];
+    loop {
+        let len = unsafe {
+            libc::getxattr(path.as_ptr(), name.as_ptr(), buf.as_mut_ptr().cast(), buf.len())
+        };
+        if len >= 0 {
+            buf.truncate(len as usize);
+            return Ok(Some(buf));
+        }
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::ERANGE) => buf.resize(buf.len() * 4, 0),
+            Some(libc::ENODATA) | Some(libc::ENOTSUP) => return Ok(None),
+            _ => return Err(err),
+        }
+    }
+}
+
+fn set_selinux_label(file: &File, label: &[u8]) -> io::Result<()> {
+    let name = CString::new(SELINUX_XATTR)?;
+    let ret = unsafe {
+        libc::fsetxattr(file.as_raw_fd(), name.as_ptr(), label.as_ptr().cast(), label.len(), 0)
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("grublist-test-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o7777
+    }
+
+    #[test]
+    fn mode_kept() {
+        let root = temp_root("write-mode");
+        let path = root.join("grub");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        write_with_mode(&path, "new", 0o755).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(mode(&path), 0o600);
+
+        // A new file gets the given mode
+        let script = root.join("42_grublist");
+        write_with_mode(&script, "#!/bin/sh\n", 0o755).unwrap();
+        assert_eq!(mode(&script), 0o755);
+
+        // Through a symlink, the file it points to is replaced
+        let link = root.join("link");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        write(&link, "linked").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "linked");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn temp_file_removed_on_error() {
+        let root = temp_root("write-error");
+        // A file can't be renamed over a directory that isn't empty
+        let path = root.join("grub.d");
+        fs::create_dir_all(path.join("10_linux")).unwrap();
+
+        assert!(write(&path, "content").is_err());
+        assert!(path.is_dir());
+        assert!(!temp_path(&path).exists());
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 1);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn lock_times_out() {
+        let root = temp_root("lock");
+        let paths = Paths::new(&root);
+
+        let held = lock(&paths).unwrap();
+        // Not re-entrant, even within one process
+        let err = lock_with_timeout(&paths, Duration::from_millis(200)).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert!(err.to_string().contains("another grublist is changing the configuration"));
+
+        drop(held);
+        assert!(lock_with_timeout(&paths, Duration::from_millis(200)).is_ok());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,332 @@
+// Trial changes, rolled back unless a boot is confirmed.
+//
+// A trial keeps the backup taken before a change as its fallback. The
+// backup's grub.cfg, minus any counter of an earlier trial, is copied next
+// to grub.cfg as grublist-fallback.cfg, and /etc/grub.d/01_grublist_trial
+// puts a boot counter at the top of grub.cfg: grubenv's grublist_trial
+// holds the boots left, GRUB counts it down with save_env, and once none
+// are left it marks grubenv with "fallback" and loads the fallback
+// configuration instead. A boot that gets to multi-user.target runs
+// `grublist confirm-boot` from grublist-confirm-boot.service: after a
+// fallback boot it restores the backup's settings and grub.cfg, otherwise
+// the change is kept. Either way the trial ends there and grub.cfg is
+// regenerated without the counter.
+//
+// GRUB can't write grubenv on LVM, RAID, btrfs or encrypted /boot; the
+// counter never runs down there.
+
+use std::fs;
+use std::io;
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::backup_manager::{self, BackupInfo, Component};
+use crate::grub_mkconfig;
+use crate::grubenv::{self, GrubEnv};
+use crate::paths::Paths;
+use crate::safe_write;
+
+/// grubenv variable with the boots left, or "fallback"
+pub const VAR: &str = "grublist_trial";
+const FALLBACK: &str = "fallback";
+
+pub const DEFAULT_BOOTS: u32 = 2;
+// GRUB script has no arithmetic, the countdown is spelled out
+pub const MAX_BOOTS: u32 = 9;
+
+const UNIT: &str = "grublist-confirm-boot.service";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trial {
+    // ID of the backup restored if no boot is confirmed
+    pub backup: String,
+    pub boots: u32,
+    // RFC 3339
+    pub started: String,
+    // What the backup was taken before
+    pub action: String,
+}
+
+pub enum Outcome {
+    NoTrial,
+    Confirmed(Trial),
+    RolledBack(Trial),
+}
+
+/// Where a running trial stands, from grubenv
+pub enum Status {
+    BootsLeft(u32),
+    // GRUB loaded the fallback configuration
+    FellBack,
+    // grublist_trial is missing from grubenv
+    Unknown,
+}
+
+pub fn load(paths: &Paths) -> Option<Trial> {
+    fs::read_to_string(paths.trial_state())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+}
+
+pub fn status(env: &GrubEnv) -> Status {
+    match env.get(VAR) {
+        Some(FALLBACK) => Status::FellBack,
+        Some(value) => value.parse().map(Status::BootsLeft).unwrap_or(Status::Unknown),
+        None => Status::Unknown,
+    }
+}
+
+/// Whether a trial of `boots` boots can start, checked before making the change
+pub fn check(paths: &Paths, boots: u32) -> Result<(), String> {
+    if !(1..=MAX_BOOTS).contains(&boots) {
+        return Err(format!("A trial can last 1 to {} boots", MAX_BOOTS));
+    }
+    if let Some(trial) = load(paths) {
+        return Err(format!("A trial of '{}' is already running, confirm or roll it back first", trial.action));
+    }
+    Ok(())
+}
+
+/// Start a trial with `backup` as the fallback: install the counter,
+/// regenerate grub.cfg and give the change `boots` boots to be confirmed
+pub fn start(paths: &Paths, backup: &BackupInfo, boots: u32) -> Result<Trial, String> {
+    check(paths, boots)?;
+    let manifest = backup.manifest.as_ref()
+        .ok_or_else(|| format!("{} is a legacy backup without grub.cfg", paths.display(&backup.path)))?;
+    let grub_cfg = backup_manager::read_file(paths, &backup.path, &paths.grub_cfg())
+        .map_err(|e| format!("Failed to read backup {}: {}", manifest.id, e))?;
+
+    let trial = Trial {
+        backup: manifest.id.clone(),
+        boots,
+        started: chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        action: manifest.action.clone(),
+    };
+    install(paths, &trial, &without_counter(paths, &grub_cfg))
+        .map_err(|e| format!("Failed to set up the trial: {}", e))?;
+    if let Err(e) = grub_mkconfig::apply(paths) {
+        let _ = remove_files(paths);
+        return Err(e);
+    }
+    // Armed last, a failure before leaves GRUB's counter unset
+    grubenv::update(paths, |env| env.set(VAR, &boots.to_string()))?;
+    Ok(trial)
+}
+
+/// Run once a boot got far enough: keep the change, or restore the backup
+/// if GRUB had to fall back to it
+pub fn confirm_boot(paths: &Paths) -> Result<Outcome, String> {
+    let Some(trial) = load(paths) else {
+        return Ok(Outcome::NoTrial);
+    };
+    let env = GrubEnv::load(paths)?;
+    if matches!(status(&env), Status::FellBack) {
+        restore(paths, &trial)?;
+        finish(paths)?;
+        Ok(Outcome::RolledBack(trial))
+    } else {
+        finish(paths)?;
+        Ok(Outcome::Confirmed(trial))
+    }
+}
+
+/// Restore the backup now and end the trial
+pub fn rollback(paths: &Paths) -> Result<Trial, String> {
+    let trial = load(paths).ok_or_else(|| "No trial is running".to_string())?;
+    restore(paths, &trial)?;
+    finish(paths)?;
+    Ok(trial)
+}
+
+// Only the settings and grub.cfg go back. grubenv holds what GRUB wrote
+// since the backup (the trial counter, saved_entry, boot_success, ...).
+fn restore(paths: &Paths, trial: &Trial) -> Result<(), String> {
+    let backup = backup_manager::find_backup(paths, &trial.backup)
+        .ok_or_else(|| format!("Backup {} no longer exists", trial.backup))?;
+    let components: Vec<Component> = backup.components().into_iter()
+        .filter(|c| matches!(c, Component::Settings | Component::GrubCfg))
+        .collect();
+    backup_manager::restore_backup(paths, &backup.path, &components)
+        .map_err(|e| format!("Failed to restore backup {}: {}", trial.backup, e))
+}
+
+// Stop the counter, remove the trial's files and regenerate grub.cfg
+// without the counter, so backups taken from now on don't hold it
+fn finish(paths: &Paths) -> Result<(), String> {
+    grubenv::update(paths, |env| env.unset(VAR))?;
+    remove_files(paths).map_err(|e| format!("Failed to remove the trial files: {}", e))?;
+    grub_mkconfig::apply(paths)
+        .map(|_| ())
+        .map_err(|e| format!("The trial ended, but grub.cfg still has its counter (it does nothing now): {}", e))
+}
+
+// grub.cfg without the section 01_grublist_trial generated. A backup taken
+// while an earlier trial's counter was still in grub.cfg would otherwise
+// load the fallback again from the fallback, in a loop.
+fn without_counter(paths: &Paths, grub_cfg: &[u8]) -> Vec<u8> {
+    let script = paths.display(&paths.trial_script());
+    let begin = format!("### BEGIN {} ###", script);
+    let end = format!("### END {} ###", script);
+    let mut out = Vec::with_capacity(grub_cfg.len());
+    let mut in_counter = false;
+    for line in grub_cfg.split_inclusive(|&b| b == b'\n') {
+        let text = String::from_utf8_lossy(line);
+        let text = text.trim_end();
+        if text == begin {
+            in_counter = true;
+        } else if in_counter {
+            in_counter = text != end;
+        } else {
+            out.extend_from_slice(line);
+        }
+    }
+    out
+}
+
+fn install(paths: &Paths, trial: &Trial, grub_cfg: &[u8]) -> io::Result<()> {
+    let _lock = safe_write::lock(paths)?;
+    // Like grub.cfg, which may hold password hashes
+    let mode = fs::metadata(paths.grub_cfg()).map(|m| m.permissions().mode() & 0o7777).unwrap_or(0o600);
+    safe_write::write_with_mode(&paths.trial_fallback_cfg(), grub_cfg, mode)?;
+    fs::create_dir_all(paths.grub_d())?;
+    safe_write::write_with_mode(&paths.trial_script(), script(), 0o755)?;
+    install_unit(paths)?;
+
+    let state = paths.trial_state();
+    if let Some(dir) = state.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let content = serde_json::to_string_pretty(trial).map_err(io::Error::other)?;
+    safe_write::write(&state, content)
+}
+
+fn remove_files(paths: &Paths) -> io::Result<()> {
+    let _lock = safe_write::lock(paths)?;
+    for file in [paths.trial_script(), paths.trial_fallback_cfg(), paths.trial_state()] {
+        match fs::remove_file(&file) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Enabled like `systemctl enable` would, so it also works with --root. The
+// unit stays installed, it only runs while a trial is.
+fn install_unit(paths: &Paths) -> io::Result<()> {
+    let dir = paths.systemd_unit_dir();
+    let unit = dir.join(UNIT);
+    let exe = installed_exe(paths)?;
+    let content = format!("\
+[Unit]
+Description=Confirm a successful boot for a grublist trial change
+After=multi-user.target
+ConditionPathExists={}
+
+[Service]
+Type=oneshot
+ExecStart={} confirm-boot
+
+[Install]
+WantedBy=multi-user.target
+", paths.display(&paths.trial_state()), exe);
+    fs::create_dir_all(&dir)?;
+    safe_write::write(&unit, content)?;
+
+    let wants = dir.join("multi-user.target.wants");
+    let link = wants.join(UNIT);
+    if fs::symlink_metadata(&link).is_err() {
+        fs::create_dir_all(&wants)?;
+        symlink(Path::new("/etc/systemd/system").join(UNIT), &link)?;
+    }
+    Ok(())
+}
+
+// grublist as the managed system runs it: this binary on the running
+// system, otherwise the first one found in the usual places under --root
+fn installed_exe(paths: &Paths) -> io::Result<String> {
+    if paths.is_host() {
+        return Ok(std::env::current_exe()?.display().to_string());
+    }
+    let candidates = ["/usr/local/sbin", "/usr/local/bin", "/usr/sbin", "/usr/bin", "/root/.cargo/bin"];
+    Ok(candidates.iter()
+        .map(|dir| format!("{}/grublist", dir))
+        .find(|exe| paths.resolve(exe).is_file())
+        .unwrap_or_else(|| "/usr/bin/grublist".to_string()))
+}
+
+fn script() -> String {
+    let mut script = format!(r#"#!/bin/sh
+exec tail -n +3 $0
+# Boot counter of a grublist trial change. grubenv's {var} holds the
+# boots left; when none are left, grub.cfg from before the change is loaded.
+if [ "${{{var}}}" = "0" ]; then
+  set {var}={fallback}
+  save_env {var}
+fi
+if [ "${{{var}}}" = "{fallback}" ]; then
+  configfile "${{config_directory}}/grublist-fallback.cfg"
+"#, var = VAR, fallback = FALLBACK);
+    for boots in 1..=MAX_BOOTS {
+        script.push_str(&format!(r#"elif [ "${{{var}}}" = "{boots}" ]; then
+  set {var}={left}
+  save_env {var}
+"#, var = VAR, boots = boots, left = boots - 1));
+    }
+    script.push_str("fi\n");
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // grub.cfg as grub-mkconfig writes it with the trial script installed
+    fn generated(paths: &Paths) -> String {
+        let name = paths.display(&paths.trial_script());
+        // The script prints itself from its third line on
+        let counter: String = script().lines().skip(2).map(|l| format!("{}\n", l)).collect();
+        format!("set default=\"0\"\n\
+            ### BEGIN {name} ###\n{counter}### END {name} ###\n\
+            \n\
+            ### BEGIN /etc/grub.d/10_linux ###\n\
+            menuentry 'Ubuntu' {{\n\tlinux /boot/vmlinuz root=UUID=1234 ro\n}}\n\
+            ### END /etc/grub.d/10_linux ###\n")
+    }
+
+    #[test]
+    fn fallback_has_no_counter() {
+        let paths = Paths::new("/mnt/target");
+        let grub_cfg = generated(&paths);
+        assert!(grub_cfg.contains("configfile"));
+
+        let fallback = String::from_utf8(without_counter(&paths, grub_cfg.as_bytes())).unwrap();
+        assert!(!fallback.contains(VAR));
+        assert!(!fallback.contains("configfile"));
+        assert_eq!(fallback, "set default=\"0\"\n\
+            \n\
+            ### BEGIN /etc/grub.d/10_linux ###\n\
+            menuentry 'Ubuntu' {\n\tlinux /boot/vmlinuz root=UUID=1234 ro\n}\n\
+            ### END /etc/grub.d/10_linux ###\n");
+        let menu = crate::grub::parse_grub(&fallback, &[]);
+        assert_eq!(menu.children.len(), 1);
+    }
+
+    #[test]
+    fn grub_cfg_without_counter_is_unchanged() {
+        let paths = Paths::new("/");
+        let grub_cfg = b"menuentry 'Ubuntu' {\n}\n# no newline at the end";
+        assert_eq!(without_counter(&paths, grub_cfg), grub_cfg.to_vec());
+    }
+
+    #[test]
+    fn unit_runs_grublist_of_the_target() {
+        let root = std::env::temp_dir().join(format!("grublist-test-trial-{}", std::process::id()));
+        let paths = Paths::new(&root);
+        assert_eq!(installed_exe(&paths).unwrap(), "/usr/bin/grublist");
+        fs::create_dir_all(paths.resolve("/usr/local/bin")).unwrap();
+        fs::write(paths.resolve("/usr/local/bin/grublist"), "").unwrap();
+        assert_eq!(installed_exe(&paths).unwrap(), "/usr/local/bin/grublist");
+        fs::remove_dir_all(&root).unwrap();
+    }
+}